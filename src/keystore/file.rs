@@ -0,0 +1,124 @@
+/*
+    Multisig Schnorr
+
+    Copyright 2018 by Kzen Networks
+
+    This file is part of Multisig Schnorr library
+    (https://github.com/KZen-networks/multisig-schnorr)
+
+    Multisig Schnorr is free software: you can redistribute
+    it and/or modify it under the terms of the GNU General Public
+    License as published by the Free Software Foundation, either
+    version 3 of the License, or (at your option) any later version.
+
+    @license GPL-3.0+ <https://github.com/KZen-networks/multisig-schnorr/blob/master/LICENSE>
+*/
+
+//! File-backed `KeyStore`: one encrypted envelope per entry in a single directory.
+use std::fs;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+use curv::BigInt;
+
+use keystore::{
+    load_ledger, valid_id, Entry, Envelope, KdfParams, KeyStore, KeyStoreError, NONCE_LEDGER_ID,
+};
+use protocols::secret::Secret;
+
+const EXTENSION: &str = "key";
+
+pub struct FileKeyStore {
+    dir: PathBuf,
+    passphrase: Secret<Vec<u8>>,
+    kdf: KdfParams,
+}
+
+impl FileKeyStore {
+    pub fn open<P: AsRef<Path>>(dir: P, passphrase: &[u8]) -> Result<FileKeyStore, KeyStoreError> {
+        FileKeyStore::open_with_params(dir, passphrase, KdfParams::default())
+    }
+
+    pub fn open_with_params<P: AsRef<Path>>(
+        dir: P,
+        passphrase: &[u8],
+        kdf: KdfParams,
+    ) -> Result<FileKeyStore, KeyStoreError> {
+        fs::create_dir_all(dir.as_ref())?;
+        Ok(FileKeyStore {
+            dir: dir.as_ref().to_path_buf(),
+            passphrase: Secret::new(passphrase.to_vec()),
+            kdf,
+        })
+    }
+
+    fn path(&self, id: &str) -> Result<PathBuf, KeyStoreError> {
+        if !valid_id(id) {
+            return Err(KeyStoreError::InvalidId);
+        }
+        Ok(self.dir.join(format!("{}.{}", id, EXTENSION)))
+    }
+
+    // the envelope is written to a temporary file and renamed into place,
+    // so a crash leaves either the old or the new record, never a torn one
+    fn write(&self, id: &str, entry: &Entry) -> Result<(), KeyStoreError> {
+        let path = self.path(id)?;
+        let envelope = Envelope::seal(id, entry, self.passphrase.expose(), self.kdf)?;
+        let bytes = serde_json::to_vec(&envelope).map_err(|_| KeyStoreError::Serialization)?;
+
+        let tmp_path = path.with_extension("tmp");
+        let mut tmp = fs::File::create(&tmp_path)?;
+        tmp.write_all(&bytes)?;
+        tmp.sync_all()?;
+        fs::rename(&tmp_path, &path)?;
+        Ok(())
+    }
+}
+
+impl KeyStore for FileKeyStore {
+    fn store(&mut self, id: &str, entry: &Entry) -> Result<(), KeyStoreError> {
+        if id == NONCE_LEDGER_ID {
+            return Err(KeyStoreError::InvalidId);
+        }
+        self.write(id, entry)
+    }
+
+    fn load(&self, id: &str) -> Result<Entry, KeyStoreError> {
+        let bytes = fs::read(self.path(id)?)?;
+        let envelope: Envelope =
+            serde_json::from_slice(&bytes).map_err(|_| KeyStoreError::Serialization)?;
+        envelope.open(id, self.passphrase.expose())
+    }
+
+    fn remove(&mut self, id: &str) -> Result<(), KeyStoreError> {
+        if id == NONCE_LEDGER_ID {
+            return Err(KeyStoreError::InvalidId);
+        }
+        fs::remove_file(self.path(id)?)?;
+        Ok(())
+    }
+
+    fn ids(&self) -> Result<Vec<String>, KeyStoreError> {
+        let mut ids = Vec::new();
+        for dir_entry in fs::read_dir(&self.dir)? {
+            let path = dir_entry?.path();
+            if path.extension().and_then(|ext| ext.to_str()) != Some(EXTENSION) {
+                continue;
+            }
+            if let Some(id) = path.file_stem().and_then(|stem| stem.to_str()) {
+                ids.push(id.to_string());
+            }
+        }
+        ids.sort();
+        Ok(ids)
+    }
+
+    fn consume_nonces(&mut self, nonces: &[BigInt]) -> Result<(), KeyStoreError> {
+        let mut ledger = load_ledger(self)?;
+        if nonces.iter().any(|nonce| ledger.contains(nonce)) {
+            return Err(KeyStoreError::NonceReused);
+        }
+        ledger.extend(nonces.iter().cloned());
+        self.write(NONCE_LEDGER_ID, &Entry::NonceLedger(ledger))
+    }
+}
@@ -0,0 +1,338 @@
+/*
+    Multisig Schnorr
+
+    Copyright 2018 by Kzen Networks
+
+    This file is part of Multisig Schnorr library
+    (https://github.com/KZen-networks/multisig-schnorr)
+
+    Multisig Schnorr is free software: you can redistribute
+    it and/or modify it under the terms of the GNU General Public
+    License as published by the Free Software Foundation, either
+    version 3 of the License, or (at your option) any later version.
+
+    @license GPL-3.0+ <https://github.com/KZen-networks/multisig-schnorr/blob/master/LICENSE>
+*/
+
+//! Persistent storage for long-term keys, threshold shares and in-flight signing sessions.
+//!
+//! Secrets are encrypted at rest with a key derived from a passphrase (scrypt) and sealed
+//! with ChaCha20-Poly1305. The entry id is bound to the ciphertext as associated data, so
+//! records cannot be swapped between ids without detection.
+use std::fmt;
+use std::io;
+
+use chacha20poly1305::aead::{Aead, NewAead, Payload};
+use chacha20poly1305::{ChaCha20Poly1305, Key, Nonce};
+use rand::{thread_rng, RngCore};
+use scrypt::{scrypt, ScryptParams};
+
+use curv::elliptic::curves::traits::*;
+use curv::BigInt;
+
+use protocols::aggsig::musig_two_rounds;
+use protocols::aggsig::oracle::{Attestation, Oracle, OracleNonce};
+use protocols::multisig;
+use protocols::secret::Secret;
+use protocols::thresholdsig::presign::{self, PreSignature, PreSignaturePool};
+use protocols::thresholdsig::zilliqa_schnorr::{LocalSig, Share, SharedKeys};
+use Error;
+
+pub mod file;
+pub use self::file::FileKeyStore;
+
+mod record;
+use self::record::EntryRecord;
+
+mod test;
+
+const KEY_LEN: usize = 32;
+const SALT_LEN: usize = 16;
+const NONCE_LEN: usize = 12;
+const ENVELOPE_VERSION: u32 = 1;
+
+/// reserved id under which the ledger of consumed signing nonces is kept
+pub const NONCE_LEDGER_ID: &str = "nonce-ledger";
+
+#[derive(Debug)]
+pub enum KeyStoreError {
+    Io(io::Error),
+    InvalidId,
+    NotFound,
+    Serialization,
+    KeyDerivation,
+    Encryption,
+    Decryption,
+    UnexpectedEntry,
+    NonceReused,
+    Protocol(Error),
+}
+
+impl fmt::Display for KeyStoreError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{:?}", &self)
+    }
+}
+
+impl std::error::Error for KeyStoreError {}
+
+impl From<io::Error> for KeyStoreError {
+    fn from(e: io::Error) -> KeyStoreError {
+        if e.kind() == io::ErrorKind::NotFound {
+            KeyStoreError::NotFound
+        } else {
+            KeyStoreError::Io(e)
+        }
+    }
+}
+
+/// Everything the store knows how to persist. The key types are not `Serialize`; an entry is
+/// exported field by field into a private record when it is sealed.
+#[derive(Debug, Clone)]
+pub enum Entry {
+    /// long-term MuSig key
+    MuSigKey(musig_two_rounds::KeyPair),
+    /// long-term ASM multisig keys
+    MultisigKeys(multisig::Keys),
+    /// threshold share together with the `vss_scheme_vec` it was verified against
+    ThresholdShare(Share),
+    /// MuSig2 signer state after round 1, before the signature share was released
+    MuSigSession(musig_two_rounds::State),
+    /// compressed ephemeral public keys of every session that released a signature share
+    NonceLedger(Vec<BigInt>),
+    /// threshold ephemeral keys generated ahead of signing
+    PreSignaturePool(PreSignaturePool),
+    /// nonce of an announced oracle event that is not attested yet
+    OracleNonce(OracleNonce),
+}
+
+/// scrypt cost parameters, stored next to every record so they can be raised later
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct KdfParams {
+    pub log_n: u8,
+    pub r: u32,
+    pub p: u32,
+}
+
+impl Default for KdfParams {
+    fn default() -> KdfParams {
+        KdfParams {
+            log_n: 15,
+            r: 8,
+            p: 1,
+        }
+    }
+}
+
+/// On-disk form of a single record.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Envelope {
+    pub version: u32,
+    pub kdf: KdfParams,
+    pub salt: Vec<u8>,
+    pub nonce: Vec<u8>,
+    pub ciphertext: Vec<u8>,
+}
+
+impl Envelope {
+    pub fn seal(
+        id: &str,
+        entry: &Entry,
+        passphrase: &[u8],
+        kdf: KdfParams,
+    ) -> Result<Envelope, KeyStoreError> {
+        let mut salt = vec![0u8; SALT_LEN];
+        let mut nonce = vec![0u8; NONCE_LEN];
+        thread_rng().fill_bytes(&mut salt);
+        thread_rng().fill_bytes(&mut nonce);
+
+        let key = derive_key(passphrase, &salt, &kdf)?;
+        let plaintext = Secret::new(
+            serde_json::to_vec(&EntryRecord::from(entry))
+                .map_err(|_| KeyStoreError::Serialization)?,
+        );
+        let cipher = ChaCha20Poly1305::new(Key::from_slice(key.expose()));
+        let ciphertext = cipher
+            .encrypt(
+                Nonce::from_slice(&nonce),
+                Payload {
+                    msg: plaintext.expose(),
+                    aad: id.as_bytes(),
+                },
+            )
+            .map_err(|_| KeyStoreError::Encryption)?;
+
+        Ok(Envelope {
+            version: ENVELOPE_VERSION,
+            kdf,
+            salt,
+            nonce,
+            ciphertext,
+        })
+    }
+
+    pub fn open(&self, id: &str, passphrase: &[u8]) -> Result<Entry, KeyStoreError> {
+        if self.version != ENVELOPE_VERSION || self.nonce.len() != NONCE_LEN {
+            return Err(KeyStoreError::Serialization);
+        }
+        let key = derive_key(passphrase, &self.salt, &self.kdf)?;
+        let cipher = ChaCha20Poly1305::new(Key::from_slice(key.expose()));
+        // a wrong passphrase and a tampered record are indistinguishable here
+        let plaintext = Secret::new(
+            cipher
+                .decrypt(
+                    Nonce::from_slice(&self.nonce),
+                    Payload {
+                        msg: &self.ciphertext,
+                        aad: id.as_bytes(),
+                    },
+                )
+                .map_err(|_| KeyStoreError::Decryption)?,
+        );
+        let record: EntryRecord =
+            serde_json::from_slice(plaintext.expose()).map_err(|_| KeyStoreError::Serialization)?;
+        Ok(Entry::from(record))
+    }
+}
+
+fn derive_key(
+    passphrase: &[u8],
+    salt: &[u8],
+    kdf: &KdfParams,
+) -> Result<Secret<Vec<u8>>, KeyStoreError> {
+    let params =
+        ScryptParams::new(kdf.log_n, kdf.r, kdf.p).map_err(|_| KeyStoreError::KeyDerivation)?;
+    let mut key = vec![0u8; KEY_LEN];
+    let derived = scrypt(passphrase, salt, &params, &mut key);
+    // wrapped before the result is checked, so a failed derivation is zeroized as well
+    let key = Secret::new(key);
+    derived.map_err(|_| KeyStoreError::KeyDerivation)?;
+    Ok(key)
+}
+
+/// Storage backend. Implementations only need to move entries in and out;
+/// session bookkeeping is provided on top of that.
+pub trait KeyStore {
+    fn store(&mut self, id: &str, entry: &Entry) -> Result<(), KeyStoreError>;
+
+    fn load(&self, id: &str) -> Result<Entry, KeyStoreError>;
+
+    fn remove(&mut self, id: &str) -> Result<(), KeyStoreError>;
+
+    fn ids(&self) -> Result<Vec<String>, KeyStoreError>;
+
+    /// Adds nonces to the ledger of consumed nonces under `NONCE_LEDGER_ID`, failing with
+    /// `NonceReused` if one of them is in it already. The ledger only grows: `store` and
+    /// `remove` refuse the reserved id.
+    fn consume_nonces(&mut self, nonces: &[BigInt]) -> Result<(), KeyStoreError>;
+
+    fn store_session(
+        &mut self,
+        id: &str,
+        state: &musig_two_rounds::State,
+    ) -> Result<(), KeyStoreError> {
+        self.store(id, &Entry::MuSigSession(state.clone()))
+    }
+
+    fn load_session(&self, id: &str) -> Result<musig_two_rounds::State, KeyStoreError> {
+        match self.load(id)? {
+            Entry::MuSigSession(state) => Ok(state),
+            _ => Err(KeyStoreError::UnexpectedEntry),
+        }
+    }
+
+    /// Hands out a stored session for the second signing round exactly once.
+    /// The session nonces are written to the nonce ledger before the state is returned,
+    /// so a crash after this call can lose the session but never reuse its nonces.
+    fn consume_session(&mut self, id: &str) -> Result<musig_two_rounds::State, KeyStoreError> {
+        let state = self.load_session(id)?;
+        let session_nonces = state
+            .ephk_vec
+            .iter()
+            .map(|ephk| ephk.keypair.public_key.bytes_compressed_to_big_int())
+            .collect::<Vec<BigInt>>();
+        self.consume_nonces(&session_nonces)?;
+        self.remove(id)?;
+        Ok(state)
+    }
+
+    /// Hands out a pre-signature from the pool stored under `pool_id` exactly once.
+    /// The id goes into the nonce ledger and the pool is written back without it before
+    /// it is returned, so a restored copy of the pool cannot hand it out again.
+    fn consume_presignature(
+        &mut self,
+        pool_id: &str,
+        presignature_id: &str,
+    ) -> Result<PreSignature, KeyStoreError> {
+        let mut pool = match self.load(pool_id)? {
+            Entry::PreSignaturePool(pool) => pool,
+            _ => return Err(KeyStoreError::UnexpectedEntry),
+        };
+        let used = presign::ledger_key(presignature_id);
+        if load_ledger(self)?.contains(&used) {
+            return Err(KeyStoreError::NonceReused);
+        }
+        let presignature = pool.take(presignature_id).ok_or(KeyStoreError::NotFound)?;
+        self.consume_nonces(&[used])?;
+        self.store(pool_id, &Entry::PreSignaturePool(pool))?;
+        Ok(presignature)
+    }
+
+    /// Online signing with a pre-signature of the pool under `pool_id`, the only way to use
+    /// one. The returned pre-signature holds the ephemeral VSS commitments, the quorum and V
+    /// needed to verify and combine the local signatures. Sign with one pre-signature at a
+    /// time, see `presign`.
+    fn sign_with_presignature(
+        &mut self,
+        pool_id: &str,
+        presignature_id: &str,
+        message: &[u8],
+        local_private_key: &SharedKeys,
+    ) -> Result<(LocalSig, PreSignature), KeyStoreError> {
+        let presignature = self.consume_presignature(pool_id, presignature_id)?;
+        let local_sig = LocalSig::compute(message, &presignature.eph_shared_key, local_private_key);
+        Ok((local_sig, presignature))
+    }
+
+    fn store_oracle_nonce(&mut self, id: &str, nonce: &OracleNonce) -> Result<(), KeyStoreError> {
+        self.store(id, &Entry::OracleNonce(nonce.clone()))
+    }
+
+    /// Attests the event whose nonce is stored under `id`, the only way to attest. R_o goes
+    /// into the nonce ledger and the nonce is removed before the attestation is returned, so
+    /// a restored copy of the nonce cannot attest a second outcome.
+    fn attest(
+        &mut self,
+        id: &str,
+        oracle: &Oracle,
+        outcome: &[u8],
+    ) -> Result<Attestation, KeyStoreError> {
+        let nonce = match self.load(id)? {
+            Entry::OracleNonce(nonce) => nonce,
+            _ => return Err(KeyStoreError::UnexpectedEntry),
+        };
+        let attestation = oracle
+            .attest(&nonce, outcome)
+            .map_err(KeyStoreError::Protocol)?;
+        self.consume_nonces(&[nonce.announcement.R_o.bytes_compressed_to_big_int()])?;
+        self.remove(id)?;
+        Ok(attestation)
+    }
+}
+
+// the ledger of consumed nonces, empty before the first one is consumed
+fn load_ledger<K: KeyStore + ?Sized>(store: &K) -> Result<Vec<BigInt>, KeyStoreError> {
+    match store.load(NONCE_LEDGER_ID) {
+        Ok(Entry::NonceLedger(ledger)) => Ok(ledger),
+        Ok(_) => Err(KeyStoreError::UnexpectedEntry),
+        Err(KeyStoreError::NotFound) => Ok(Vec::new()),
+        Err(e) => Err(e),
+    }
+}
+
+fn valid_id(id: &str) -> bool {
+    !id.is_empty()
+        && id
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+}
@@ -0,0 +1,253 @@
+/*
+    Multisig Schnorr
+
+    Copyright 2018 by Kzen Networks
+
+    This file is part of Multisig Schnorr library
+    (https://github.com/KZen-networks/multisig-schnorr)
+
+    Multisig Schnorr is free software: you can redistribute
+    it and/or modify it under the terms of the GNU General Public
+    License as published by the Free Software Foundation, either
+    version 3 of the License, or (at your option) any later version.
+
+    @license GPL-3.0+ <https://github.com/KZen-networks/multisig-schnorr/blob/master/LICENSE>
+*/
+
+//! Serializable records of the entries, built from the explicit exports of the key types.
+//! This is the only place secrets are serialized, and only into a sealed `Envelope`.
+use curv::cryptographic_primitives::secret_sharing::feldman_vss::VerifiableSS;
+use curv::elliptic::curves::traits::*;
+use curv::BigInt;
+
+use keystore::Entry;
+use protocols::aggsig::musig_two_rounds;
+use protocols::aggsig::oracle::{Announcement, OracleNonce};
+use protocols::multisig;
+use protocols::secret::Secret;
+use protocols::thresholdsig::presign::{PreSignature, PreSignaturePool};
+use protocols::thresholdsig::quorum::SigningQuorum;
+use protocols::thresholdsig::zilliqa_schnorr::{Share, SharedKeys};
+
+type GE = curv::elliptic::curves::secp256_k1::GE;
+type FE = curv::elliptic::curves::secp256_k1::FE;
+
+// serde adapter for the secret fields of the records below
+mod exported {
+    use protocols::secret::Secret;
+    use serde::{Deserialize, Deserializer, Serialize, Serializer};
+    use zeroize::Zeroize;
+
+    pub fn serialize<T, S>(secret: &Secret<T>, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        T: Zeroize + Serialize,
+        S: Serializer,
+    {
+        secret.expose().serialize(serializer)
+    }
+
+    pub fn deserialize<'de, T, D>(deserializer: D) -> Result<Secret<T>, D::Error>
+    where
+        T: Zeroize + Deserialize<'de>,
+        D: Deserializer<'de>,
+    {
+        T::deserialize(deserializer).map(Secret::new)
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+pub enum EntryRecord {
+    MuSigKey(SecretKeyRecord),
+    MultisigKeys(SecretKeyRecord, SecretKeyRecord),
+    ThresholdShare(ShareRecord),
+    MuSigSession(SessionRecord),
+    NonceLedger(Vec<BigInt>),
+    PreSignaturePool(PoolRecord),
+    OracleNonce(OracleNonceRecord),
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct SecretKeyRecord {
+    #[serde(with = "exported")]
+    private_key: Secret<FE>,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct SharedKeysRecord {
+    y: GE,
+    #[serde(with = "exported")]
+    x_i: Secret<FE>,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct ShareRecord {
+    id: String,
+    shared_key: SharedKeysRecord,
+    vss_scheme_vec: Vec<VerifiableSS<GE>>,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct EphemeralKeyRecord {
+    key: SecretKeyRecord,
+    commitment: BigInt,
+    #[serde(with = "exported")]
+    blind_factor: Secret<BigInt>,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct SessionRecord {
+    keypair: SecretKeyRecord,
+    ephk_vec: Vec<EphemeralKeyRecord>,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct PreSignatureRecord {
+    id: String,
+    quorum: SigningQuorum,
+    eph_shared_key: SharedKeysRecord,
+    eph_vss_vec: Vec<VerifiableSS<GE>>,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct PoolRecord {
+    presignatures: Vec<PreSignatureRecord>,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct OracleNonceRecord {
+    announcement: Announcement,
+    #[serde(with = "exported")]
+    k: Secret<FE>,
+}
+
+impl<'a> From<&'a Entry> for EntryRecord {
+    fn from(entry: &Entry) -> EntryRecord {
+        match entry {
+            Entry::MuSigKey(keypair) => EntryRecord::MuSigKey(SecretKeyRecord {
+                private_key: keypair.export_private_key(),
+            }),
+            Entry::MultisigKeys(keys) => EntryRecord::MultisigKeys(
+                SecretKeyRecord {
+                    private_key: keys.I.export_private_key(),
+                },
+                SecretKeyRecord {
+                    private_key: keys.X.export_private_key(),
+                },
+            ),
+            Entry::ThresholdShare(share) => EntryRecord::ThresholdShare(ShareRecord {
+                id: share.id.clone(),
+                shared_key: SharedKeysRecord::from(&share.shared_key),
+                vss_scheme_vec: share.vss_scheme_vec.clone(),
+            }),
+            Entry::MuSigSession(state) => EntryRecord::MuSigSession(SessionRecord {
+                keypair: SecretKeyRecord {
+                    private_key: state.keypair.export_private_key(),
+                },
+                ephk_vec: state
+                    .ephk_vec
+                    .iter()
+                    .map(|ephk| EphemeralKeyRecord {
+                        key: SecretKeyRecord {
+                            private_key: ephk.keypair.export_private_key(),
+                        },
+                        commitment: ephk.commitment.clone(),
+                        blind_factor: ephk.blind_factor.clone(),
+                    })
+                    .collect(),
+            }),
+            Entry::NonceLedger(ledger) => EntryRecord::NonceLedger(ledger.clone()),
+            Entry::PreSignaturePool(pool) => EntryRecord::PreSignaturePool(PoolRecord {
+                presignatures: pool
+                    .presignatures
+                    .iter()
+                    .map(|presig| PreSignatureRecord {
+                        id: presig.id.clone(),
+                        quorum: presig.quorum.clone(),
+                        eph_shared_key: SharedKeysRecord::from(&presig.eph_shared_key),
+                        eph_vss_vec: presig.eph_vss_vec.clone(),
+                    })
+                    .collect(),
+            }),
+            Entry::OracleNonce(nonce) => EntryRecord::OracleNonce(OracleNonceRecord {
+                announcement: nonce.announcement.clone(),
+                k: nonce.k.clone(),
+            }),
+        }
+    }
+}
+
+impl From<EntryRecord> for Entry {
+    fn from(record: EntryRecord) -> Entry {
+        match record {
+            EntryRecord::MuSigKey(key) => Entry::MuSigKey(key.musig()),
+            EntryRecord::MultisigKeys(identity, commitment) => {
+                Entry::MultisigKeys(multisig::Keys {
+                    I: identity.multisig(),
+                    X: commitment.multisig(),
+                })
+            }
+            EntryRecord::ThresholdShare(share) => Entry::ThresholdShare(Share {
+                id: share.id,
+                shared_key: share.shared_key.into_shared_keys(),
+                vss_scheme_vec: share.vss_scheme_vec,
+            }),
+            EntryRecord::MuSigSession(session) => Entry::MuSigSession(musig_two_rounds::State {
+                keypair: session.keypair.musig(),
+                ephk_vec: session
+                    .ephk_vec
+                    .into_iter()
+                    .map(|ephk| musig_two_rounds::EphemeralKey {
+                        keypair: ephk.key.musig(),
+                        commitment: ephk.commitment,
+                        blind_factor: ephk.blind_factor,
+                    })
+                    .collect(),
+            }),
+            EntryRecord::NonceLedger(ledger) => Entry::NonceLedger(ledger),
+            EntryRecord::PreSignaturePool(pool) => Entry::PreSignaturePool(PreSignaturePool {
+                presignatures: pool
+                    .presignatures
+                    .into_iter()
+                    .map(|presig| PreSignature {
+                        id: presig.id,
+                        quorum: presig.quorum,
+                        eph_shared_key: presig.eph_shared_key.into_shared_keys(),
+                        eph_vss_vec: presig.eph_vss_vec,
+                    })
+                    .collect(),
+            }),
+            EntryRecord::OracleNonce(nonce) => Entry::OracleNonce(OracleNonce {
+                announcement: nonce.announcement,
+                k: nonce.k,
+            }),
+        }
+    }
+}
+
+impl SecretKeyRecord {
+    fn musig(&self) -> musig_two_rounds::KeyPair {
+        musig_two_rounds::KeyPair::create_from_private_key(&self.private_key.expose().to_big_int())
+    }
+
+    fn multisig(&self) -> multisig::KeyPair {
+        multisig::KeyPair::create_from_private_key(*self.private_key.expose())
+    }
+}
+
+impl<'a> From<&'a SharedKeys> for SharedKeysRecord {
+    fn from(shared_keys: &SharedKeys) -> SharedKeysRecord {
+        SharedKeysRecord {
+            y: shared_keys.y,
+            x_i: shared_keys.x_i.clone(),
+        }
+    }
+}
+
+impl SharedKeysRecord {
+    fn into_shared_keys(self) -> SharedKeys {
+        SharedKeys {
+            y: self.y,
+            x_i: self.x_i,
+        }
+    }
+}
@@ -0,0 +1,361 @@
+/*
+    Multisig Schnorr
+
+    Copyright 2018 by Kzen Networks
+
+    This file is part of Multisig Schnorr library
+    (https://github.com/KZen-networks/multisig-schnorr)
+
+    Multisig Schnorr is free software: you can redistribute
+    it and/or modify it under the terms of the GNU General Public
+    License as published by the Free Software Foundation, either
+    version 3 of the License, or (at your option) any later version.
+
+    @license GPL-3.0+ <https://github.com/KZen-networks/multisig-schnorr/blob/master/LICENSE>
+*/
+
+#[cfg(test)]
+mod tests {
+    #![allow(non_snake_case)]
+    use std::env;
+    use std::fs;
+    use std::path::PathBuf;
+
+    use curv::arithmetic::traits::*;
+    use curv::cryptographic_primitives::secret_sharing::feldman_vss::VerifiableSS;
+    use curv::elliptic::curves::secp256_k1::{FE, GE};
+    use curv::elliptic::curves::traits::*;
+    use curv::BigInt;
+
+    use keystore::{Entry, FileKeyStore, KdfParams, KeyStore, KeyStoreError, NONCE_LEDGER_ID};
+    use protocols::aggsig::musig_two_rounds::*;
+    use protocols::aggsig::oracle::Oracle;
+    use protocols::multisig::Keys;
+    use protocols::secret::Secret;
+    use protocols::thresholdsig::presign::{PreSignature, PreSignaturePool};
+    use protocols::thresholdsig::quorum::SigningQuorum;
+    use protocols::thresholdsig::zilliqa_schnorr::{LocalSig, Share, SharedKeys, Signature};
+
+    // keeps the tests fast; production stores use `KdfParams::default()`
+    const TEST_KDF: KdfParams = KdfParams {
+        log_n: 4,
+        r: 8,
+        p: 1,
+    };
+
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir = env::temp_dir().join(format!(
+            "mps-keystore-{}-{}",
+            name,
+            BigInt::sample(64).to_str_radix(16)
+        ));
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    #[test]
+    fn test_round_trip_long_term_keys_and_shares() {
+        let dir = temp_dir("round-trip");
+        let mut store = FileKeyStore::open_with_params(&dir, b"passphrase", TEST_KDF).unwrap();
+
+        let keys = Keys::create();
+        store
+            .store("asm", &Entry::MultisigKeys(keys.clone()))
+            .unwrap();
+
+        let secret: FE = ECScalar::new_random();
+        let (vss_scheme, secret_shares) = VerifiableSS::share(1, 3, &secret);
+        let share = Share {
+            id: "share-1".to_string(),
+            shared_key: SharedKeys {
+                y: GE::generator() * &secret,
+                x_i: Secret::new(secret_shares[0]),
+            },
+            vss_scheme_vec: vec![vss_scheme],
+        };
+        store
+            .store("share-1", &Entry::ThresholdShare(share.clone()))
+            .unwrap();
+
+        let reopened = FileKeyStore::open_with_params(&dir, b"passphrase", TEST_KDF).unwrap();
+        assert_eq!(reopened.ids().unwrap(), vec!["asm", "share-1"]);
+        match reopened.load("asm").unwrap() {
+            Entry::MultisigKeys(loaded) => assert_eq!(loaded.I.public_key, keys.I.public_key),
+            _ => panic!("unexpected entry"),
+        }
+        match reopened.load("share-1").unwrap() {
+            Entry::ThresholdShare(loaded) => {
+                assert_eq!(
+                    loaded.shared_key.x_i.expose(),
+                    share.shared_key.x_i.expose()
+                );
+                assert!(loaded.vss_scheme_vec[0]
+                    .validate_share(loaded.shared_key.x_i.expose(), 1)
+                    .is_ok());
+            }
+            _ => panic!("unexpected entry"),
+        }
+
+        // secrets never hit the disk in the clear
+        let raw = fs::read(dir.join("share-1.key")).unwrap();
+        let x_i_hex = share.shared_key.x_i.expose().to_big_int().to_hex();
+        assert!(!String::from_utf8_lossy(&raw).contains(&x_i_hex));
+
+        let wrong = FileKeyStore::open_with_params(&dir, b"wrong", TEST_KDF).unwrap();
+        match wrong.load("asm") {
+            Err(KeyStoreError::Decryption) => {}
+            _ => panic!("wrong passphrase must not decrypt"),
+        }
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_record_bound_to_id() {
+        let dir = temp_dir("bound");
+        let mut store = FileKeyStore::open_with_params(&dir, b"passphrase", TEST_KDF).unwrap();
+        store
+            .store("a", &Entry::MuSigKey(KeyPair::create()))
+            .unwrap();
+        fs::rename(dir.join("a.key"), dir.join("b.key")).unwrap();
+        match store.load("b") {
+            Err(KeyStoreError::Decryption) => {}
+            _ => panic!("record moved to another id must not decrypt"),
+        }
+        match store.load("../a") {
+            Err(KeyStoreError::InvalidId) => {}
+            _ => panic!("ids must not escape the store directory"),
+        }
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_resume_musig_session_after_crash() {
+        let dir = temp_dir("resume");
+        let message: [u8; 4] = [79, 77, 69, 82];
+
+        let party1_key = KeyPair::create();
+        let party2_key = KeyPair::create();
+        let pks: Vec<GE> = vec![party1_key.public_key, party2_key.public_key];
+        let party1_key_agg = KeyAgg::key_aggregation_n(&pks, 0);
+
+        let (party_1_msg_round_1, party_1_state) = sign(party1_key);
+        let (party_2_msg_round_1, party_2_state) = sign(party2_key);
+        {
+            let mut store = FileKeyStore::open_with_params(&dir, b"pw", TEST_KDF).unwrap();
+            store.store_session("session-1", &party_1_state).unwrap();
+            // party 1 crashes here, before round 2
+        }
+
+        let mut store = FileKeyStore::open_with_params(&dir, b"pw", TEST_KDF).unwrap();
+        let party_1_state = store.consume_session("session-1").unwrap();
+
+        let party1_received_msg_round_1 = vec![party_2_msg_round_1];
+        let party2_received_msg_round_1 = vec![party_1_msg_round_1];
+        let (party_1_StatePrime, _party1_msg_round_2) =
+            party_1_state.sign_prime(&message, &pks, party1_received_msg_round_1.clone(), 0, None);
+        let (_, party2_msg_round_2) =
+            party_2_state.sign_prime(&message, &pks, party2_received_msg_round_1, 1, None);
+
+        let s = sign_double_prime(party_1_StatePrime, &vec![party2_msg_round_2]);
+        let (c, R, _) =
+            party_1_state.compute_global_params(&message, &pks, party1_received_msg_round_1, 0);
+        assert!(verify(&s, &R.x_coor().unwrap(), &party1_key_agg.X_tilde, &c).is_ok());
+
+        // a stale copy of the session (e.g. restored from a backup) must not be signed again
+        store.store_session("session-1", &party_1_state).unwrap();
+        match store.consume_session("session-1") {
+            Err(KeyStoreError::NonceReused) => {}
+            _ => panic!("nonce reuse must be refused"),
+        }
+        let mut reopened = FileKeyStore::open_with_params(&dir, b"pw", TEST_KDF).unwrap();
+        match reopened.consume_session("session-1") {
+            Err(KeyStoreError::NonceReused) => {}
+            _ => panic!("nonce ledger must survive a reload"),
+        }
+
+        // the ledger cannot be wiped through the public interface
+        match reopened.store(NONCE_LEDGER_ID, &Entry::NonceLedger(Vec::new())) {
+            Err(KeyStoreError::InvalidId) => {}
+            _ => panic!("the ledger id is reserved"),
+        }
+        match reopened.remove(NONCE_LEDGER_ID) {
+            Err(KeyStoreError::InvalidId) => {}
+            _ => panic!("the ledger id is reserved"),
+        }
+        match reopened.consume_session("session-1") {
+            Err(KeyStoreError::NonceReused) => {}
+            _ => panic!("nonce ledger must survive"),
+        }
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_presignature_pool_is_single_use_across_reloads() {
+        let dir = temp_dir("presign");
+        let mut store = FileKeyStore::open_with_params(&dir, b"pw", TEST_KDF).unwrap();
+
+        let quorum = SigningQuorum::new(1, 3, &[0, 2]).unwrap();
+        let mut pool = PreSignaturePool::new();
+        for _ in 0..2 {
+            let beta: FE = ECScalar::new_random();
+            let (vss_scheme, shares) = VerifiableSS::share(1, 2, &beta);
+            let eph_shared_key = SharedKeys {
+                y: GE::generator() * &beta,
+                x_i: Secret::new(shares[0]),
+            };
+            assert!(pool.add(PreSignature::new(
+                quorum.clone(),
+                eph_shared_key,
+                vec![vss_scheme]
+            )));
+        }
+        let ids = pool.ids();
+        let snapshot = pool.clone();
+        store.store("pool", &Entry::PreSignaturePool(pool)).unwrap();
+
+        let presignature = store.consume_presignature("pool", &ids[0]).unwrap();
+        assert_eq!(presignature.id, ids[0]);
+        match store.consume_presignature("pool", &ids[0]) {
+            Err(KeyStoreError::NonceReused) => {}
+            _ => panic!("a pre-signature must be handed out once"),
+        }
+        match store.consume_presignature("pool", "unknown") {
+            Err(KeyStoreError::NotFound) => {}
+            _ => panic!("unknown pre-signature"),
+        }
+
+        let mut reopened = FileKeyStore::open_with_params(&dir, b"pw", TEST_KDF).unwrap();
+        match reopened.consume_presignature("pool", &ids[0]) {
+            Err(KeyStoreError::NonceReused) => {}
+            _ => panic!("used ids must survive a reload"),
+        }
+        assert!(reopened.consume_presignature("pool", &ids[1]).is_ok());
+
+        // a pool restored from an old copy still holds both, the ledger does not forget them
+        reopened
+            .store("pool", &Entry::PreSignaturePool(snapshot))
+            .unwrap();
+        for id in ids.iter() {
+            match reopened.consume_presignature("pool", id) {
+                Err(KeyStoreError::NonceReused) => {}
+                _ => panic!("a restored pre-signature must not be handed out again"),
+            }
+        }
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_sign_with_presignature_from_store() {
+        let message: [u8; 4] = [79, 77, 69, 82];
+        let x: FE = ECScalar::new_random();
+        let Y = GE::generator() * &x;
+        let (vss_scheme, x_shares) = VerifiableSS::share(1, 3, &x);
+        let quorum = SigningQuorum::new(1, 3, &[0, 2]).unwrap();
+
+        // preprocessing: every member of the quorum stores its share of the same ephemeral keys
+        let dirs = (0..2)
+            .map(|k| temp_dir(&format!("presign-sign-{}", k)))
+            .collect::<Vec<PathBuf>>();
+        let mut pool_vec = vec![PreSignaturePool::new(); 2];
+        for _ in 0..2 {
+            let beta: FE = ECScalar::new_random();
+            let (eph_vss_scheme, beta_shares) =
+                VerifiableSS::share_at_indices(1, 2, &beta, &quorum.points());
+            for k in 0..2 {
+                assert!(pool_vec[k].add(PreSignature::new(
+                    quorum.clone(),
+                    SharedKeys {
+                        y: GE::generator() * &beta,
+                        x_i: Secret::new(beta_shares[k]),
+                    },
+                    vec![eph_vss_scheme.clone()],
+                )));
+            }
+        }
+        // every member derives the same ids
+        assert_eq!(pool_vec[0].ids(), pool_vec[1].ids());
+        let id = pool_vec[0].ids()[0].clone();
+        let mut store_vec = dirs
+            .iter()
+            .zip(pool_vec.into_iter())
+            .map(|(dir, pool)| {
+                let mut store = FileKeyStore::open_with_params(dir, b"pw", TEST_KDF).unwrap();
+                store.store("pool", &Entry::PreSignaturePool(pool)).unwrap();
+                store
+            })
+            .collect::<Vec<FileKeyStore>>();
+
+        // online: one round of local signatures
+        let (local_sig_vec, presignature_vec): (Vec<LocalSig>, Vec<PreSignature>) = (0..2)
+            .map(|k| {
+                let shared_keys = SharedKeys {
+                    y: Y,
+                    x_i: Secret::new(x_shares[quorum.parties[k]]),
+                };
+                store_vec[k]
+                    .sign_with_presignature("pool", &id, &message, &shared_keys)
+                    .unwrap()
+            })
+            .unzip();
+        let presignature = &presignature_vec[0];
+        let vss_sum_local_sigs = LocalSig::verify_local_sigs(
+            &local_sig_vec,
+            &presignature.quorum.parties,
+            &vec![vss_scheme],
+            &presignature.eph_vss_vec,
+        )
+        .unwrap();
+        let signature = Signature::generate(
+            &vss_sum_local_sigs,
+            &local_sig_vec,
+            &presignature.quorum,
+            &presignature.v(),
+            &Y,
+            &message,
+        )
+        .unwrap();
+        assert!(signature.verify(&message, &Y).is_ok());
+
+        // the same pre-signature cannot sign another message
+        let shared_keys = SharedKeys {
+            y: Y,
+            x_i: Secret::new(x_shares[0]),
+        };
+        match store_vec[0].sign_with_presignature("pool", &id, &[0x00], &shared_keys) {
+            Err(KeyStoreError::NonceReused) => {}
+            _ => panic!("a pre-signature is used once"),
+        }
+        for dir in dirs.iter() {
+            fs::remove_dir_all(dir).unwrap();
+        }
+    }
+
+    #[test]
+    fn test_oracle_attests_once_across_reloads() {
+        let dir = temp_dir("oracle");
+        let mut store = FileKeyStore::open_with_params(&dir, b"pw", TEST_KDF).unwrap();
+        let oracle = Oracle::new();
+        let (announcement, nonce) = oracle.announce(&[b"yes".to_vec(), b"no".to_vec()]);
+        store.store_oracle_nonce("event", &nonce).unwrap();
+
+        match store.attest("event", &oracle, b"maybe") {
+            Err(KeyStoreError::Protocol(_)) => {}
+            _ => panic!("only announced outcomes are attested"),
+        }
+        let attestation = store.attest("event", &oracle, b"yes").unwrap();
+        assert!(attestation.verify(&announcement).is_ok());
+        match store.attest("event", &oracle, b"no") {
+            Err(KeyStoreError::NotFound) => {}
+            _ => panic!("the nonce is removed once it attested"),
+        }
+
+        // a nonce restored from an old copy must not attest the other outcome
+        let mut reopened = FileKeyStore::open_with_params(&dir, b"pw", TEST_KDF).unwrap();
+        reopened.store_oracle_nonce("event", &nonce).unwrap();
+        match reopened.attest("event", &oracle, b"no") {
+            Err(KeyStoreError::NonceReused) => {}
+            _ => panic!("an attested nonce must not be used again"),
+        }
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
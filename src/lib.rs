@@ -17,9 +17,16 @@
 #[macro_use]
 extern crate serde_derive;
 extern crate serde;
+extern crate serde_json;
 
 extern crate centipede;
+extern crate chacha20poly1305;
 extern crate curv;
+extern crate rand;
+extern crate scrypt;
+extern crate subtle;
+extern crate zeroize;
+pub mod keystore;
 pub mod protocols;
 
 #[derive(Copy, PartialEq, Eq, Clone, Debug)]
@@ -28,6 +35,8 @@ pub enum Error {
     InvalidSS,
     InvalidCom,
     InvalidSig,
+    InvalidBackup,
+    InvalidTerms,
 }
 
 use std::fmt;
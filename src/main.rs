@@ -3,8 +3,12 @@
 #[macro_use]
 extern crate serde_derive;
 extern crate centipede;
+extern crate chacha20poly1305;
 extern crate curv;
 extern crate serde;
+extern crate serde_json;
+extern crate subtle;
+extern crate zeroize;
 pub mod protocols;
 
 use protocols::aggsig::musig_three_rounds::*;
@@ -23,6 +27,8 @@ pub enum Error {
     InvalidSS,
     InvalidCom,
     InvalidSig,
+    InvalidBackup,
+    InvalidTerms,
 }
 use std::fmt;
 impl fmt::Display for Error {
@@ -65,33 +71,33 @@ fn main() {
     // p1 release R1' and p2 test com(R1') = com(R1):
     assert!(EphemeralKey::test_com(
         &party1_ephemeral_key.keypair.public_key,
-        &party1_ephemeral_key.blind_factor,
+        party1_ephemeral_key.blind_factor.expose(),
         party1_commitment
     ));
     // p1 release T' and others test com(T') = com(T):
     assert!(EphemeralKey::test_com(
         &party1_sec_ephemeral_key.keypair.public_key,
-        &party1_sec_ephemeral_key.blind_factor,
+        party1_sec_ephemeral_key.blind_factor.expose(),
         party1_sec_commitment
     ));
     assert!(EphemeralKey::test_com(
         &party2_ephemeral_key.keypair.public_key,
-        &party2_ephemeral_key.blind_factor,
+        party2_ephemeral_key.blind_factor.expose(),
         party2_commitment
     ));
     assert!(EphemeralKey::test_com(
         &party3_ephemeral_key.keypair.public_key,
-        &party3_ephemeral_key.blind_factor,
+        party3_ephemeral_key.blind_factor.expose(),
         party3_commitment
     ));
     assert!(EphemeralKey::test_com(
         &party4_ephemeral_key.keypair.public_key,
-        &party4_ephemeral_key.blind_factor,
+        party4_ephemeral_key.blind_factor.expose(),
         party4_commitment
     ));
     assert!(EphemeralKey::test_com(
         &party5_ephemeral_key.keypair.public_key,
-        &party5_ephemeral_key.blind_factor,
+        party5_ephemeral_key.blind_factor.expose(),
         party5_commitment
     ));
 
@@ -249,21 +255,21 @@ fn main() {
 //     // p1 release R1' and p2 test com(R1') = com(R1):
 //     assert!(EphemeralKey::test_com(
 //         &party1_ephemeral_key.keypair.public_key,
-//         &party1_ephemeral_key.blind_factor,
+//         party1_ephemeral_key.blind_factor.expose(),
 //         party1_commitment
 //     ));
 
 //     // p1 release T' and p2 test com(T') = com(T):
 //     assert!(EphemeralKey::test_com(
 //         &party1_sec_ephemeral_key.keypair.public_key,
-//         &party1_sec_ephemeral_key.blind_factor,
+//         party1_sec_ephemeral_key.blind_factor.expose(),
 //         party1_sec_commitment
 //     ));
 
 //     // p2 release R2' and p1 test com(R2') = com(R2):
 //     assert!(EphemeralKey::test_com(
 //         &party2_ephemeral_key.keypair.public_key,
-//         &party2_ephemeral_key.blind_factor,
+//         party2_ephemeral_key.blind_factor.expose(),
 //         party2_commitment
 //     ));
 
@@ -377,13 +383,13 @@ fn main() {
 //     // p1 release R1' and p2 test com(R1') = com(R1):
 //     assert!(EphemeralKey::test_com(
 //         &party2_ephemeral_key.keypair.public_key,
-//         &party2_ephemeral_key.blind_factor,
+//         party2_ephemeral_key.blind_factor.expose(),
 //         party2_commitment
 //     ));
 //     // p2 release R2' and p1 test com(R2') = com(R2):
 //     assert!(EphemeralKey::test_com(
 //         &party1_ephemeral_key.keypair.public_key,
-//         &party1_ephemeral_key.blind_factor,
+//         party1_ephemeral_key.blind_factor.expose(),
 //         party1_commitment
 //     ));
 
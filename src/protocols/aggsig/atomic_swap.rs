@@ -0,0 +1,651 @@
+#![allow(non_snake_case)]
+/*
+    Multisig Schnorr
+
+    Copyright 2018 by Kzen Networks
+
+    This file is part of Multisig Schnorr library
+    (https://github.com/KZen-networks/multisig-schnorr)
+
+    Multisig Schnorr is free software: you can redistribute
+    it and/or modify it under the terms of the GNU General Public
+    License as published by the Free Software Foundation, either
+    version 3 of the License, or (at your option) any later version.
+
+    @license GPL-3.0+ <https://github.com/KZen-networks/multisig-schnorr/blob/master/LICENSE>
+*/
+
+//! Scriptless two-party atomic swap over two ledgers with `musig_three_rounds` locks.
+//!
+//! The initiator holds coins on ledger A, the responder on ledger B, and every coin moves
+//! into a 2-of-2 MuSig lock. The initiator picks the adaptor secret t and sends T = t*G with
+//! its first message. Three rounds then sign all four spends of the locks at once:
+//!
+//! - refund A pays the initiator back once `refund_time_a` has passed, refund B pays the
+//!   responder back after `refund_time_b`;
+//! - redeem A pays the responder and redeem B the initiator. Both are signed under
+//!   R' = R_1 + R_2 + T, so only pre-signatures exist until someone adds t.
+//!
+//! With the refunds and pre-signatures in hand the initiator locks A, and the responder locks
+//! B once lock A is on ledger A. The initiator completes redeem B with
+//! `add_signature_parts_with_secret`; publishing it reveals t = s - s', which completes redeem
+//! A for the responder. `refund_time_b` must expire before `refund_time_a`, so the responder
+//! can still redeem A after the last moment at which redeem B is possible. If either side
+//! stops early, the refunds return the coins after their timeouts.
+use curv::arithmetic::traits::*;
+use curv::elliptic::curves::traits::*;
+use curv::BigInt;
+use protocols::aggsig::musig_three_rounds::{
+    verify, verify_partial, verify_pre_signature, EphemeralKey, KeyAgg, KeyPair,
+};
+use protocols::transcript::{to_fixed_bytes, Transcript, SCALAR_BYTES};
+use Error::{self, InvalidCom, InvalidKey, InvalidSig, InvalidTerms};
+
+type GE = curv::elliptic::curves::secp256_k1::GE;
+type FE = curv::elliptic::curves::secp256_k1::FE;
+
+// positions of the spends in every message
+const REFUND_A: usize = 0;
+const REDEEM_A: usize = 1;
+const REFUND_B: usize = 2;
+const REDEEM_B: usize = 3;
+const SPENDS: usize = 4;
+
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub enum Role {
+    // holds coins on ledger A and the adaptor secret
+    Initiator,
+    // holds coins on ledger B
+    Responder,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct SwapTerms {
+    pub amount_a: u64,
+    pub amount_b: u64,
+    pub refund_time_a: u64,
+    pub refund_time_b: u64,
+}
+
+impl SwapTerms {
+    pub fn new(
+        amount_a: u64,
+        amount_b: u64,
+        refund_time_a: u64,
+        refund_time_b: u64,
+    ) -> Result<SwapTerms, Error> {
+        if refund_time_b >= refund_time_a {
+            return Err(InvalidTerms);
+        }
+        Ok(SwapTerms {
+            amount_a,
+            amount_b,
+            refund_time_a,
+            refund_time_b,
+        })
+    }
+}
+
+// a spend of a lock; valid on its ledger from `locktime` on
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Transaction {
+    pub lock: GE,
+    pub amount: u64,
+    pub to: GE,
+    pub locktime: u64,
+}
+
+impl Transaction {
+    pub fn message(&self) -> Vec<u8> {
+        let mut transcript = Transcript::new(b"atomic-swap/transaction");
+        transcript.append_point(b"lock", &self.lock);
+        transcript.append_u64(b"amount", self.amount);
+        transcript.append_point(b"to", &self.to);
+        transcript.append_u64(b"locktime", self.locktime);
+        to_fixed_bytes(&transcript.challenge(), SCALAR_BYTES)
+    }
+}
+
+// verifies with `musig_three_rounds::verify` under the lock
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct SignedTransaction {
+    pub transaction: Transaction,
+    pub r_x: BigInt,
+    pub s: BigInt,
+}
+
+impl SignedTransaction {
+    pub fn verify(&self) -> Result<(), Error> {
+        verify(
+            &self.s,
+            &self.r_x,
+            &self.transaction.lock,
+            &self.transaction.message(),
+            true,
+        )
+        .map_err(|_| InvalidSig)
+    }
+}
+
+// keys for both locks, T from the initiator and the nonce commitments of all spends
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct SwapMessage1 {
+    pub key_a: GE,
+    pub key_b: GE,
+    pub adaptor_point: Option<GE>,
+    pub nonce_commitments: Vec<BigInt>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct SwapMessage2 {
+    pub nonces: Vec<GE>,
+    pub blind_factors: Vec<BigInt>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct SwapMessage3 {
+    pub partial_sigs: Vec<BigInt>,
+}
+
+#[derive(Debug)]
+pub struct SwapParty {
+    pub role: Role,
+    pub terms: SwapTerms,
+    key_a: KeyPair,
+    key_b: KeyPair,
+    adaptor_secret: Option<EphemeralKey>,
+    ephemeral_keys: Vec<EphemeralKey>,
+    counterparty: Option<SwapMessage1>,
+    nonces: Vec<GE>,
+    r_tags: Vec<GE>,
+    partial_sigs: Vec<BigInt>,
+    // full signatures of the refunds, pre-signatures of the redeems
+    signatures: Vec<BigInt>,
+}
+
+impl SwapParty {
+    pub fn new(role: Role, terms: &SwapTerms) -> (SwapParty, SwapMessage1) {
+        let adaptor_secret = match role {
+            Role::Initiator => Some(EphemeralKey::create()),
+            Role::Responder => None,
+        };
+        let party = SwapParty {
+            role,
+            terms: terms.clone(),
+            key_a: KeyPair::create(),
+            key_b: KeyPair::create(),
+            adaptor_secret,
+            ephemeral_keys: (0..SPENDS).map(|_| EphemeralKey::create()).collect(),
+            counterparty: None,
+            nonces: Vec::new(),
+            r_tags: Vec::new(),
+            partial_sigs: Vec::new(),
+            signatures: Vec::new(),
+        };
+        let message = SwapMessage1 {
+            key_a: party.key_a.public_key,
+            key_b: party.key_b.public_key,
+            adaptor_point: party.adaptor_secret.as_ref().map(|t| t.keypair.public_key),
+            nonce_commitments: party
+                .ephemeral_keys
+                .iter()
+                .map(|eph| eph.commitment.clone())
+                .collect(),
+        };
+        (party, message)
+    }
+
+    pub fn round2(&mut self, message: &SwapMessage1) -> Result<SwapMessage2, Error> {
+        let expects_adaptor_point = self.role == Role::Responder;
+        if self.counterparty.is_some()
+            || message.nonce_commitments.len() != SPENDS
+            || message.adaptor_point.is_some() != expects_adaptor_point
+        {
+            return Err(InvalidKey);
+        }
+        self.counterparty = Some(message.clone());
+        Ok(SwapMessage2 {
+            nonces: self
+                .ephemeral_keys
+                .iter()
+                .map(|eph| eph.keypair.public_key)
+                .collect(),
+            blind_factors: self
+                .ephemeral_keys
+                .iter()
+                .map(|eph| eph.blind_factor.expose().clone())
+                .collect(),
+        })
+    }
+
+    // checks the revealed nonces and signs all spends; redeems only under R' + T. The
+    // ephemeral keys are removed before use, so a second call fails.
+    pub fn round3(&mut self, message: &SwapMessage2) -> Result<SwapMessage3, Error> {
+        let counterparty = self.counterparty.clone().ok_or(InvalidKey)?;
+        if self.ephemeral_keys.len() != SPENDS {
+            return Err(InvalidKey);
+        }
+        if message.nonces.len() != SPENDS || message.blind_factors.len() != SPENDS {
+            return Err(InvalidCom);
+        }
+        let opened = (0..SPENDS).all(|i| {
+            EphemeralKey::test_com(
+                &message.nonces[i],
+                &message.blind_factors[i],
+                &counterparty.nonce_commitments[i],
+            )
+        });
+        if !opened {
+            return Err(InvalidCom);
+        }
+        let T = self.adaptor_point()?;
+        let ephemeral_keys = self.ephemeral_keys.drain(..).collect::<Vec<EphemeralKey>>();
+        self.nonces = message.nonces.clone();
+        self.r_tags = (0..SPENDS)
+            .map(|i| {
+                let r_tag = EphemeralKey::add_ephemeral_pub_keys(
+                    &ephemeral_keys[i].keypair.public_key,
+                    &message.nonces[i],
+                );
+                if i == REDEEM_A || i == REDEEM_B {
+                    EphemeralKey::add_ephemeral_pub_keys(&r_tag, &T)
+                } else {
+                    r_tag
+                }
+            })
+            .collect();
+        self.partial_sigs = (0..SPENDS)
+            .map(|i| {
+                let key_agg = self.key_agg(i, self.own_position());
+                let c = self.challenge(i)?;
+                Ok(EphemeralKey::sign(
+                    &ephemeral_keys[i],
+                    &c,
+                    self.own_key(i),
+                    &key_agg.hash,
+                ))
+            })
+            .collect::<Result<Vec<BigInt>, Error>>()?;
+        Ok(SwapMessage3 {
+            partial_sigs: self.partial_sigs.clone(),
+        })
+    }
+
+    // checks the partial signatures of the counterparty; afterwards both refunds are signed
+    // and both redeems pre-signed, so the initiator may lock A
+    pub fn finish(&mut self, message: &SwapMessage3) -> Result<(), Error> {
+        if message.partial_sigs.len() != SPENDS || self.partial_sigs.len() != SPENDS {
+            return Err(InvalidSig);
+        }
+        let counterparty = self.counterparty.clone().ok_or(InvalidKey)?;
+        let other = 1 - self.own_position();
+        let T = self.adaptor_point()?;
+        let mut signatures = Vec::new();
+        for i in 0..SPENDS {
+            let c: FE = ECScalar::from(&self.challenge(i)?);
+            let a: FE = ECScalar::from(&self.key_agg(i, other).hash);
+            let key = if i < REFUND_B {
+                counterparty.key_a
+            } else {
+                counterparty.key_b
+            };
+            verify_partial(
+                &ECScalar::from(&message.partial_sigs[i]),
+                &self.nonces[i].x_coor().unwrap(),
+                &c,
+                &a,
+                &key,
+            )
+            .map_err(|_| InvalidSig)?;
+            let (_, s) = EphemeralKey::add_signature_parts(
+                self.partial_sigs[i].clone(),
+                &message.partial_sigs[i],
+                &self.r_tags[i],
+            );
+            let apk = self.key_agg(i, other).apk;
+            let message = self.transaction(i)?.message();
+            if i == REDEEM_A || i == REDEEM_B {
+                verify_pre_signature(&s, &self.r_tags[i], &T, &apk, &message)?;
+            } else {
+                verify(&s, &self.r_tags[i].x_coor().unwrap(), &apk, &message, true)
+                    .map_err(|_| InvalidSig)?;
+            }
+            signatures.push(s);
+        }
+        self.signatures = signatures;
+        Ok(())
+    }
+
+    // the 2-of-2 keys the coins are locked to
+    pub fn lock_a(&self) -> Result<GE, Error> {
+        Ok(self.key_agg_checked(REFUND_A)?.apk)
+    }
+
+    pub fn lock_b(&self) -> Result<GE, Error> {
+        Ok(self.key_agg_checked(REFUND_B)?.apk)
+    }
+
+    // the refund of the party's own coins, valid after its timeout
+    pub fn refund(&self) -> Result<SignedTransaction, Error> {
+        let i = match self.role {
+            Role::Initiator => REFUND_A,
+            Role::Responder => REFUND_B,
+        };
+        self.signed(i, self.signatures.get(i).ok_or(InvalidSig)?.clone())
+    }
+
+    // initiator: completes redeem B with t; publishing it reveals t
+    pub fn redeem_with_secret(&self) -> Result<SignedTransaction, Error> {
+        let t = self.adaptor_secret.as_ref().ok_or(InvalidKey)?;
+        let s_pre = self.signatures.get(REDEEM_B).ok_or(InvalidSig)?.clone();
+        let (_, s) =
+            EphemeralKey::add_signature_parts_with_secret(s_pre, t, &self.r_tags[REDEEM_B]);
+        self.signed(REDEEM_B, s)
+    }
+
+    // responder: t = s - s' from redeem B as published on ledger B, then completes redeem A
+    pub fn redeem_with_revealed(
+        &self,
+        published: &SignedTransaction,
+    ) -> Result<SignedTransaction, Error> {
+        if self.role != Role::Responder || self.signatures.len() != SPENDS {
+            return Err(InvalidKey);
+        }
+        if published.transaction != self.transaction(REDEEM_B)? {
+            return Err(InvalidSig);
+        }
+        published.verify()?;
+        let t = BigInt::mod_sub(&published.s, &self.signatures[REDEEM_B], &FE::q());
+        if t == BigInt::from(0)
+            || KeyPair::create_from_private_key(&t).public_key != self.adaptor_point()?
+        {
+            return Err(InvalidSig);
+        }
+        let (_, s) = EphemeralKey::add_signature_parts(
+            self.signatures[REDEEM_A].clone(),
+            &t,
+            &self.r_tags[REDEEM_A],
+        );
+        self.signed(REDEEM_A, s)
+    }
+
+    // spend is one of the positions REFUND_A, REDEEM_A, REFUND_B and REDEEM_B
+    pub fn transaction(&self, spend: usize) -> Result<Transaction, Error> {
+        let counterparty = self.counterparty.as_ref().ok_or(InvalidKey)?;
+        let (initiator_a, initiator_b, responder_a, responder_b) = match self.role {
+            Role::Initiator => (
+                self.key_a.public_key,
+                self.key_b.public_key,
+                counterparty.key_a,
+                counterparty.key_b,
+            ),
+            Role::Responder => (
+                counterparty.key_a,
+                counterparty.key_b,
+                self.key_a.public_key,
+                self.key_b.public_key,
+            ),
+        };
+        let (amount, to, locktime) = match spend {
+            REFUND_A => (self.terms.amount_a, initiator_a, self.terms.refund_time_a),
+            REDEEM_A => (self.terms.amount_a, responder_a, 0),
+            REFUND_B => (self.terms.amount_b, responder_b, self.terms.refund_time_b),
+            REDEEM_B => (self.terms.amount_b, initiator_b, 0),
+            _ => return Err(InvalidKey),
+        };
+        Ok(Transaction {
+            lock: self.key_agg(spend, 0).apk,
+            amount,
+            to,
+            locktime,
+        })
+    }
+
+    fn signed(&self, spend: usize, s: BigInt) -> Result<SignedTransaction, Error> {
+        let signed = SignedTransaction {
+            transaction: self.transaction(spend)?,
+            r_x: self.r_tags[spend].x_coor().unwrap(),
+            s,
+        };
+        signed.verify()?;
+        Ok(signed)
+    }
+
+    fn adaptor_point(&self) -> Result<GE, Error> {
+        match self.adaptor_secret {
+            Some(ref t) => Ok(t.keypair.public_key),
+            None => self
+                .counterparty
+                .as_ref()
+                .and_then(|message| message.adaptor_point)
+                .ok_or(InvalidKey),
+        }
+    }
+
+    fn own_position(&self) -> usize {
+        match self.role {
+            Role::Initiator => 0,
+            Role::Responder => 1,
+        }
+    }
+
+    fn own_key(&self, spend: usize) -> &KeyPair {
+        if spend < REFUND_B {
+            &self.key_a
+        } else {
+            &self.key_b
+        }
+    }
+
+    // c = H(R'.x || apk || message) of a spend
+    fn challenge(&self, spend: usize) -> Result<BigInt, Error> {
+        Ok(EphemeralKey::hash_0(
+            &self.r_tags[spend],
+            &self.key_agg(spend, 0).apk,
+            &self.transaction(spend)?.message(),
+            true,
+        ))
+    }
+
+    fn key_agg_checked(&self, spend: usize) -> Result<KeyAgg, Error> {
+        if self.counterparty.is_none() {
+            return Err(InvalidKey);
+        }
+        Ok(self.key_agg(spend, 0))
+    }
+
+    // keys are ordered initiator first
+    fn key_agg(&self, spend: usize, position: usize) -> KeyAgg {
+        let counterparty = self.counterparty.as_ref().expect("round2 not run");
+        let (own, other) = if spend < REFUND_B {
+            (self.key_a.public_key, counterparty.key_a)
+        } else {
+            (self.key_b.public_key, counterparty.key_b)
+        };
+        let pks = match self.role {
+            Role::Initiator => vec![own, other],
+            Role::Responder => vec![other, own],
+        };
+        KeyAgg::key_aggregation_n(&pks, position)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // one output per lock, spent at most once
+    struct Ledger {
+        time: u64,
+        locks: Vec<(GE, u64)>,
+        spent: Vec<SignedTransaction>,
+    }
+
+    impl Ledger {
+        fn new() -> Ledger {
+            Ledger {
+                time: 0,
+                locks: Vec::new(),
+                spent: Vec::new(),
+            }
+        }
+
+        fn lock(&mut self, apk: &GE, amount: u64) {
+            self.locks.push((*apk, amount));
+        }
+
+        fn is_locked(&self, apk: &GE, amount: u64) -> bool {
+            self.locks.contains(&(*apk, amount))
+        }
+
+        fn publish(&mut self, tx: &SignedTransaction) -> Result<(), Error> {
+            let lock = (tx.transaction.lock, tx.transaction.amount);
+            let position = self
+                .locks
+                .iter()
+                .position(|l| *l == lock)
+                .ok_or(InvalidKey)?;
+            if self.time < tx.transaction.locktime {
+                return Err(InvalidKey);
+            }
+            tx.verify()?;
+            self.locks.remove(position);
+            self.spent.push(tx.clone());
+            Ok(())
+        }
+
+        fn spend_of(&self, apk: &GE) -> Option<&SignedTransaction> {
+            self.spent.iter().find(|tx| tx.transaction.lock == *apk)
+        }
+    }
+
+    fn setup(terms: &SwapTerms) -> (SwapParty, SwapParty) {
+        let (mut alice, alice_msg1) = SwapParty::new(Role::Initiator, terms);
+        let (mut bob, bob_msg1) = SwapParty::new(Role::Responder, terms);
+        let alice_msg2 = alice.round2(&bob_msg1).unwrap();
+        let bob_msg2 = bob.round2(&alice_msg1).unwrap();
+        let alice_msg3 = alice.round3(&bob_msg2).unwrap();
+        let bob_msg3 = bob.round3(&alice_msg2).unwrap();
+        alice.finish(&bob_msg3).unwrap();
+        bob.finish(&alice_msg3).unwrap();
+        (alice, bob)
+    }
+
+    #[test]
+    fn test_atomic_swap() {
+        let terms = SwapTerms::new(5, 7, 200, 100).unwrap();
+        let (alice, bob) = setup(&terms);
+        let mut ledger_a = Ledger::new();
+        let mut ledger_b = Ledger::new();
+        assert_eq!(alice.lock_a().unwrap(), bob.lock_a().unwrap());
+        assert_eq!(alice.lock_b().unwrap(), bob.lock_b().unwrap());
+
+        ledger_a.lock(&alice.lock_a().unwrap(), terms.amount_a);
+        assert!(ledger_b.spend_of(&bob.lock_b().unwrap()).is_none());
+        assert!(ledger_a.is_locked(&bob.lock_a().unwrap(), terms.amount_a));
+        ledger_b.lock(&bob.lock_b().unwrap(), terms.amount_b);
+
+        // alice takes the coins on B and reveals t
+        let redeem_b = alice.redeem_with_secret().unwrap();
+        assert_eq!(redeem_b.transaction.to, alice.key_b.public_key);
+        ledger_b.publish(&redeem_b).unwrap();
+        assert!(bob.redeem_with_secret().is_err());
+
+        let published = ledger_b.spend_of(&bob.lock_b().unwrap()).unwrap().clone();
+        let redeem_a = bob.redeem_with_revealed(&published).unwrap();
+        assert_eq!(redeem_a.transaction.to, bob.key_a.public_key);
+        ledger_a.publish(&redeem_a).unwrap();
+
+        // the refunds come too late
+        ledger_a.time = 300;
+        ledger_b.time = 300;
+        assert!(ledger_a.publish(&alice.refund().unwrap()).is_err());
+        assert!(ledger_b.publish(&bob.refund().unwrap()).is_err());
+    }
+
+    #[test]
+    fn test_swap_terms_refund_b_expires_first() {
+        assert!(SwapTerms::new(5, 7, 200, 100).is_ok());
+        assert_eq!(SwapTerms::new(5, 7, 100, 200), Err(InvalidTerms));
+        assert_eq!(SwapTerms::new(5, 7, 100, 100), Err(InvalidTerms));
+    }
+
+    #[test]
+    fn test_initiator_refunds_when_b_is_never_locked() {
+        let terms = SwapTerms::new(5, 7, 200, 100).unwrap();
+        let (alice, _bob) = setup(&terms);
+        let mut ledger_a = Ledger::new();
+        ledger_a.lock(&alice.lock_a().unwrap(), terms.amount_a);
+        let refund_a = alice.refund().unwrap();
+        assert_eq!(ledger_a.publish(&refund_a), Err(InvalidKey));
+        ledger_a.time = 200;
+        ledger_a.publish(&refund_a).unwrap();
+    }
+
+    #[test]
+    fn test_both_refund_when_b_is_never_redeemed() {
+        let terms = SwapTerms::new(5, 7, 200, 100).unwrap();
+        let (alice, bob) = setup(&terms);
+        let mut ledger_a = Ledger::new();
+        let mut ledger_b = Ledger::new();
+        ledger_a.lock(&alice.lock_a().unwrap(), terms.amount_a);
+        ledger_b.lock(&bob.lock_b().unwrap(), terms.amount_b);
+        ledger_b.time = 100;
+        ledger_b.publish(&bob.refund().unwrap()).unwrap();
+        ledger_a.time = 200;
+        ledger_a.publish(&alice.refund().unwrap()).unwrap();
+        // a refund reveals nothing that completes redeem A
+        let refund_b = ledger_b.spend_of(&bob.lock_b().unwrap()).unwrap().clone();
+        assert!(bob.redeem_with_revealed(&refund_b).is_err());
+    }
+
+    #[test]
+    fn test_bad_partial_sig_stops_setup() {
+        let terms = SwapTerms::new(5, 7, 200, 100).unwrap();
+        let (mut alice, alice_msg1) = SwapParty::new(Role::Initiator, &terms);
+        let (mut bob, bob_msg1) = SwapParty::new(Role::Responder, &terms);
+        let alice_msg2 = alice.round2(&bob_msg1).unwrap();
+        let bob_msg2 = bob.round2(&alice_msg1).unwrap();
+        let alice_msg3 = alice.round3(&bob_msg2).unwrap();
+        let mut bob_msg3 = bob.round3(&alice_msg2).unwrap();
+        bob_msg3.partial_sigs[REDEEM_B] = BigInt::from(1);
+        assert_eq!(alice.finish(&bob_msg3), Err(InvalidSig));
+        // nothing is signed, so alice does not lock A
+        assert!(alice.refund().is_err());
+        bob.finish(&alice_msg3).unwrap();
+    }
+
+    #[test]
+    fn test_every_round_runs_once() {
+        let terms = SwapTerms::new(5, 7, 200, 100).unwrap();
+        let (mut alice, _bob) = setup(&terms);
+        let (_, alice_msg1) = SwapParty::new(Role::Initiator, &terms);
+        let (mut bob, bob_msg1) = SwapParty::new(Role::Responder, &terms);
+        let bob_msg2 = bob.round2(&alice_msg1).unwrap();
+        assert_eq!(alice.round2(&bob_msg1).unwrap_err(), InvalidKey);
+        assert_eq!(alice.round3(&bob_msg2).unwrap_err(), InvalidKey);
+        // and only the four spends exist
+        assert!(alice.transaction(REDEEM_B).is_ok());
+        assert_eq!(alice.transaction(SPENDS), Err(InvalidKey));
+    }
+
+    #[test]
+    fn test_responder_must_not_send_adaptor_point() {
+        let terms = SwapTerms::new(5, 7, 200, 100).unwrap();
+        let (alice, _) = SwapParty::new(Role::Initiator, &terms);
+        let (mut bob, bob_msg1) = SwapParty::new(Role::Responder, &terms);
+        assert_eq!(alice.transaction(REFUND_A), Err(InvalidKey));
+        assert_eq!(bob.round2(&bob_msg1).unwrap_err(), InvalidKey);
+    }
+
+    #[test]
+    fn test_opened_nonces_must_match_commitments() {
+        let terms = SwapTerms::new(5, 7, 200, 100).unwrap();
+        let (mut alice, alice_msg1) = SwapParty::new(Role::Initiator, &terms);
+        let (mut bob, bob_msg1) = SwapParty::new(Role::Responder, &terms);
+        alice.round2(&bob_msg1).unwrap();
+        let mut bob_msg2 = bob.round2(&alice_msg1).unwrap();
+        bob_msg2.nonces.swap(0, 1);
+        assert_eq!(alice.round3(&bob_msg2).unwrap_err(), InvalidCom);
+    }
+}
@@ -0,0 +1,409 @@
+#![allow(non_snake_case)]
+/*
+    Multisig Schnorr
+
+    Copyright 2018 by Kzen Networks
+
+    This file is part of Multisig Schnorr library
+    (https://github.com/KZen-networks/multisig-schnorr)
+
+    Multisig Schnorr is free software: you can redistribute
+    it and/or modify it under the terms of the GNU General Public
+    License as published by the Free Software Foundation, either
+    version 3 of the License, or (at your option) any later version.
+
+    @license GPL-3.0+ <https://github.com/KZen-networks/multisig-schnorr/blob/master/LICENSE>
+*/
+
+//! Adaptor secrets shared between secp256k1 and ed25519, for swaps with ed25519 chains.
+//!
+//! The secret t is below 2^252, so it is the same integer in both groups. The proof that
+//! T = t*G and T' = t*G' share t follows the bit decomposition of Noether, "Discrete logarithm
+//! equality across groups": for every bit b_i of t the prover commits C_i = b_i*G + r_i*H and
+//! C'_i = b_i*G' + r'_i*H', with blindings chosen so that sum_i 2^i r_i = 0 in both groups.
+//! Then sum_i 2^i C_i = T and sum_i 2^i C'_i = T'. A two-member ring signature per bit shows
+//! that (C_i, C'_i) commits to 0 or to 1 in both groups at once; its challenges are kept below
+//! 2^250 and used as the same integer on both curves.
+//!
+//! On the secp256k1 side the signers of a `musig_three_rounds` session add T to their
+//! ephemeral keys, R' = sum_i R_i + T, and sign with plain `EphemeralKey::sign`. The sum s' of
+//! the partial signatures is the pre-signature, checked with `verify_pre_signature`. The
+//! holder of t completes it with `complete_pre_signature`, and whoever sees the completed
+//! signature recovers t, and with it the ed25519 key, from `extract_secret`.
+use curv::arithmetic::traits::*;
+use curv::cryptographic_primitives::commitments::hash_commitment::HashCommitment;
+use curv::cryptographic_primitives::commitments::traits::*;
+use curv::elliptic::curves::traits::*;
+use curv::BigInt;
+use protocols::aggsig::musig_three_rounds::{verify_pre_signature, EphemeralKey, KeyPair};
+use protocols::secret::Secret;
+use protocols::transcript::{to_fixed_bytes, Transcript, SCALAR_BYTES};
+use Error::{self, InvalidKey, InvalidSig};
+
+type GE = curv::elliptic::curves::secp256_k1::GE;
+type FE = curv::elliptic::curves::secp256_k1::FE;
+type EdGE = curv::elliptic::curves::ed25519::GE;
+type EdFE = curv::elliptic::curves::ed25519::FE;
+
+// below the order of both groups
+pub const SECRET_BITS: usize = 252;
+
+#[derive(Debug)]
+pub struct AdaptorSecret {
+    t: Secret<BigInt>,
+}
+
+impl AdaptorSecret {
+    pub fn create() -> AdaptorSecret {
+        AdaptorSecret {
+            t: Secret::new(BigInt::sample(SECRET_BITS)),
+        }
+    }
+
+    // T = t*G
+    pub fn secp256k1_point(&self) -> GE {
+        let t: FE = ECScalar::from(self.t.expose());
+        GE::generator() * &t
+    }
+
+    // T' = t*G'
+    pub fn ed25519_point(&self) -> EdGE {
+        let t = self.ed25519_scalar();
+        EdGE::generator().scalar_mul(&t.expose().get_element())
+    }
+
+    // the key that unlocks the ed25519 side
+    pub fn ed25519_scalar(&self) -> Secret<EdFE> {
+        Secret::new(ECScalar::from(self.t.expose()))
+    }
+
+    // t as an ephemeral key, for `sign_with_secret` and `add_signature_parts_with_secret`
+    pub fn ephemeral_key(&self) -> EphemeralKey {
+        let keypair = KeyPair::create_from_private_key(self.t.expose());
+        let (commitment, blind_factor) =
+            HashCommitment::create_commitment(&keypair.public_key.bytes_compressed_to_big_int());
+        EphemeralKey {
+            keypair,
+            commitment,
+            blind_factor: Secret::new(blind_factor),
+        }
+    }
+
+    pub fn prove(&self) -> CrossGroupDleqProof {
+        let T = self.secp256k1_point();
+        let T_ed = self.ed25519_point();
+        let t_bytes = Secret::new(to_fixed_bytes(self.t.expose(), SCALAR_BYTES));
+        let bits = (0..SECRET_BITS)
+            .map(|i| (t_bytes.expose()[SCALAR_BYTES - 1 - i / 8] >> (i % 8)) & 1)
+            .collect::<Vec<u8>>();
+        let random = |q: &BigInt| -> Vec<BigInt> {
+            (1..SECRET_BITS).map(|_| BigInt::sample_below(q)).collect()
+        };
+        let r_vec = balanced_blindings(&FE::q(), random(&FE::q()));
+        let r_ed_vec = balanced_blindings(&EdFE::q(), random(&EdFE::q()));
+        let bit_proofs = (0..SECRET_BITS)
+            .map(|i| {
+                let r: FE = ECScalar::from(&r_vec[i]);
+                let r_ed: EdFE = ECScalar::from(&r_ed_vec[i]);
+                BitProof::prove(&T, &T_ed, i, bits[i] as usize, &r, &r_ed)
+            })
+            .collect::<Vec<BitProof>>();
+        CrossGroupDleqProof {
+            T,
+            T_ed,
+            bits: bit_proofs,
+        }
+    }
+}
+
+// one bit of t: C = b*G + r*H, C' = b*G' + r'*H' and a ring signature over
+// {C, C'} and {C - G, C' - G'}
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct BitProof {
+    pub C: GE,
+    pub C_ed: EdGE,
+    pub c_0: BigInt,
+    pub z: [FE; 2],
+    pub z_ed: [EdFE; 2],
+}
+
+impl BitProof {
+    fn prove(T: &GE, T_ed: &EdGE, index: usize, bit: usize, r: &FE, r_ed: &EdFE) -> BitProof {
+        let H = GE::base_point2();
+        let H_ed = EdGE::base_point2();
+        let mut C = H * r;
+        let mut C_ed = H_ed.scalar_mul(&r_ed.get_element());
+        if bit == 1 {
+            C = C + &GE::generator();
+            C_ed = C_ed.add_point(&EdGE::generator().get_element());
+        }
+        let other = 1 - bit;
+        // the ring starts right after the real member
+        let k: FE = ECScalar::new_random();
+        let k_ed: EdFE = ECScalar::new_random();
+        let c_other = ring_challenge(
+            T,
+            T_ed,
+            index,
+            &C,
+            &C_ed,
+            &(H * &k),
+            &H_ed.scalar_mul(&k_ed.get_element()),
+        );
+        let z_other: FE = ECScalar::new_random();
+        let z_other_ed: EdFE = ECScalar::new_random();
+        let (R_other, R_other_ed) =
+            ring_member_nonce(&C, &C_ed, other, &c_other, &z_other, &z_other_ed);
+        let c_bit = ring_challenge(T, T_ed, index, &C, &C_ed, &R_other, &R_other_ed);
+        let c_bit_fe: FE = ECScalar::from(&c_bit);
+        let c_bit_ed: EdFE = ECScalar::from(&c_bit);
+        let z_bit = k + &(c_bit_fe * r);
+        let z_bit_ed = k_ed.add(&c_bit_ed.mul(&r_ed.get_element()).get_element());
+        let (c_0, z, z_ed) = if bit == 0 {
+            (c_bit, [z_bit, z_other], [z_bit_ed, z_other_ed])
+        } else {
+            (c_other, [z_other, z_bit], [z_other_ed, z_bit_ed])
+        };
+        BitProof {
+            C,
+            C_ed,
+            c_0,
+            z,
+            z_ed,
+        }
+    }
+
+    fn verify(&self, T: &GE, T_ed: &EdGE, index: usize) -> bool {
+        let (R_0, R_0_ed) =
+            ring_member_nonce(&self.C, &self.C_ed, 0, &self.c_0, &self.z[0], &self.z_ed[0]);
+        let c_1 = ring_challenge(T, T_ed, index, &self.C, &self.C_ed, &R_0, &R_0_ed);
+        let (R_1, R_1_ed) =
+            ring_member_nonce(&self.C, &self.C_ed, 1, &c_1, &self.z[1], &self.z_ed[1]);
+        ring_challenge(T, T_ed, index, &self.C, &self.C_ed, &R_1, &R_1_ed) == self.c_0
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct CrossGroupDleqProof {
+    pub T: GE,
+    pub T_ed: EdGE,
+    // bits[i] is bit i of t, least significant first
+    pub bits: Vec<BitProof>,
+}
+
+impl CrossGroupDleqProof {
+    pub fn verify(&self) -> Result<(), Error> {
+        if self.bits.len() != SECRET_BITS {
+            return Err(InvalidKey);
+        }
+        let C_vec = self.bits.iter().map(|bit| bit.C).collect::<Vec<GE>>();
+        let C_ed_vec = self
+            .bits
+            .iter()
+            .map(|bit| bit.C_ed.clone())
+            .collect::<Vec<EdGE>>();
+        if binary_sum(&C_vec) != self.T || binary_sum(&C_ed_vec) != self.T_ed {
+            return Err(InvalidKey);
+        }
+        if (0..SECRET_BITS).all(|i| self.bits[i].verify(&self.T, &self.T_ed, i)) {
+            Ok(())
+        } else {
+            Err(InvalidKey)
+        }
+    }
+}
+
+// (R'.x, s' + t), verifies with `musig_three_rounds::verify`
+pub fn complete_pre_signature(
+    s_pre: BigInt,
+    secret: &AdaptorSecret,
+    r_tag: &GE,
+) -> (BigInt, BigInt) {
+    EphemeralKey::add_signature_parts_with_secret(s_pre, &secret.ephemeral_key(), r_tag)
+}
+
+// t = s - s', checked against both points of the proof
+pub fn extract_secret(
+    signature: &BigInt,
+    s_pre: &BigInt,
+    proof: &CrossGroupDleqProof,
+) -> Result<AdaptorSecret, Error> {
+    let secret = AdaptorSecret {
+        t: Secret::new(BigInt::mod_sub(signature, s_pre, &FE::q())),
+    };
+    // t < 2^SECRET_BITS
+    if to_fixed_bytes(secret.t.expose(), SCALAR_BYTES)[0] >= 0x10
+        || secret.secp256k1_point() != proof.T
+        || secret.ed25519_point() != proof.T_ed
+    {
+        return Err(InvalidSig);
+    }
+    Ok(secret)
+}
+
+// blindings r_0..r_{n-1} below q with sum_i 2^i r_i = 0, from n - 1 random ones
+fn balanced_blindings(q: &BigInt, mut blindings: Vec<BigInt>) -> Vec<BigInt> {
+    let two = BigInt::from(2);
+    let weight = |i: usize| BigInt::mod_pow(&two, &BigInt::from(i as i32), q);
+    let weighted_sum = blindings
+        .iter()
+        .enumerate()
+        .fold(BigInt::from(0), |acc, (i, r)| {
+            BigInt::mod_add(&acc, &BigInt::mod_mul(&weight(i), r, q), q)
+        });
+    let last = BigInt::mod_mul(
+        &weighted_sum,
+        &BigInt::mod_inv(&weight(blindings.len()), q),
+        q,
+    );
+    blindings.push(BigInt::mod_sub(&BigInt::from(0), &last, q));
+    blindings
+}
+
+// sum_i 2^i P_i
+fn binary_sum<P: ECPoint<PK, SK> + Clone, PK, SK>(points: &[P]) -> P {
+    let last = points[points.len() - 1].clone();
+    points[..points.len() - 1]
+        .iter()
+        .rev()
+        .fold(last, |acc, point| {
+            acc.add_point(&acc.get_element())
+                .add_point(&point.get_element())
+        })
+}
+
+// R_j = z*H - c*(C - j*G) and R'_j = z'*H' - c*(C' - j*G')
+fn ring_member_nonce(
+    C: &GE,
+    C_ed: &EdGE,
+    member: usize,
+    c: &BigInt,
+    z: &FE,
+    z_ed: &EdFE,
+) -> (GE, EdGE) {
+    let c_fe: FE = ECScalar::from(c);
+    let c_ed: EdFE = ECScalar::from(c);
+    let (P, P_ed) = if member == 0 {
+        (*C, C_ed.clone())
+    } else {
+        (
+            C.sub_point(&GE::generator().get_element()),
+            C_ed.sub_point(&EdGE::generator().get_element()),
+        )
+    };
+    let R = (GE::base_point2() * z).sub_point(&(P * &c_fe).get_element());
+    let R_ed = EdGE::base_point2()
+        .scalar_mul(&z_ed.get_element())
+        .sub_point(&P_ed.scalar_mul(&c_ed.get_element()).get_element());
+    (R, R_ed)
+}
+
+// challenge below 2^250, the same integer in both groups
+fn ring_challenge(
+    T: &GE,
+    T_ed: &EdGE,
+    index: usize,
+    C: &GE,
+    C_ed: &EdGE,
+    R: &GE,
+    R_ed: &EdGE,
+) -> BigInt {
+    let mut transcript = Transcript::new(b"cross-group-dleq/ring");
+    transcript.append_point(b"T", T);
+    transcript.append_message(b"T'", &ed25519_point_bytes(T_ed));
+    transcript.append_u64(b"bit", index as u64);
+    transcript.append_point(b"C", C);
+    transcript.append_message(b"C'", &ed25519_point_bytes(C_ed));
+    transcript.append_point(b"R", R);
+    transcript.append_message(b"R'", &ed25519_point_bytes(R_ed));
+    let mut bytes = to_fixed_bytes(&transcript.challenge(), SCALAR_BYTES);
+    bytes[0] &= 0x03;
+    BigInt::from_bytes(&bytes)
+}
+
+fn ed25519_point_bytes(point: &EdGE) -> Vec<u8> {
+    to_fixed_bytes(&point.bytes_compressed_to_big_int(), SCALAR_BYTES)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use protocols::aggsig::musig_three_rounds::{verify, KeyAgg};
+    use serde_json;
+
+    #[test]
+    fn test_adaptor_signature_unlocks_ed25519_key() {
+        let message: [u8; 4] = [79, 77, 69, 82];
+        let party1_key = KeyPair::create();
+        let party2_key = KeyPair::create();
+        let pks = vec![party1_key.public_key, party2_key.public_key];
+        let party1_key_agg = KeyAgg::key_aggregation_n(&pks, 0);
+        let party2_key_agg = KeyAgg::key_aggregation_n(&pks, 1);
+
+        // party 1 holds t and proves that T and T' share it
+        let secret = AdaptorSecret::create();
+        let proof = secret.prove();
+        assert!(proof.verify().is_ok());
+        let proof: CrossGroupDleqProof =
+            serde_json::from_str(&serde_json::to_string(&proof).unwrap()).unwrap();
+        assert!(proof.verify().is_ok());
+
+        // R' = R1 + R2 + T, both parties sign without t
+        let party1_ephemeral_key = EphemeralKey::create();
+        let party2_ephemeral_key = EphemeralKey::create();
+        let r_tag = EphemeralKey::add_ephemeral_pub_keys(
+            &EphemeralKey::add_ephemeral_pub_keys(
+                &party1_ephemeral_key.keypair.public_key,
+                &party2_ephemeral_key.keypair.public_key,
+            ),
+            &proof.T,
+        );
+        let c = EphemeralKey::hash_0(&r_tag, &party1_key_agg.apk, &message, true);
+        let s1 = EphemeralKey::sign(&party1_ephemeral_key, &c, &party1_key, &party1_key_agg.hash);
+        let s2 = EphemeralKey::sign(&party2_ephemeral_key, &c, &party2_key, &party2_key_agg.hash);
+        let (_, s_pre) = EphemeralKey::add_signature_parts(s1, &s2, &r_tag);
+        assert!(
+            verify_pre_signature(&s_pre, &r_tag, &proof.T, &party1_key_agg.apk, &message).is_ok()
+        );
+        assert!(verify(
+            &s_pre,
+            &r_tag.x_coor().unwrap(),
+            &party1_key_agg.apk,
+            &message,
+            true
+        )
+        .is_err());
+
+        // publishing the completed signature reveals the ed25519 key
+        let (r_x, s) = complete_pre_signature(s_pre.clone(), &secret, &r_tag);
+        assert!(verify(&s, &r_x, &party1_key_agg.apk, &message, true).is_ok());
+        let extracted = extract_secret(&s, &s_pre, &proof).unwrap();
+        assert_eq!(extracted.ed25519_point(), proof.T_ed);
+        assert_eq!(
+            EdGE::generator().scalar_mul(&extracted.ed25519_scalar().expose().get_element()),
+            secret.ed25519_point()
+        );
+    }
+
+    #[test]
+    fn test_dleq_proof_rejects_different_secrets() {
+        let secret = AdaptorSecret::create();
+        let other = AdaptorSecret::create();
+        let mut proof = secret.prove();
+        proof.T_ed = other.ed25519_point();
+        assert_eq!(proof.verify(), Err(InvalidKey));
+
+        // consistent sums, but a bit that commits to 2
+        let mut proof = secret.prove();
+        let G = GE::generator();
+        let G_ed = EdGE::generator();
+        proof.bits[0].C = proof.bits[0].C + &G + &G;
+        proof.bits[0].C_ed = proof.bits[0]
+            .C_ed
+            .add_point(&G_ed.get_element())
+            .add_point(&G_ed.get_element());
+        proof.bits[1].C = proof.bits[1].C.sub_point(&G.get_element());
+        proof.bits[1].C_ed = proof.bits[1].C_ed.sub_point(&G_ed.get_element());
+        assert_eq!(proof.verify(), Err(InvalidKey));
+    }
+}
@@ -2,3 +2,9 @@
 
 pub mod musig_three_rounds;
 pub mod musig_two_rounds;
+// secp256k1/ed25519 discrete log equality and adaptor pre-signatures for cross-chain swaps
+pub mod cross_group;
+// two-party atomic swap over two ledgers with MuSig locks and adaptor signatures
+pub mod atomic_swap;
+// Discreet Log Contract oracle attestations and per-outcome adaptor pre-signatures
+pub mod oracle;
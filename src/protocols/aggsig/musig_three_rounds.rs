@@ -25,6 +25,11 @@ use curv::cryptographic_primitives::hashing::traits::*;
 use curv::arithmetic::traits::Converter;
 use curv::cryptographic_primitives::commitments::hash_commitment::HashCommitment;
 use curv::cryptographic_primitives::commitments::traits::*;
+use curv::cryptographic_primitives::secret_sharing::feldman_vss::VerifiableSS;
+
+use protocols::secret::{self, Secret};
+use protocols::transcript::{self, Transcript};
+use Error::{self, InvalidSig};
 
 type GE = curv::elliptic::curves::secp256_k1::GE;
 type FE = curv::elliptic::curves::secp256_k1::FE;
@@ -32,7 +37,7 @@ type FE = curv::elliptic::curves::secp256_k1::FE;
 #[derive(Debug)]
 pub struct KeyPair {
     pub public_key: GE,
-    private_key: FE,
+    private_key: Secret<FE>,
 }
 
 impl KeyPair {
@@ -42,7 +47,7 @@ impl KeyPair {
         let public_key = ec_point.scalar_mul(&private_key.get_element());
         KeyPair {
             public_key,
-            private_key,
+            private_key: Secret::new(private_key),
         }
     }
 
@@ -52,9 +57,22 @@ impl KeyPair {
         let public_key = ec_point.scalar_mul(&private_key.get_element());
         KeyPair {
             public_key,
-            private_key,
+            private_key: Secret::new(private_key),
         }
     }
+
+    // Feldman shares of a*x, where a is this key's KeyAgg coefficient, for a threshold
+    // committee that takes over the aggregate key (thresholdsig::musig_conversion)
+    pub fn share_weighted_key(
+        &self,
+        a: &BigInt,
+        threshold: usize,
+        share_count: usize,
+    ) -> (VerifiableSS<GE>, Vec<FE>) {
+        let a_fe: FE = ECScalar::from(a);
+        let weighted_key = Secret::new(a_fe * self.private_key.expose());
+        VerifiableSS::share(threshold, share_count, weighted_key.expose())
+    }
 }
 
 #[derive(Debug)]
@@ -132,7 +150,7 @@ impl KeyAgg {
 pub struct EphemeralKey {
     pub keypair: KeyPair,
     pub commitment: BigInt,
-    pub blind_factor: BigInt,
+    pub blind_factor: Secret<BigInt>,
 }
 
 impl EphemeralKey {
@@ -143,14 +161,19 @@ impl EphemeralKey {
         EphemeralKey {
             keypair,
             commitment,
-            blind_factor,
+            blind_factor: Secret::new(blind_factor),
         }
     }
 
     pub fn create_from_private_key(x1: &KeyPair, message: &[u8]) -> EphemeralKey {
         let base_point: GE = ECPoint::generator();
-        let hash_private_key_message =
-            HSha256::create_hash(&[&x1.private_key.to_big_int(), &BigInt::from_bytes(message)]);
+        // fixed-width key followed by the raw message, as in bip-schnorr
+        let mut bytes = transcript::to_fixed_bytes(
+            &x1.private_key.expose().to_big_int(),
+            transcript::SCALAR_BYTES,
+        );
+        bytes.extend_from_slice(message);
+        let hash_private_key_message = HSha256::create_hash_from_slice(&bytes);
         let ephemeral_private_key: FE = ECScalar::from(&hash_private_key_message);
         let ephemeral_public_key = base_point.scalar_mul(&ephemeral_private_key.get_element());
         let (commitment, blind_factor) =
@@ -158,10 +181,10 @@ impl EphemeralKey {
         EphemeralKey {
             keypair: KeyPair {
                 public_key: ephemeral_public_key,
-                private_key: ephemeral_private_key,
+                private_key: Secret::new(ephemeral_private_key),
             },
             commitment,
-            blind_factor,
+            blind_factor: Secret::new(blind_factor),
         }
     }
 
@@ -170,7 +193,7 @@ impl EphemeralKey {
             &r_to_test.bytes_compressed_to_big_int(),
             blind_factor,
         );
-        computed_comm == comm
+        secret::ct_eq_bn(computed_comm, comm)
     }
 
     pub fn add_ephemeral_pub_keys(r1: &GE, r2: &GE) -> GE {
@@ -178,26 +201,13 @@ impl EphemeralKey {
     }
 
     pub fn hash_0(r_hat: &GE, apk: &GE, message: &[u8], musig_bit: bool) -> BigInt {
-        if musig_bit {
-            HSha256::create_hash(&[
-                &BigInt::from(0),
-                &r_hat.x_coor().unwrap(),
-                &apk.bytes_compressed_to_big_int(),
-                &BigInt::from_bytes(message),
-            ])
-        } else {
-            HSha256::create_hash(&[
-                &r_hat.x_coor().unwrap(),
-                &apk.bytes_compressed_to_big_int(),
-                &BigInt::from_bytes(message),
-            ])
-        }
+        challenge(&r_hat.x_coor().unwrap(), apk, message, musig_bit)
     }
 
     pub fn sign(r: &EphemeralKey, c: &BigInt, x: &KeyPair, a: &BigInt) -> BigInt {
         let c_fe: FE = ECScalar::from(c);
         let a_fe: FE = ECScalar::from(a);
-        let s_fe = r.keypair.private_key.clone() + (c_fe * x.private_key.clone() * a_fe);
+        let s_fe = *r.keypair.private_key.expose() + (c_fe * x.private_key.expose() * a_fe);
         s_fe.to_big_int()
     }
 
@@ -211,9 +221,9 @@ impl EphemeralKey {
     ) -> BigInt {
         let c_fe: FE = ECScalar::from(c);
         let a_fe: FE = ECScalar::from(a);
-        let s_fe = t.keypair.private_key.clone()
-            + r.keypair.private_key.clone()
-            + (c_fe * x.private_key.clone() * a_fe);
+        let s_fe = *t.keypair.private_key.expose()
+            + r.keypair.private_key.expose()
+            + (c_fe * x.private_key.expose() * a_fe);
         s_fe.to_big_int()
     }
 
@@ -231,11 +241,28 @@ impl EphemeralKey {
     // construct adaptor signature with the secret
     pub fn add_signature_parts_with_secret(s1: BigInt, t: &EphemeralKey, r_tag: &GE) -> (BigInt, BigInt) {
         let s1_fe: FE = ECScalar::from(&s1);
-        let s1_plus_t = s1_fe + t.keypair.private_key.clone();
+        let s1_plus_t = s1_fe + t.keypair.private_key.expose();
         (r_tag.x_coor().unwrap(), s1_plus_t.to_big_int())    
     }
 }
 
+// s'*G + T = R' + c*apk with c = H(R'.x || apk || message)
+pub fn verify_pre_signature(
+    s_pre: &BigInt,
+    r_tag: &GE,
+    T: &GE,
+    apk: &GE,
+    message: &[u8],
+) -> Result<(), Error> {
+    let c: FE = ECScalar::from(&EphemeralKey::hash_0(r_tag, apk, message, true));
+    let s_pre: FE = ECScalar::from(s_pre);
+    if GE::generator() * &s_pre + T == *r_tag + &(apk * &c) {
+        Ok(())
+    } else {
+        Err(InvalidSig)
+    }
+}
+
 // verify full signature
 pub fn verify(
     signature: &BigInt,
@@ -246,6 +273,31 @@ pub fn verify(
 ) -> Result<(), ProofError> {
     let base_point: GE = ECPoint::generator();
 
+    let c = challenge(r_x, apk, message, musig_bit);
+
+    let signature_fe: FE = ECScalar::from(signature);
+    let sG = base_point.scalar_mul(&signature_fe.get_element());
+    let c: FE = ECScalar::from(&c);
+    let cY = apk.scalar_mul(&c.get_element());
+    let sG = sG.sub_point(&cY.get_element());
+    if secret::ct_eq_bn(&sG.x_coor().unwrap(), r_x) {
+        Ok(())
+    } else {
+        Err(ProofError)
+    }
+}
+
+// verify a full signature made with the encoding used before transcript version 1;
+// kept only to migrate existing signatures, new signatures never verify here
+pub fn verify_legacy(
+    signature: &BigInt,
+    r_x: &BigInt,
+    apk: &GE,
+    message: &[u8],
+    musig_bit: bool,
+) -> Result<(), ProofError> {
+    let base_point: GE = ECPoint::generator();
+
     let c = if musig_bit {
         HSha256::create_hash(&[
             &BigInt::from(0),
@@ -266,13 +318,31 @@ pub fn verify(
     let c: FE = ECScalar::from(&c);
     let cY = apk.scalar_mul(&c.get_element());
     let sG = sG.sub_point(&cY.get_element());
-    if sG.x_coor().unwrap().to_hex() == r_x.to_hex() {
+    if secret::ct_eq_bn(&sG.x_coor().unwrap(), r_x) {
         Ok(())
     } else {
         Err(ProofError)
     }
 }
 
+// c = H0(Rtag || apk || message). The single-signer variant keeps the bip-schnorr layout
+// (32-byte R.x, 33-byte key, raw message), which is already unambiguous; the musig variant
+// uses a domain-separated transcript.
+fn challenge(r_x: &BigInt, apk: &GE, message: &[u8], musig_bit: bool) -> BigInt {
+    if musig_bit {
+        let mut transcript = Transcript::new(b"musig/challenge");
+        transcript.append_bn(b"R", r_x);
+        transcript.append_point(b"apk", apk);
+        transcript.append_message(b"message", message);
+        transcript.challenge()
+    } else {
+        let mut bytes = transcript::to_fixed_bytes(r_x, transcript::SCALAR_BYTES);
+        bytes.extend_from_slice(&transcript::point_bytes(apk));
+        bytes.extend_from_slice(message);
+        HSha256::create_hash_from_slice(&bytes)
+    }
+}
+
 // verify partial signature
 pub fn verify_partial(
     signature: &FE,
@@ -285,7 +355,7 @@ pub fn verify_partial(
     let sG = g * signature;
     let cY = key_pub * a * c;
     let sG = sG.sub_point(&cY.get_element());
-    if sG.x_coor().unwrap().to_hex() == *r_x.to_hex() {
+    if secret::ct_eq_bn(&sG.x_coor().unwrap(), r_x) {
         Ok(())
     } else {
         Err(ProofError)
@@ -318,13 +388,13 @@ mod tests {
         // p1 release R1' and p2 test com(R1') = com(R1):
         assert!(EphemeralKey::test_com(
             &party2_ephemeral_key.keypair.public_key,
-            &party2_ephemeral_key.blind_factor,
+            party2_ephemeral_key.blind_factor.expose(),
             party2_commitment
         ));
         // p2 release R2' and p1 test com(R2') = com(R2):
         assert!(EphemeralKey::test_com(
             &party1_ephemeral_key.keypair.public_key,
-            &party1_ephemeral_key.blind_factor,
+            party1_ephemeral_key.blind_factor.expose(),
             party1_commitment
         ));
 
@@ -421,6 +491,59 @@ mod tests {
         assert!(verify(&s, &R, &party1_key.public_key, &message, is_musig).is_ok());
     }
 
+    #[test]
+    fn test_leading_zero_message_bytes() {
+        let message = [0x00, 0x01];
+        let truncated = [0x01];
+        for &is_musig in [false, true].iter() {
+            let party1_key = KeyPair::create();
+            let party1_ephemeral_key = EphemeralKey::create();
+            let party1_h_0 = EphemeralKey::hash_0(
+                &party1_ephemeral_key.keypair.public_key,
+                &party1_key.public_key,
+                &message,
+                is_musig,
+            );
+            let s_tag = EphemeralKey::sign(
+                &party1_ephemeral_key,
+                &party1_h_0,
+                &party1_key,
+                &BigInt::from(1),
+            );
+            let (R, s) = EphemeralKey::add_signature_parts(
+                s_tag,
+                &BigInt::from(0),
+                &party1_ephemeral_key.keypair.public_key,
+            );
+            assert!(verify(&s, &R, &party1_key.public_key, &message, is_musig).is_ok());
+            assert!(verify(&s, &R, &party1_key.public_key, &truncated, is_musig).is_err());
+        }
+    }
+
+    #[test]
+    fn test_verify_legacy_signature() {
+        let is_musig = true;
+        let message: [u8; 4] = [79, 77, 69, 82];
+        let party1_key = KeyPair::create();
+        let party1_ephemeral_key = EphemeralKey::create();
+        let R = party1_ephemeral_key.keypair.public_key.x_coor().unwrap();
+        // challenge as computed before transcript version 1
+        let legacy_c = HSha256::create_hash(&[
+            &BigInt::from(0),
+            &R,
+            &party1_key.public_key.bytes_compressed_to_big_int(),
+            &BigInt::from_bytes(&message),
+        ]);
+        let s = EphemeralKey::sign(
+            &party1_ephemeral_key,
+            &legacy_c,
+            &party1_key,
+            &BigInt::from(1),
+        );
+        assert!(verify_legacy(&s, &R, &party1_key.public_key, &message, is_musig).is_ok());
+        assert!(verify(&s, &R, &party1_key.public_key, &message, is_musig).is_err());
+    }
+
     //this test works only for curvesecp256k1
     #[test]
     fn test_schnorr_bip_test_vector_2() {
@@ -18,7 +18,6 @@
 
 #![allow(non_snake_case)]
 
-use curv::arithmetic::traits::Converter;
 use curv::cryptographic_primitives::commitments::hash_commitment::HashCommitment;
 use curv::cryptographic_primitives::commitments::traits::*;
 use curv::cryptographic_primitives::hashing::hash_sha256::HSha256;
@@ -27,6 +26,9 @@ use curv::cryptographic_primitives::proofs::*;
 use curv::elliptic::curves::traits::*;
 use curv::BigInt;
 
+use protocols::secret::{self, Secret};
+use protocols::transcript::Transcript;
+
 type GE = curv::elliptic::curves::secp256_k1::GE;
 type FE = curv::elliptic::curves::secp256_k1::FE;
 
@@ -36,7 +38,7 @@ const Nv: usize = 2;
 #[derive(Debug, Clone)]
 pub struct KeyPair {
     pub public_key: GE,
-    private_key: FE,
+    private_key: Secret<FE>,
 }
 
 impl KeyPair {
@@ -46,7 +48,7 @@ impl KeyPair {
         let public_key = ec_point.scalar_mul(&private_key.get_element());
         KeyPair {
             public_key,
-            private_key,
+            private_key: Secret::new(private_key),
         }
     }
 
@@ -56,9 +58,14 @@ impl KeyPair {
         let public_key = ec_point.scalar_mul(&private_key.get_element());
         KeyPair {
             public_key,
-            private_key,
+            private_key: Secret::new(private_key),
         }
     }
+
+    // the only way to get the private key out, for the keystore; key types are not Serialize
+    pub fn export_private_key(&self) -> Secret<FE> {
+        self.private_key.clone()
+    }
 }
 
 #[derive(Debug)]
@@ -121,14 +128,16 @@ impl KeyAgg {
 pub struct EphemeralKey {
     pub keypair: KeyPair,
     pub commitment: BigInt,
-    pub blind_factor: BigInt,
+    pub blind_factor: Secret<BigInt>,
 }
 
 impl EphemeralKey {
     pub fn create_from_private_key(x1: &KeyPair, pad: usize) -> EphemeralKey {
         let base_point: GE = ECPoint::generator();
-        let hash_private_key_message =
-            HSha256::create_hash(&[&x1.private_key.to_big_int(), &BigInt::from(pad as i32)]);
+        let mut transcript = Transcript::new(b"musig2/nonce");
+        transcript.append_scalar(b"x", x1.private_key.expose());
+        transcript.append_u64(b"pad", pad as u64);
+        let hash_private_key_message = transcript.challenge();
         let ephemeral_private_key: FE = ECScalar::from(&hash_private_key_message);
         let ephemeral_public_key = base_point.scalar_mul(&ephemeral_private_key.get_element());
         let (commitment, blind_factor) =
@@ -136,10 +145,10 @@ impl EphemeralKey {
         EphemeralKey {
             keypair: KeyPair {
                 public_key: ephemeral_public_key,
-                private_key: ephemeral_private_key,
+                private_key: Secret::new(ephemeral_private_key),
             },
             commitment,
-            blind_factor,
+            blind_factor: Secret::new(blind_factor),
         }
     }
 
@@ -153,19 +162,20 @@ impl EphemeralKey {
     }
 }
 
-pub fn hash_tag_challange(r_hat: &GE, X_tilde: &GE) -> BigInt {
-    HSha256::create_hash(&[
-        &r_hat.x_coor().unwrap(),
-        &X_tilde.bytes_compressed_to_big_int(),
-    ])
+pub fn hash_tag_challange(r_hat: &GE, X_tilde: &GE, message: &[u8]) -> BigInt {
+    let mut transcript = Transcript::new(b"schnorr/challenge");
+    transcript.append_bn(b"R", &r_hat.x_coor().unwrap());
+    transcript.append_point(b"X", X_tilde);
+    transcript.append_message(b"message", message);
+    transcript.challenge()
 }
 
-pub fn hash_tag(r_hat: &GE, X_tilde: &GE) -> BigInt {
-    HSha256::create_hash(&[
-        &BigInt::from(0),
-        &r_hat.x_coor().unwrap(),
-        &X_tilde.bytes_compressed_to_big_int(),
-    ])
+pub fn hash_tag(r_hat: &GE, X_tilde: &GE, message: &[u8]) -> BigInt {
+    let mut transcript = Transcript::new(b"musig2/challenge");
+    transcript.append_bn(b"R", &r_hat.x_coor().unwrap());
+    transcript.append_point(b"X", X_tilde);
+    transcript.append_message(b"message", message);
+    transcript.challenge()
 }
 
 pub fn sign(x: KeyPair) -> (Vec<GE>, State) {
@@ -217,9 +227,9 @@ impl State {
             .iter()
             .zip(b_coefficients)
             .fold(ECScalar::zero(), |acc, (ephk, b)| {
-                acc + ephk.keypair.private_key * <FE as ECScalar>::from(b)
+                acc + *ephk.keypair.private_key.expose() * <FE as ECScalar>::from(b)
             });
-        let s_fe = lin_comb_ephemeral_i.clone() + (c_fe * x.private_key.clone() * a_fe);
+        let s_fe = lin_comb_ephemeral_i.clone() + (c_fe * x.private_key.expose() * a_fe);
         s_fe
     }
 
@@ -230,20 +240,32 @@ impl State {
         pks: &Vec<GE>,
         msg_vec: Vec<Vec<GE>>,
         party_index: usize,
+    ) -> (BigInt, GE, Vec<BigInt>) {
+        self.compute_global_params_with_adaptor(message, pks, msg_vec, party_index, None)
+    }
+
+    // same as compute_global_params, for an adaptor point T the returned R is R + T
+    pub fn compute_global_params_with_adaptor(
+        &self,
+        message: &[u8],
+        pks: &Vec<GE>,
+        msg_vec: Vec<Vec<GE>>,
+        party_index: usize,
+        adaptor: Option<&GE>,
     ) -> (BigInt, GE, Vec<BigInt>) {
         let key_agg = KeyAgg::key_aggregation_n(&pks, party_index);
         let R_j_vec = self.add_ephemeral_keys(&msg_vec);
         let mut b_coefficients: Vec<BigInt> = Vec::new();
         b_coefficients.push(BigInt::from(1));
         for j in 1..Nv {
-            let mut hnon_preimage: Vec<BigInt> = Vec::new();
-            hnon_preimage.push(key_agg.X_tilde.bytes_compressed_to_big_int());
-            for i in 0..Nv {
-                hnon_preimage.push(R_j_vec[i].bytes_compressed_to_big_int());
+            let mut transcript = Transcript::new(b"musig2/nonce-coefficient");
+            transcript.append_point(b"X", &key_agg.X_tilde);
+            for R_j in R_j_vec.iter() {
+                transcript.append_point(b"R", R_j);
             }
-            hnon_preimage.push(BigInt::from_bytes(message));
-            hnon_preimage.push(BigInt::from(j as i32));
-            let b_j = HSha256::create_hash(&hnon_preimage.iter().collect::<Vec<_>>());
+            transcript.append_message(b"message", message);
+            transcript.append_u64(b"j", j as u64);
+            let b_j = transcript.challenge();
             b_coefficients.push(b_j);
         }
 
@@ -254,19 +276,26 @@ impl State {
             .skip(1)
             .map(|(R_j, b_j)| R_j * &<FE as ECScalar>::from(&b_j))
             .fold(R_0, |acc, R_j| acc.add_point(&R_j.get_element()));
-        let c = hash_tag(&R, &key_agg.X_tilde);
+        let R = match adaptor {
+            Some(T) => R.add_point(&T.get_element()),
+            None => R,
+        };
+        let c = hash_tag(&R, &key_agg.X_tilde, message);
         (c, R, b_coefficients)
     }
 
+    // with an adaptor point T the shares combine into a pre-signature, see verify_pre_signature
     pub fn sign_prime(
         &self,
         message: &[u8],
         pks: &Vec<GE>,
         msg_vec: Vec<Vec<GE>>,
         party_index: usize,
+        adaptor: Option<&GE>,
     ) -> (StatePrime, FE) {
         let key_agg = KeyAgg::key_aggregation_n(&pks, party_index);
-        let (c, R, b_coefficients) = self.compute_global_params(message, pks, msg_vec, party_index);
+        let (c, R, b_coefficients) =
+            self.compute_global_params_with_adaptor(message, pks, msg_vec, party_index, adaptor);
         let s_i = self.compute_signature_share(&b_coefficients, &c, &self.keypair, &key_agg.a_i);
         (StatePrime { R, s_i }, s_i)
     }
@@ -283,6 +312,37 @@ pub fn sign_double_prime(StatePrime: StatePrime, msg_vec: &Vec<FE>) -> FE {
     msg_vec.iter().fold(s_0, |acc, s_i| acc + s_i)
 }
 
+// pre-signature s' for adaptor point T: s'G + T = R + cX_tilde, where R includes T
+pub fn verify_pre_signature(
+    pre_signature: &FE,
+    R: &GE,
+    T: &GE,
+    X_tilde: &GE,
+    c: &BigInt,
+) -> Result<(), ProofError> {
+    let base_point: GE = ECPoint::generator();
+    let c: FE = ECScalar::from(&c);
+    let sG_plus_T = base_point
+        .scalar_mul(&pre_signature.get_element())
+        .add_point(&T.get_element());
+    let R_plus_cY = R.add_point(&X_tilde.scalar_mul(&c.get_element()).get_element());
+    if sG_plus_T == R_plus_cY {
+        Ok(())
+    } else {
+        Err(ProofError)
+    }
+}
+
+// complete a pre-signature with the adaptor secret t
+pub fn adapt(pre_signature: &FE, t: &FE) -> FE {
+    *pre_signature + t
+}
+
+// recover the adaptor secret t from a pre-signature and the published signature
+pub fn extract(signature: &FE, pre_signature: &FE) -> FE {
+    signature.sub(&pre_signature.get_element())
+}
+
 pub fn verify(
     signature: &FE,
     r_x: &BigInt,
@@ -294,7 +354,7 @@ pub fn verify(
     let c: FE = ECScalar::from(&c);
     let cY = X_tilde.scalar_mul(&c.get_element());
     let sG = sG.sub_point(&cY.get_element());
-    if sG.x_coor().unwrap().to_hex() == r_x.to_hex() {
+    if secret::ct_eq_bn(&sG.x_coor().unwrap(), r_x) {
         Ok(())
     } else {
         Err(ProofError)
@@ -303,7 +363,7 @@ pub fn verify(
 
 #[cfg(test)]
 mod tests {
-    use curv::elliptic::curves::secp256_k1::GE;
+    use curv::elliptic::curves::secp256_k1::{FE, GE};
     use protocols::aggsig::musig_two_rounds::*;
 
     extern crate hex;
@@ -334,9 +394,9 @@ mod tests {
 
         //Sign prime: each party creates state'
         let (party_1_StatePrime, party1_msg_round_2) =
-            party_1_state.sign_prime(&message, &pks, party1_received_msg_round_1.clone(), 0);
+            party_1_state.sign_prime(&message, &pks, party1_received_msg_round_1.clone(), 0, None);
         let (party_2_StatePrime, party2_msg_round_2) =
-            party_2_state.sign_prime(&message, &pks, party2_received_msg_round_1.clone(), 1);
+            party_2_state.sign_prime(&message, &pks, party2_received_msg_round_1.clone(), 1, None);
 
         //round 2: sending signature shares
         let party1_received_msg_round_2 = vec![party2_msg_round_2];
@@ -372,4 +432,59 @@ mod tests {
         // verification that the signature is computed correctly
         assert!(verify(&s, &R.x_coor().unwrap(), &party1_key_agg.X_tilde, &c).is_ok());
     }
+
+    #[test]
+    fn test_adaptor_signature_for_two_parties() {
+        let message: [u8; 4] = [79, 77, 69, 82];
+        let party1_key = KeyPair::create();
+        let party2_key = KeyPair::create();
+        let pks: Vec<GE> = vec![party1_key.public_key, party2_key.public_key];
+        let X_tilde = KeyAgg::key_aggregation_n(&pks, 0).X_tilde;
+
+        // adaptor secret t known only to party 1, T = tG known to both
+        let t: FE = ECScalar::new_random();
+        let T = GE::generator() * &t;
+
+        let (party_1_msg_round_1, party_1_state) = sign(party1_key);
+        let (party_2_msg_round_1, party_2_state) = sign(party2_key);
+        let party1_received_msg_round_1 = vec![party_2_msg_round_1];
+        let party2_received_msg_round_1 = vec![party_1_msg_round_1];
+
+        let (party_1_StatePrime, party1_msg_round_2) = party_1_state.sign_prime(
+            &message,
+            &pks,
+            party1_received_msg_round_1.clone(),
+            0,
+            Some(&T),
+        );
+        let (party_2_StatePrime, party2_msg_round_2) = party_2_state.sign_prime(
+            &message,
+            &pks,
+            party2_received_msg_round_1.clone(),
+            1,
+            Some(&T),
+        );
+        assert_eq!(party_1_StatePrime.R, party_2_StatePrime.R);
+        let pre_signature = sign_double_prime(party_1_StatePrime, &vec![party2_msg_round_2]);
+        assert_eq!(
+            pre_signature,
+            sign_double_prime(party_2_StatePrime, &vec![party1_msg_round_2])
+        );
+
+        let (c, R, _) = party_2_state.compute_global_params_with_adaptor(
+            &message,
+            &pks,
+            party2_received_msg_round_1,
+            1,
+            Some(&T),
+        );
+        // the pre-signature verifies against T but is not a valid signature by itself
+        assert!(verify_pre_signature(&pre_signature, &R, &T, &X_tilde, &c).is_ok());
+        assert!(verify(&pre_signature, &R.x_coor().unwrap(), &X_tilde, &c).is_err());
+
+        let s = adapt(&pre_signature, &t);
+        assert!(verify(&s, &R.x_coor().unwrap(), &X_tilde, &c).is_ok());
+        // publishing s reveals t to anyone holding the pre-signature
+        assert_eq!(extract(&s, &pre_signature), t);
+    }
 }
@@ -0,0 +1,303 @@
+#![allow(non_snake_case)]
+/*
+    Multisig Schnorr
+
+    Copyright 2018 by Kzen Networks
+
+    This file is part of Multisig Schnorr library
+    (https://github.com/KZen-networks/multisig-schnorr)
+
+    Multisig Schnorr is free software: you can redistribute
+    it and/or modify it under the terms of the GNU General Public
+    License as published by the Free Software Foundation, either
+    version 3 of the License, or (at your option) any later version.
+
+    @license GPL-3.0+ <https://github.com/KZen-networks/multisig-schnorr/blob/master/LICENSE>
+*/
+
+//! Oracle attestations for Discreet Log Contracts (Dryja, "Discreet Log Contracts").
+//!
+//! The oracle announces an event with its key P_o, a fresh nonce R_o and the possible
+//! outcomes. Its attestation of outcome m is a Schnorr signature s = k + H(R_o || P_o || m) * x
+//! under the announced nonce, so anyone can compute s*G = S_m = R_o + H(R_o || P_o || m) * P_o
+//! before the event. The parties of a contract lock their coins to a `musig_three_rounds` apk
+//! and pre-sign one contract execution transaction (CET) per outcome, each under
+//! R'_m = sum_i R_i + S_m with fresh nonces per outcome. The attestation is the adaptor secret
+//! of exactly one of them. An oracle that attests two outcomes with one nonce reveals x, so
+//! the nonce of an announcement lives in the keystore and attestations are only made by
+//! `KeyStore::attest`, which records R_o in the consume-once nonce ledger first.
+use curv::elliptic::curves::traits::*;
+use curv::BigInt;
+use protocols::aggsig::musig_three_rounds::{verify_pre_signature, EphemeralKey};
+use protocols::secret::Secret;
+use protocols::transcript::Transcript;
+use Error::{self, InvalidKey, InvalidSig};
+
+type GE = curv::elliptic::curves::secp256_k1::GE;
+type FE = curv::elliptic::curves::secp256_k1::FE;
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Announcement {
+    pub P_o: GE,
+    pub R_o: GE,
+    pub outcomes: Vec<Vec<u8>>,
+}
+
+impl Announcement {
+    // S_m = R_o + H(R_o || P_o || m) * P_o
+    pub fn adaptor_point(&self, outcome: &[u8]) -> GE {
+        self.R_o + &(self.P_o * &outcome_challenge(&self.R_o, &self.P_o, outcome))
+    }
+
+    // S_m of every announced outcome, in announcement order
+    pub fn adaptor_points(&self) -> Vec<GE> {
+        self.outcomes
+            .iter()
+            .map(|outcome| self.adaptor_point(outcome))
+            .collect()
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Attestation {
+    pub R_o: GE,
+    pub outcome: Vec<u8>,
+    pub s: FE,
+}
+
+impl Attestation {
+    // s*G = S_m for an announced outcome
+    pub fn verify(&self, announcement: &Announcement) -> Result<(), Error> {
+        if self.R_o != announcement.R_o || !announcement.outcomes.contains(&self.outcome) {
+            return Err(InvalidSig);
+        }
+        if GE::generator() * &self.s == announcement.adaptor_point(&self.outcome) {
+            Ok(())
+        } else {
+            Err(InvalidSig)
+        }
+    }
+}
+
+// the secret nonce k of an announcement, stored with `KeyStore::store_oracle_nonce`
+#[derive(Clone, Debug)]
+pub struct OracleNonce {
+    pub announcement: Announcement,
+    pub(crate) k: Secret<FE>,
+}
+
+#[derive(Debug)]
+pub struct Oracle {
+    pub P_o: GE,
+    x: Secret<FE>,
+}
+
+impl Oracle {
+    pub fn new() -> Oracle {
+        let x: FE = ECScalar::new_random();
+        Oracle {
+            P_o: GE::generator() * &x,
+            x: Secret::new(x),
+        }
+    }
+
+    // the nonce has to be in the keystore before the announcement is published
+    pub fn announce(&self, outcomes: &[Vec<u8>]) -> (Announcement, OracleNonce) {
+        let k: FE = ECScalar::new_random();
+        let announcement = Announcement {
+            P_o: self.P_o,
+            R_o: GE::generator() * &k,
+            outcomes: outcomes.to_vec(),
+        };
+        let nonce = OracleNonce {
+            announcement: announcement.clone(),
+            k: Secret::new(k),
+        };
+        (announcement, nonce)
+    }
+
+    // s = k + H(R_o || P_o || m) * x; only the keystore attests, once it consumed the nonce
+    pub(crate) fn attest(&self, nonce: &OracleNonce, outcome: &[u8]) -> Result<Attestation, Error> {
+        let announcement = &nonce.announcement;
+        if announcement.P_o != self.P_o
+            || !announcement
+                .outcomes
+                .iter()
+                .any(|m| m.as_slice() == outcome)
+        {
+            return Err(InvalidKey);
+        }
+        let c = outcome_challenge(&announcement.R_o, &self.P_o, outcome);
+        Ok(Attestation {
+            R_o: announcement.R_o,
+            outcome: outcome.to_vec(),
+            s: *nonce.k.expose() + &(c * self.x.expose()),
+        })
+    }
+}
+
+impl Default for Oracle {
+    fn default() -> Oracle {
+        Oracle::new()
+    }
+}
+
+// the CET of one outcome, pre-signed under R'_m = sum_i R_i + S_m
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct ContractOutcome {
+    pub outcome: Vec<u8>,
+    pub message: Vec<u8>,
+    pub adaptor_point: GE,
+}
+
+impl ContractOutcome {
+    // messages[i] is the CET of announcement.outcomes[i]
+    pub fn from_announcement(
+        announcement: &Announcement,
+        messages: &[Vec<u8>],
+    ) -> Result<Vec<ContractOutcome>, Error> {
+        if messages.len() != announcement.outcomes.len() {
+            return Err(InvalidKey);
+        }
+        Ok(announcement
+            .outcomes
+            .iter()
+            .zip(messages.iter())
+            .map(|(outcome, message)| ContractOutcome {
+                outcome: outcome.clone(),
+                message: message.clone(),
+                adaptor_point: announcement.adaptor_point(outcome),
+            })
+            .collect())
+    }
+
+    // R'_m from the signers' nonces for this outcome
+    pub fn r_tag(&self, nonces: &[GE]) -> GE {
+        nonces.iter().fold(self.adaptor_point, |acc, nonce| {
+            EphemeralKey::add_ephemeral_pub_keys(&acc, nonce)
+        })
+    }
+
+    // c for `EphemeralKey::sign` of every signer
+    pub fn challenge(&self, r_tag: &GE, apk: &GE) -> BigInt {
+        EphemeralKey::hash_0(r_tag, apk, &self.message, true)
+    }
+
+    pub fn verify_pre_signature(&self, s_pre: &BigInt, r_tag: &GE, apk: &GE) -> Result<(), Error> {
+        verify_pre_signature(s_pre, r_tag, &self.adaptor_point, apk, &self.message)
+    }
+
+    // (R'_m.x, s' + s_o), only for the attested outcome
+    pub fn complete(
+        &self,
+        s_pre: &BigInt,
+        r_tag: &GE,
+        attestation: &Attestation,
+    ) -> Result<(BigInt, BigInt), Error> {
+        if attestation.outcome != self.outcome
+            || GE::generator() * &attestation.s != self.adaptor_point
+        {
+            return Err(InvalidSig);
+        }
+        Ok(EphemeralKey::add_signature_parts(
+            s_pre.clone(),
+            &attestation.s.to_big_int(),
+            r_tag,
+        ))
+    }
+}
+
+fn outcome_challenge(R_o: &GE, P_o: &GE, outcome: &[u8]) -> FE {
+    let mut transcript = Transcript::new(b"dlc-oracle/attestation");
+    transcript.append_point(b"R_o", R_o);
+    transcript.append_point(b"P_o", P_o);
+    transcript.append_message(b"outcome", outcome);
+    transcript.challenge_scalar()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use protocols::aggsig::musig_three_rounds::{verify, KeyAgg, KeyPair};
+
+    #[test]
+    fn test_attestation_completes_one_outcome() {
+        let oracle = Oracle::new();
+        let outcomes = vec![b"win".to_vec(), b"lose".to_vec(), b"draw".to_vec()];
+        let (announcement, nonce) = oracle.announce(&outcomes);
+        let messages = outcomes
+            .iter()
+            .map(|outcome| [b"pay on ".to_vec(), outcome.clone()].concat())
+            .collect::<Vec<Vec<u8>>>();
+        let contract = ContractOutcome::from_announcement(&announcement, &messages).unwrap();
+
+        let party1_key = KeyPair::create();
+        let party2_key = KeyPair::create();
+        let pks = vec![party1_key.public_key, party2_key.public_key];
+        let party1_key_agg = KeyAgg::key_aggregation_n(&pks, 0);
+        let party2_key_agg = KeyAgg::key_aggregation_n(&pks, 1);
+        let apk = party1_key_agg.apk;
+
+        // fresh nonces per outcome, commitments exchanged as in musig_three_rounds
+        let mut pre_signatures = Vec::new();
+        for cet in contract.iter() {
+            let party1_ephemeral_key = EphemeralKey::create();
+            let party2_ephemeral_key = EphemeralKey::create();
+            let r_tag = cet.r_tag(&[
+                party1_ephemeral_key.keypair.public_key,
+                party2_ephemeral_key.keypair.public_key,
+            ]);
+            let c = cet.challenge(&r_tag, &apk);
+            let s1 =
+                EphemeralKey::sign(&party1_ephemeral_key, &c, &party1_key, &party1_key_agg.hash);
+            let s2 =
+                EphemeralKey::sign(&party2_ephemeral_key, &c, &party2_key, &party2_key_agg.hash);
+            let (_, s_pre) = EphemeralKey::add_signature_parts(s1, &s2, &r_tag);
+            assert!(cet.verify_pre_signature(&s_pre, &r_tag, &apk).is_ok());
+            pre_signatures.push((s_pre, r_tag));
+        }
+
+        let attestation = oracle.attest(&nonce, b"lose").unwrap();
+        assert!(attestation.verify(&announcement).is_ok());
+
+        for (cet, (s_pre, r_tag)) in contract.iter().zip(pre_signatures.iter()) {
+            if cet.outcome == b"lose".to_vec() {
+                let (r_x, s) = cet.complete(s_pre, r_tag, &attestation).unwrap();
+                assert!(verify(&s, &r_x, &apk, &cet.message, true).is_ok());
+            } else {
+                assert_eq!(cet.complete(s_pre, r_tag, &attestation), Err(InvalidSig));
+                let forged = Attestation {
+                    outcome: cet.outcome.clone(),
+                    ..attestation.clone()
+                };
+                assert_eq!(cet.complete(s_pre, r_tag, &forged), Err(InvalidSig));
+                let (r_x, s) = EphemeralKey::add_signature_parts(
+                    s_pre.clone(),
+                    &attestation.s.to_big_int(),
+                    r_tag,
+                );
+                assert!(verify(&s, &r_x, &apk, &cet.message, true).is_err());
+            }
+        }
+    }
+
+    #[test]
+    fn test_oracle_rejects_unannounced_outcomes() {
+        let oracle = Oracle::new();
+        let (announcement, nonce) = oracle.announce(&[b"yes".to_vec(), b"no".to_vec()]);
+        assert_eq!(oracle.attest(&nonce, b"maybe"), Err(InvalidKey));
+        assert_eq!(Oracle::new().attest(&nonce, b"yes"), Err(InvalidKey));
+        assert_eq!(announcement.adaptor_points().len(), 2);
+
+        let mut attestation = oracle.attest(&nonce, b"yes").unwrap();
+        assert_eq!(
+            GE::generator() * &attestation.s,
+            announcement.adaptor_points()[0]
+        );
+        attestation.outcome = b"no".to_vec();
+        assert_eq!(attestation.verify(&announcement), Err(InvalidSig));
+
+        let (other, _) = Oracle::new().announce(&announcement.outcomes);
+        assert!(ContractOutcome::from_announcement(&other, &[Vec::new()]).is_err());
+    }
+}
@@ -0,0 +1,216 @@
+/*
+    Multisig Schnorr
+
+    Copyright 2018 by Kzen Networks
+
+    This file is part of Multisig Schnorr library
+    (https://github.com/KZen-networks/multisig-schnorr)
+
+    Multisig Schnorr is free software: you can redistribute
+    it and/or modify it under the terms of the GNU General Public
+    License as published by the Free Software Foundation, either
+    version 3 of the License, or (at your option) any later version.
+
+    @license GPL-3.0+ <https://github.com/KZen-networks/multisig-schnorr/blob/master/LICENSE>
+*/
+#![allow(non_snake_case)]
+
+//! Verifiable encrypted backup of a private key or threshold share.
+//!
+//! The secret x is split into segments and each segment is ElGamal encrypted to a recovery
+//! public key Y (centipede segmentation). The attached proof shows that the ciphertexts
+//! decrypt to the discrete log of Q = xG, so a backup service can check a backup against
+//! the party's public key without being able to open it.
+use centipede::juggling::proof_system::{Helgamalsegmented, Proof, Witness};
+use centipede::juggling::segmentation::Msegmentation;
+use curv::elliptic::curves::traits::*;
+use protocols::secret::Secret;
+use Error::{self, InvalidBackup};
+
+type GE = curv::elliptic::curves::secp256_k1::GE;
+type FE = curv::elliptic::curves::secp256_k1::FE;
+
+// 32 segments of 8 bits cover a secp256k1 scalar; decryption brute-forces 2^8 values per segment
+pub const SEGMENT_SIZE: usize = 8;
+pub const NUM_OF_SEGMENTS: usize = 32;
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct EncryptedBackup {
+    pub encryptions: Helgamalsegmented,
+    pub proof: Proof,
+    pub recovery_public_key: GE,
+}
+
+impl EncryptedBackup {
+    pub fn create(secret: &FE, recovery_public_key: &GE) -> EncryptedBackup {
+        let G: GE = ECPoint::generator();
+        let (witness, encryptions) = Msegmentation::to_encrypted_segments(
+            secret,
+            &SEGMENT_SIZE,
+            NUM_OF_SEGMENTS,
+            recovery_public_key,
+            &G,
+        );
+        EncryptedBackup::from_segments(&witness, encryptions, recovery_public_key)
+    }
+
+    pub fn from_segments(
+        witness: &Witness,
+        encryptions: Helgamalsegmented,
+        recovery_public_key: &GE,
+    ) -> EncryptedBackup {
+        let G: GE = ECPoint::generator();
+        let proof = Proof::prove(
+            witness,
+            &encryptions,
+            &G,
+            recovery_public_key,
+            &SEGMENT_SIZE,
+        );
+        EncryptedBackup {
+            encryptions,
+            proof,
+            recovery_public_key: *recovery_public_key,
+        }
+    }
+
+    /// checks, without decrypting, that the backup holds the discrete log of `public_key`
+    pub fn verify(&self, public_key: &GE) -> Result<(), Error> {
+        let G: GE = ECPoint::generator();
+        self.proof
+            .verify(
+                &self.encryptions,
+                &G,
+                &self.recovery_public_key,
+                public_key,
+                &SEGMENT_SIZE,
+            )
+            .map_err(|_| InvalidBackup)
+    }
+
+    /// decrypts the backup and checks the result against `public_key`
+    pub fn restore(&self, recovery_private_key: &FE, public_key: &GE) -> Result<FE, Error> {
+        let G: GE = ECPoint::generator();
+        if G * recovery_private_key != self.recovery_public_key {
+            return Err(InvalidBackup);
+        }
+        let secret =
+            Msegmentation::decrypt(&self.encryptions, &G, recovery_private_key, &SEGMENT_SIZE)
+                .map_err(|_| InvalidBackup)?;
+        if G * &secret == *public_key {
+            Ok(secret)
+        } else {
+            Err(InvalidBackup)
+        }
+    }
+}
+
+/// backup of a threshold share x_i; the group key y is public and is not part of the backup
+pub fn backup_share(x_i: &Secret<FE>, recovery_public_key: &GE) -> EncryptedBackup {
+    EncryptedBackup::create(x_i.expose(), recovery_public_key)
+}
+
+/// restores (y, x_i) of a threshold share. public_share is x_i*G, computed from the VSS
+/// commitments of the key generation
+pub fn restore_share(
+    backup: &EncryptedBackup,
+    recovery_private_key: &FE,
+    y: &GE,
+    public_share: &GE,
+) -> Result<(GE, Secret<FE>), Error> {
+    let x_i = backup.restore(recovery_private_key, public_share)?;
+    Ok((*y, Secret::new(x_i)))
+}
+
+#[cfg(test)]
+mod tests {
+    use curv::cryptographic_primitives::secret_sharing::feldman_vss::VerifiableSS;
+    use curv::elliptic::curves::secp256_k1::{FE, GE};
+    use curv::elliptic::curves::traits::*;
+
+    use protocols::multisig::KeyPair;
+    use protocols::secret::Secret;
+    use protocols::thresholdsig::bitcoin_schnorr;
+    use protocols::thresholdsig::zilliqa_schnorr::SharedKeys;
+
+    fn recovery_keys() -> (FE, GE) {
+        let recovery_private_key: FE = ECScalar::new_random();
+        let recovery_public_key = GE::generator() * &recovery_private_key;
+        (recovery_private_key, recovery_public_key)
+    }
+
+    #[test]
+    fn test_multisig_key_backup() {
+        let (recovery_private_key, recovery_public_key) = recovery_keys();
+        let key_pair = KeyPair::create();
+
+        let backup = key_pair.backup(&recovery_public_key);
+        assert!(backup.verify(&key_pair.public_key).is_ok());
+        // the proof is bound to the key it was made for
+        assert!(backup.verify(&KeyPair::create().public_key).is_err());
+
+        let restored = KeyPair::restore(&backup, &recovery_private_key, &key_pair.public_key)
+            .expect("restore failed");
+        assert_eq!(restored.public_key, key_pair.public_key);
+
+        let (wrong_private_key, _) = recovery_keys();
+        assert!(KeyPair::restore(&backup, &wrong_private_key, &key_pair.public_key).is_err());
+    }
+
+    #[test]
+    fn test_threshold_share_backup() {
+        let (recovery_private_key, recovery_public_key) = recovery_keys();
+        let secret: FE = ECScalar::new_random();
+        let y = GE::generator() * &secret;
+        let (vss_scheme, secret_shares) = VerifiableSS::share(1, 3, &secret);
+        // both flavours back up shares of the same key
+        let zilliqa_keys = SharedKeys {
+            y,
+            x_i: Secret::new(secret_shares[0]),
+        };
+        let bitcoin_keys = bitcoin_schnorr::SharedKeys {
+            y,
+            x_i: Secret::new(secret_shares[1]),
+        };
+
+        // the public share x_i*G is known to everyone from the VSS commitments
+        let public_share_1 = vss_scheme.get_point_commitment(1);
+        let public_share_2 = vss_scheme.get_point_commitment(2);
+        let zilliqa_backup = zilliqa_keys.backup(&recovery_public_key);
+        let bitcoin_backup = bitcoin_keys.backup(&recovery_public_key);
+        assert!(zilliqa_backup.verify(&public_share_1).is_ok());
+        assert!(bitcoin_backup.verify(&public_share_2).is_ok());
+        // a backup only verifies against the share it was made for
+        assert!(zilliqa_backup.verify(&public_share_2).is_err());
+        assert!(bitcoin_backup.verify(&public_share_1).is_err());
+        assert!(
+            SharedKeys::restore(&zilliqa_backup, &recovery_private_key, &y, &public_share_2)
+                .is_err()
+        );
+
+        let restored_1 =
+            SharedKeys::restore(&zilliqa_backup, &recovery_private_key, &y, &public_share_1)
+                .expect("restore failed");
+        let restored_2 = bitcoin_schnorr::SharedKeys::restore(
+            &bitcoin_backup,
+            &recovery_private_key,
+            &y,
+            &public_share_2,
+        )
+        .expect("restore failed");
+        assert_eq!(restored_1.y, y);
+        assert_eq!(restored_2.y, y);
+        assert!(vss_scheme
+            .validate_share(restored_1.x_i.expose(), 1)
+            .is_ok());
+        assert!(vss_scheme
+            .validate_share(restored_2.x_i.expose(), 2)
+            .is_ok());
+        // the restored shares still reach the threshold together
+        let reconstructed = vss_scheme.reconstruct(
+            &[0, 1],
+            &[*restored_1.x_i.expose(), *restored_2.x_i.expose()],
+        );
+        assert_eq!(reconstructed, secret);
+    }
+}
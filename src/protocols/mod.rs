@@ -20,3 +20,9 @@ pub mod multisig;
 // {t,n}- Signatures based on Provable Secure Distributed Schnorr Signatures
 // ( https://github.com/KZen-networks/multi-party-schnorr/blob/master/papers/provably_secure_distributed_schnorr_signatures_and_a_threshold_scheme.pdf)
 pub mod thresholdsig;
+// zeroizing, redacted wrappers for secret material and constant-time comparisons
+pub mod secret;
+// verifiable encrypted backups of keys and threshold shares
+pub mod backup;
+// injective, domain-separated encoding of challenge hash inputs
+pub mod transcript;
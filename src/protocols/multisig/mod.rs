@@ -18,15 +18,15 @@
 //! Schnorr {n,n}-Signatures based on Accountable-Subgroup Multisignatures
 //!
 //See (https://pdfs.semanticscholar.org/6bf4/f9450e7a8e31c106a8670b961de4735589cf.pdf)
-use curv::arithmetic::Converter;
 use curv::elliptic::curves::traits::*;
-use curv::BigInt;
 
 use centipede::juggling::proof_system::{Helgamalsegmented, Witness};
 use centipede::juggling::segmentation::Msegmentation;
-use curv::cryptographic_primitives::hashing::hash_sha256::HSha256;
-use curv::cryptographic_primitives::hashing::traits::*;
-use protocols::multisig;
+use curv::cryptographic_primitives::hashing::merkle_tree::MT256;
+use protocols::backup::{EncryptedBackup, NUM_OF_SEGMENTS, SEGMENT_SIZE};
+use protocols::secret::Secret;
+use protocols::transcript::Transcript;
+use Error::{self, InvalidKey, InvalidSig};
 
 type GE = curv::elliptic::curves::secp256_k1::GE;
 type FE = curv::elliptic::curves::secp256_k1::FE;
@@ -41,7 +41,7 @@ pub struct Keys {
 #[derive(Debug, Clone)]
 pub struct KeyPair {
     pub public_key: GE,
-    private_key: FE,
+    private_key: Secret<FE>,
 }
 
 impl KeyPair {
@@ -51,7 +51,7 @@ impl KeyPair {
         let public_key = ec_point * &private_key;
         KeyPair {
             public_key,
-            private_key,
+            private_key: Secret::new(private_key),
         }
     }
 
@@ -61,14 +61,19 @@ impl KeyPair {
 
         KeyPair {
             public_key,
-            private_key,
+            private_key: Secret::new(private_key),
         }
     }
 
+    // the only way to get the private key out, for the keystore; key types are not Serialize
+    pub fn export_private_key(&self) -> Secret<FE> {
+        self.private_key.clone()
+    }
+
     pub fn update_key_pair(&mut self, to_add: FE) {
-        self.private_key = to_add + &self.private_key;
+        self.private_key = Secret::new(to_add + self.private_key.expose());
         let g: GE = ECPoint::generator();
-        self.public_key = g * &self.private_key;
+        self.public_key = g * self.private_key.expose();
     }
 
     pub fn to_encrypted_segment(
@@ -79,13 +84,30 @@ impl KeyPair {
         g: &GE,
     ) -> (Witness, Helgamalsegmented) {
         Msegmentation::to_encrypted_segments(
-            &self.private_key,
+            self.private_key.expose(),
             &segment_size,
             num_of_segments,
             pub_ke_y,
             g,
         )
     }
+
+    // verifiable encrypted backup of the private key to a recovery key
+    pub fn backup(&self, recovery_public_key: &GE) -> EncryptedBackup {
+        let g: GE = ECPoint::generator();
+        let (witness, encryptions) =
+            self.to_encrypted_segment(&SEGMENT_SIZE, NUM_OF_SEGMENTS, recovery_public_key, &g);
+        EncryptedBackup::from_segments(&witness, encryptions, recovery_public_key)
+    }
+
+    pub fn restore(
+        backup: &EncryptedBackup,
+        recovery_private_key: &FE,
+        public_key: &GE,
+    ) -> Result<KeyPair, Error> {
+        let private_key = backup.restore(recovery_private_key, public_key)?;
+        Ok(KeyPair::create_from_private_key(private_key))
+    }
 }
 
 impl Keys {
@@ -119,20 +141,22 @@ impl Keys {
     }
 
     pub fn collect_and_compute_challenge(ix_vec: &[Vec<GE>]) -> FE {
-        let concat_vec = ix_vec.iter().fold(Vec::new(), |mut acc, x| {
-            acc.extend_from_slice(x);
-            acc
-        });
-        let ref_vec = concat_vec.iter().collect::<Vec<&GE>>();
-        multisig::hash_4(&ref_vec[..])
+        let mut transcript = Transcript::new(b"asm/key-gen");
+        for ix in ix_vec {
+            for point in ix {
+                transcript.append_point(b"key", point);
+            }
+        }
+        transcript.challenge_scalar()
     }
 }
 
 pub fn partial_sign(keys: &Keys, e: FE) -> FE {
-    e * &keys.I.private_key + &keys.X.private_key
+    e * keys.I.private_key.expose() + keys.X.private_key.expose()
 }
 
-pub fn verify<'a>(I: &GE, sig: &Signature, e: &FE) -> Result<(), &'a str> {
+// y*G = X + e*I for a given challenge e, used for the key generation proofs of possession
+pub fn verify_with_challenge<'a>(I: &GE, sig: &Signature, e: &FE) -> Result<(), &'a str> {
     let X = &sig.X;
     let y = &sig.y;
     let base_point: GE = ECPoint::generator();
@@ -146,13 +170,162 @@ pub fn verify<'a>(I: &GE, sig: &Signature, e: &FE) -> Result<(), &'a str> {
     }
 }
 
-fn hash_4(key_list: &[&GE]) -> FE {
-    let four_fe: FE = ECScalar::from(&BigInt::from(4));
+// verify a subgroup signature on message, recomputing the challenge from the group
+pub fn verify(group: &Group, message: &[u8], signature: &SubgroupSignature) -> Result<(), Error> {
+    group.validate()?;
+    let I_S = group.subgroup_key(&signature.signers)?;
+    let e = EphKey::subgroup_challenge(&signature.X, message, &I_S, group, &signature.signers);
     let base_point: GE = ECPoint::generator();
-    let four_ge = base_point * four_fe;
-    let mut four_ge_vec = vec![&four_ge];
-    four_ge_vec.extend(key_list);
-    HSha256::create_hash_from_ge(&four_ge_vec)
+    if base_point * &signature.y == signature.X + &(I_S * &e) {
+        Ok(())
+    } else {
+        Err(InvalidSig)
+    }
+}
+
+// the full member list of a group; the Merkle root of the member keys is the group commitment
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Group {
+    pub members: Vec<GE>,
+    pub root: [u8; 32],
+}
+
+impl Group {
+    pub fn new(members: Vec<GE>) -> Group {
+        assert!(!members.is_empty());
+        let root = merkle_root(&members);
+        Group { members, root }
+    }
+
+    // check that the member list is the one committed to by the root
+    pub fn validate(&self) -> Result<(), Error> {
+        if !self.members.is_empty() && merkle_root(&self.members) == self.root {
+            Ok(())
+        } else {
+            Err(InvalidKey)
+        }
+    }
+
+    // I_S: the sum of the public keys of the members in S
+    pub fn subgroup_key(&self, signers: &SignerSet) -> Result<GE, Error> {
+        let pub_key_vec = self.subgroup_members(signers)?;
+        let first_pub_key = pub_key_vec[0];
+        Ok(pub_key_vec
+            .iter()
+            .skip(1)
+            .fold(first_pub_key, |acc, x| acc.add_point(&x.get_element())))
+    }
+
+    pub fn subgroup_members(&self, signers: &SignerSet) -> Result<Vec<GE>, Error> {
+        signers.validate(self.members.len())?;
+        Ok(signers
+            .indices()
+            .iter()
+            .map(|i| self.members[*i])
+            .collect::<Vec<GE>>())
+    }
+}
+
+// ASM key generation (p11 in the paper): every member broadcasts (I_i, X_i), receives the joint
+// challenge e and answers with y_i = e*s_i + x_i, a proof of possession of s_i bound to all
+// broadcast keys. A rogue key I_r chosen as a function of the other keys cannot be answered.
+pub struct GroupSetup {
+    ix_vec: Vec<Vec<GE>>,
+    e: FE,
+}
+
+impl GroupSetup {
+    // round 1: the broadcasts of all members, in member order
+    pub fn new(ix_vec: Vec<Vec<GE>>) -> Result<GroupSetup, Error> {
+        if ix_vec.is_empty() || ix_vec.iter().any(|ix| ix.len() != 2) {
+            return Err(InvalidKey);
+        }
+        let duplicate = (0..ix_vec.len())
+            .any(|i| (0..i).any(|j| ix_vec[i][0] == ix_vec[j][0] || ix_vec[i][1] == ix_vec[j][1]));
+        if duplicate {
+            return Err(InvalidKey);
+        }
+        let e = Keys::collect_and_compute_challenge(&ix_vec);
+        Ok(GroupSetup { ix_vec, e })
+    }
+
+    pub fn challenge(&self) -> FE {
+        self.e
+    }
+
+    // indices of the members whose proof of possession does not verify
+    pub fn faulty_members(&self, y_vec: &[FE]) -> Vec<usize> {
+        (0..self.ix_vec.len())
+            .filter(|i| {
+                let ok = match y_vec.get(*i) {
+                    Some(y_i) => {
+                        let sig = Signature::set_signature(&self.ix_vec[*i][1], y_i);
+                        verify_with_challenge(&self.ix_vec[*i][0], &sig, &self.e).is_ok()
+                    }
+                    None => false,
+                };
+                !ok
+            })
+            .collect::<Vec<usize>>()
+    }
+
+    // round 2: check every proof of possession and output the group descriptor
+    pub fn finalize(&self, y_vec: &[FE]) -> Result<Group, Error> {
+        if y_vec.len() != self.ix_vec.len() || !self.faulty_members(y_vec).is_empty() {
+            return Err(InvalidKey);
+        }
+        Ok(Group::new(
+            self.ix_vec.iter().map(|ix| ix[0]).collect::<Vec<GE>>(),
+        ))
+    }
+}
+
+fn merkle_root(members: &[GE]) -> [u8; 32] {
+    let mt256 = MT256::create_tree(&members.to_vec());
+    *mt256.get_root()
+}
+
+// the signing subset S, as a bitmap over the group member list
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SignerSet {
+    pub bitmap: Vec<u8>,
+}
+
+impl SignerSet {
+    pub fn from_indices(group_size: usize, indices: &[usize]) -> Result<SignerSet, Error> {
+        let mut bitmap = vec![0u8; (group_size + 7) / 8];
+        for &i in indices {
+            if i >= group_size {
+                return Err(InvalidKey);
+            }
+            bitmap[i / 8] |= 1 << (i % 8);
+        }
+        let signers = SignerSet { bitmap };
+        signers.validate(group_size)?;
+        Ok(signers)
+    }
+
+    pub fn contains(&self, index: usize) -> bool {
+        index / 8 < self.bitmap.len() && self.bitmap[index / 8] & (1 << (index % 8)) != 0
+    }
+
+    pub fn indices(&self) -> Vec<usize> {
+        (0..self.bitmap.len() * 8)
+            .filter(|i| self.contains(*i))
+            .collect::<Vec<usize>>()
+    }
+
+    // S must be a non-empty subset of a group of size group_size
+    pub fn validate(&self, group_size: usize) -> Result<(), Error> {
+        let indices = self.indices();
+        if self.bitmap.len() != (group_size + 7) / 8
+            || indices.is_empty()
+            || indices.iter().any(|i| *i >= group_size)
+        {
+            return Err(InvalidKey);
+        }
+        Ok(())
+    }
 }
 
 pub struct EphKey {
@@ -168,28 +341,64 @@ impl EphKey {
     //signing steps 2,3
     // we treat S as a list of public keys and compute a sum.
     pub fn compute_joint_comm_e(
-        mut pub_key_vec: Vec<GE>,
-        mut eph_pub_key_vec: Vec<GE>,
+        pub_key_vec: Vec<GE>,
+        eph_pub_key_vec: Vec<GE>,
         message: &[u8],
     ) -> (GE, GE, FE) {
-        let first_pub_key = pub_key_vec.remove(0);
-        let sum_pub = pub_key_vec
-            .iter()
-            .fold(first_pub_key, |acc, x| acc.add_point(&x.get_element()));
-        let first_eph_pub_key = eph_pub_key_vec.remove(0);
-        let sum_pub_eph = eph_pub_key_vec
-            .iter()
-            .fold(first_eph_pub_key, |acc, x| acc.add_point(&x.get_element()));
-        //TODO: maybe there is a better way?
-        let m_fe: FE = ECScalar::from(&BigInt::from_bytes(message));
-        let base_point: GE = GE::generator();
-        let m_ge = base_point.scalar_mul(&m_fe.get_element());
-        let e = multisig::hash_4(&[&sum_pub_eph, &m_ge, &sum_pub]);
+        let sum_pub = sum_points(pub_key_vec);
+        let sum_pub_eph = sum_points(eph_pub_key_vec);
+        let e = EphKey::challenge(&sum_pub_eph, message, &sum_pub);
         (sum_pub, sum_pub_eph, e)
     }
 
+    // e = H(X || I || message), the message is hashed as an arbitrary-length byte string
+    fn challenge(X: &GE, message: &[u8], I: &GE) -> FE {
+        let mut transcript = Transcript::new(b"asm/challenge");
+        transcript.append_point(b"X", X);
+        transcript.append_point(b"I", I);
+        transcript.append_message(b"message", message);
+        transcript.challenge_scalar()
+    }
+
+    // e = H(X || I_S || root || S || message): the signature commits to the group and to
+    // the signing subset, not only to the subgroup key
+    fn subgroup_challenge(
+        X: &GE,
+        message: &[u8],
+        I_S: &GE,
+        group: &Group,
+        signers: &SignerSet,
+    ) -> FE {
+        let mut transcript = Transcript::new(b"asm/subgroup-challenge");
+        transcript.append_point(b"X", X);
+        transcript.append_point(b"I_S", I_S);
+        transcript.append_message(b"root", &group.root);
+        transcript.append_message(b"signers", &signers.bitmap);
+        transcript.append_message(b"message", message);
+        transcript.challenge_scalar()
+    }
+
+    // signing steps 2,3 for a subgroup S of a committed group:
+    // I_S is recomputed from the group member list instead of trusting the signers' keys
+    pub fn compute_subgroup_comm_e(
+        group: &Group,
+        signers: &SignerSet,
+        eph_pub_key_vec: Vec<GE>,
+        message: &[u8],
+    ) -> Result<(GE, GE, FE), Error> {
+        group.validate()?;
+        let pub_key_vec = group.subgroup_members(signers)?;
+        if eph_pub_key_vec.len() != pub_key_vec.len() {
+            return Err(InvalidKey);
+        }
+        let I_S = sum_points(pub_key_vec);
+        let X = sum_points(eph_pub_key_vec);
+        let e = EphKey::subgroup_challenge(&X, message, &I_S, group, signers);
+        Ok((I_S, X, e))
+    }
+
     pub fn partial_sign(&self, local_keys: &KeyPair, es: FE) -> FE {
-        es * &local_keys.private_key + &self.eph_key_pair.private_key
+        es * local_keys.private_key.expose() + self.eph_key_pair.private_key.expose()
     }
 
     pub fn add_signature_parts(sig_vec: Vec<FE>) -> FE {
@@ -202,6 +411,13 @@ impl EphKey {
     }
 }
 
+fn sum_points(mut points: Vec<GE>) -> GE {
+    let first = points.remove(0);
+    points
+        .iter()
+        .fold(first, |acc, x| acc.add_point(&x.get_element()))
+}
+
 pub struct Signature {
     X: GE,
     y: FE,
@@ -216,4 +432,18 @@ impl Signature {
     }
 }
 
+// signature of a subgroup S, accountable: S is part of the signature
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SubgroupSignature {
+    pub signers: SignerSet,
+    pub X: GE,
+    pub y: FE,
+}
+
+impl SubgroupSignature {
+    pub fn verify(&self, group: &Group, message: &[u8]) -> Result<(), Error> {
+        verify(group, message, self)
+    }
+}
+
 mod test;
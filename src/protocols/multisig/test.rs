@@ -16,12 +16,17 @@
 
 #[cfg(test)]
 mod tests {
+    use curv::arithmetic::traits::Converter;
     use curv::cryptographic_primitives::hashing::merkle_tree::MT256;
     use curv::elliptic::curves::secp256_k1::FE;
     use curv::elliptic::curves::secp256_k1::GE;
-    use curv::elliptic::curves::traits::ECScalar;
+    use curv::elliptic::curves::traits::{ECPoint, ECScalar};
+    use curv::BigInt;
 
-    use protocols::multisig::{partial_sign, verify, EphKey, Keys, Signature};
+    use protocols::multisig::{
+        partial_sign, verify, verify_with_challenge, EphKey, Group, GroupSetup, Keys, Signature,
+        SignerSet, SubgroupSignature,
+    };
 
     #[test]
     fn two_party_key_gen() {
@@ -43,8 +48,8 @@ mod tests {
         let sig1 = Signature::set_signature(&keys_1.X.public_key, &y1);
         let sig2 = Signature::set_signature(&keys_2.X.public_key, &y2);
         // partial verify
-        assert!(verify(&keys_1.I.public_key, &sig1, &e).is_ok());
-        assert!(verify(&keys_2.I.public_key, &sig2, &e).is_ok());
+        assert!(verify_with_challenge(&keys_1.I.public_key, &sig1, &e).is_ok());
+        assert!(verify_with_challenge(&keys_2.I.public_key, &sig2, &e).is_ok());
 
         // merkle tree (in case needed)
 
@@ -72,9 +77,184 @@ mod tests {
         let y2 = party2_com.partial_sign(&keys_2.I, es.clone());
         let y = EphKey::add_signature_parts(vec![y1, y2]);
         let sig = Signature::set_signature(&Xt, &y);
-        assert!(verify(&It, &sig, &es).is_ok());
+        assert!(verify_with_challenge(&It, &sig, &es).is_ok());
 
         assert!(MT256::<GE>::validate_proof(&proof1, root).is_ok());
         assert!(MT256::<GE>::validate_proof(&proof2, root).is_ok());
     }
+
+    #[test]
+    fn subgroup_signing() {
+        let message: [u8; 4] = [79, 77, 69, 82];
+        let keys_vec = (0..4).map(|_| Keys::create()).collect::<Vec<Keys>>();
+        let group = Group::new(
+            keys_vec
+                .iter()
+                .map(|keys| keys.I.public_key)
+                .collect::<Vec<GE>>(),
+        );
+
+        // members 0 and 2 sign
+        let signers = SignerSet::from_indices(4, &[0, 2]).unwrap();
+        let party0_com = EphKey::gen_commit();
+        let party2_com = EphKey::gen_commit();
+        let eph_pub_key_vec = vec![
+            party0_com.eph_key_pair.public_key,
+            party2_com.eph_key_pair.public_key,
+        ];
+        let (_I_S, X, es) =
+            EphKey::compute_subgroup_comm_e(&group, &signers, eph_pub_key_vec, &message).unwrap();
+        let y0 = party0_com.partial_sign(&keys_vec[0].I, es);
+        let y2 = party2_com.partial_sign(&keys_vec[2].I, es);
+        let sig = SubgroupSignature {
+            signers: signers.clone(),
+            X,
+            y: EphKey::add_signature_parts(vec![y0, y2]),
+        };
+        assert!(sig.verify(&group, &message).is_ok());
+        assert_eq!(sig.signers.indices(), vec![0, 2]);
+
+        // the plain challenge does not bind the group root and the signer set
+        let (_, _, e_plain) = EphKey::compute_joint_comm_e(
+            vec![keys_vec[0].I.public_key, keys_vec[2].I.public_key],
+            vec![X],
+            &message,
+        );
+        let unbound = SubgroupSignature {
+            y: EphKey::add_signature_parts(vec![
+                party0_com.partial_sign(&keys_vec[0].I, e_plain),
+                party2_com.partial_sign(&keys_vec[2].I, e_plain),
+            ]),
+            ..sig.clone()
+        };
+        assert!(unbound.verify(&group, &message).is_err());
+
+        // claiming a different subset fails
+        let mut wrong_signers = sig.clone();
+        wrong_signers.signers = SignerSet::from_indices(4, &[0, 1]).unwrap();
+        assert!(wrong_signers.verify(&group, &message).is_err());
+
+        // indices outside the group are rejected
+        assert!(SignerSet::from_indices(4, &[4]).is_err());
+        let mut outside = sig.clone();
+        outside.signers.bitmap = vec![0b0001_0101];
+        assert!(outside.verify(&group, &message).is_err());
+
+        // a member list that does not match the committed root is rejected
+        let mut forged_group = group.clone();
+        forged_group.members[1] = Keys::create().I.public_key;
+        assert!(sig.verify(&forged_group, &message).is_err());
+    }
+
+    #[test]
+    fn message_hashing() {
+        let keys_vec = (0..2).map(|_| Keys::create()).collect::<Vec<Keys>>();
+        let group = Group::new(
+            keys_vec
+                .iter()
+                .map(|keys| keys.I.public_key)
+                .collect::<Vec<GE>>(),
+        );
+        let signers = SignerSet::from_indices(2, &[0, 1]).unwrap();
+        let sign = |message: &[u8]| {
+            let com_vec = (0..2)
+                .map(|_| EphKey::gen_commit())
+                .collect::<Vec<EphKey>>();
+            let eph_pub_key_vec = com_vec
+                .iter()
+                .map(|com| com.eph_key_pair.public_key)
+                .collect::<Vec<GE>>();
+            let (_, X, es) =
+                EphKey::compute_subgroup_comm_e(&group, &signers, eph_pub_key_vec, message)
+                    .unwrap();
+            let y_vec = (0..2)
+                .map(|i| com_vec[i].partial_sign(&keys_vec[i].I, es))
+                .collect::<Vec<FE>>();
+            SubgroupSignature {
+                signers: signers.clone(),
+                X,
+                y: EphKey::add_signature_parts(y_vec),
+            }
+        };
+
+        // messages of any length are signed
+        let long_message = vec![0xab; 100];
+        let sig = sign(&long_message);
+        assert!(verify(&group, &long_message, &sig).is_ok());
+        assert!(verify(&group, &long_message[..99], &sig).is_err());
+
+        // leading zero bytes are part of the message
+        let sig = sign(&[0x00, 0x01]);
+        assert!(verify(&group, &[0x00, 0x01], &sig).is_ok());
+        assert!(verify(&group, &[0x01], &sig).is_err());
+
+        // q and the empty message used to map to the same scalar
+        let q_bytes = BigInt::to_bytes(&FE::q());
+        let sig = sign(&q_bytes);
+        assert!(verify(&group, &q_bytes, &sig).is_ok());
+        assert!(verify(&group, &[], &sig).is_err());
+    }
+
+    #[test]
+    fn group_setup_with_proof_of_possession() {
+        let message: [u8; 4] = [79, 77, 69, 82];
+        let keys_vec = (0..3).map(|_| Keys::create()).collect::<Vec<Keys>>();
+        let ix_vec = keys_vec
+            .iter()
+            .map(|keys| Keys::broadcast(keys.clone()))
+            .collect::<Vec<Vec<GE>>>();
+        let setup = GroupSetup::new(ix_vec).unwrap();
+        let e = setup.challenge();
+        let y_vec = keys_vec
+            .iter()
+            .map(|keys| partial_sign(keys, e))
+            .collect::<Vec<FE>>();
+        let group = setup.finalize(&y_vec).unwrap();
+        assert!(group.validate().is_ok());
+        assert_eq!(group.members[1], keys_vec[1].I.public_key);
+
+        // the group descriptor is consumed by subgroup signing
+        let signers = SignerSet::from_indices(3, &[1]).unwrap();
+        let party1_com = EphKey::gen_commit();
+        let (_, X, es) = EphKey::compute_subgroup_comm_e(
+            &group,
+            &signers,
+            vec![party1_com.eph_key_pair.public_key],
+            &message,
+        )
+        .unwrap();
+        let sig = SubgroupSignature {
+            signers,
+            X,
+            y: party1_com.partial_sign(&keys_vec[1].I, es),
+        };
+        assert!(sig.verify(&group, &message).is_ok());
+    }
+
+    #[test]
+    fn group_setup_rejects_rogue_key() {
+        let honest_keys = Keys::create();
+        // the attacker picks I_r = s*G - I_honest so that I_honest + I_r = s*G, without knowing log(I_r)
+        let s: FE = ECScalar::new_random();
+        let rogue_I = (GE::generator() * &s).sub_point(&honest_keys.I.public_key.get_element());
+        let rogue_X = Keys::create();
+        let ix_vec = vec![
+            Keys::broadcast(honest_keys.clone()),
+            vec![rogue_I, rogue_X.X.public_key],
+        ];
+        let setup = GroupSetup::new(ix_vec).unwrap();
+        let e = setup.challenge();
+        // the best the attacker can do is answer with its known secrets
+        let rogue_y = partial_sign(&rogue_X, e);
+        let y_vec = vec![partial_sign(&honest_keys, e), rogue_y];
+        assert_eq!(setup.faulty_members(&y_vec), vec![1]);
+        assert!(setup.finalize(&y_vec).is_err());
+
+        // the same key broadcast twice is rejected
+        let ix_vec = vec![
+            Keys::broadcast(honest_keys.clone()),
+            Keys::broadcast(honest_keys.clone()),
+        ];
+        assert!(GroupSetup::new(ix_vec).is_err());
+    }
 }
@@ -0,0 +1,117 @@
+/*
+    Multisig Schnorr
+
+    Copyright 2018 by Kzen Networks
+
+    This file is part of Multisig Schnorr library
+    (https://github.com/KZen-networks/multisig-schnorr)
+
+    Multisig Schnorr is free software: you can redistribute
+    it and/or modify it under the terms of the GNU General Public
+    License as published by the Free Software Foundation, either
+    version 3 of the License, or (at your option) any later version.
+
+    @license GPL-3.0+ <https://github.com/KZen-networks/multisig-schnorr/blob/master/LICENSE>
+*/
+
+//! Handling of secret material: private keys, shares and blind factors.
+//!
+//! `Secret<T>` zeroizes its content on drop, prints as `[REDACTED]` and does not implement
+//! `Serialize`, and neither do the key types holding one. Secrets leave a key type only
+//! through its explicit `export_*` methods or public fields, and are serialized only by the
+//! keystore, which encrypts them.
+use std::fmt;
+
+use curv::arithmetic::traits::Converter;
+use curv::BigInt;
+use subtle::ConstantTimeEq;
+use zeroize::Zeroize;
+
+// scalars and x coordinates of secp256k1
+const FIELD_BYTES: usize = 32;
+
+pub struct Secret<T: Zeroize>(T);
+
+impl<T: Zeroize> Secret<T> {
+    pub fn new(value: T) -> Secret<T> {
+        Secret(value)
+    }
+
+    pub fn expose(&self) -> &T {
+        &self.0
+    }
+}
+
+impl<T: Zeroize + Clone> Clone for Secret<T> {
+    fn clone(&self) -> Secret<T> {
+        Secret(self.0.clone())
+    }
+}
+
+impl<T: Zeroize> Drop for Secret<T> {
+    fn drop(&mut self) {
+        self.0.zeroize();
+    }
+}
+
+impl<T: Zeroize> fmt::Debug for Secret<T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "[REDACTED]")
+    }
+}
+
+impl<T: Zeroize> From<T> for Secret<T> {
+    fn from(value: T) -> Secret<T> {
+        Secret(value)
+    }
+}
+
+pub fn ct_eq_bytes(a: &[u8], b: &[u8]) -> bool {
+    a.ct_eq(b).into()
+}
+
+/// Constant-time equality of two non-negative integers below 2^256,
+/// compared as fixed-width big-endian encodings.
+pub fn ct_eq_bn(a: &BigInt, b: &BigInt) -> bool {
+    ct_eq_bytes(&to_fixed_bytes(a), &to_fixed_bytes(b))
+}
+
+fn to_fixed_bytes(n: &BigInt) -> Vec<u8> {
+    let bytes = BigInt::to_bytes(n);
+    if bytes.len() >= FIELD_BYTES {
+        return bytes;
+    }
+    let mut padded = vec![0u8; FIELD_BYTES - bytes.len()];
+    padded.extend_from_slice(&bytes);
+    padded
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use curv::elliptic::curves::secp256_k1::FE;
+    use curv::elliptic::curves::traits::*;
+
+    #[test]
+    fn test_secret_is_redacted() {
+        let x: FE = ECScalar::new_random();
+        let secret = Secret::new(x);
+        let printed = format!("{:?}", secret);
+        assert_eq!(printed, "[REDACTED]");
+        assert!(!printed.contains(&x.to_big_int().to_hex()));
+    }
+
+    #[test]
+    fn test_ct_eq_bn() {
+        let a = BigInt::from(1);
+        assert!(ct_eq_bn(&a, &BigInt::from(1)));
+        assert!(!ct_eq_bn(&a, &BigInt::from(256)));
+        // minimal encodings of 31 and 32 bytes with the same low bytes
+        let short = BigInt::from_bytes(&[0xff; 31]);
+        let long = BigInt::from_bytes(&[&[0x01][..], &[0xff; 31][..]].concat());
+        assert!(!ct_eq_bn(&short, &long));
+        // a leading zero byte in the input does not change the fixed-width encoding
+        let padded = BigInt::from_bytes(&[&[0x00][..], &[0xff; 31][..]].concat());
+        assert!(ct_eq_bn(&short, &padded));
+    }
+}
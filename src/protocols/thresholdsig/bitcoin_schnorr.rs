@@ -16,6 +16,8 @@
     @license GPL-3.0+ <https://github.com/KZen-networks/multisig-schnorr/blob/master/LICENSE>
 */
 /// following the variant used in bip-schnorr: https://github.com/sipa/bips/blob/bip-schnorr/bip-schnorr.mediawiki
+/// secret shares of the key generation go point-to-point encrypted: phase2_encrypt_shares on the
+/// sending side, phase2_verify_vss_construct_keypair decrypts them on the receiving side
 use Error::{self, InvalidKey, InvalidSS, InvalidSig};
 
 use curv::arithmetic::traits::*;
@@ -28,6 +30,19 @@ use curv::cryptographic_primitives::hashing::hash_sha256::HSha256;
 use curv::cryptographic_primitives::hashing::traits::Hash;
 use curv::cryptographic_primitives::secret_sharing::feldman_vss::VerifiableSS;
 use curv::BigInt;
+use protocols::backup::{self, EncryptedBackup};
+use protocols::secret::Secret;
+use protocols::thresholdsig::echo_broadcast::{
+    verify_echoes, EchoMessage, COMMITMENTS, DECOMMITMENTS, VSS_COMMITMENTS,
+};
+use protocols::thresholdsig::key_package::{
+    verification_shares, PublicKeyPackage, SignatureAggregator,
+};
+use protocols::thresholdsig::key_proof::KeyProof;
+use protocols::thresholdsig::quorum::SigningQuorum;
+use protocols::thresholdsig::share_encryption::{EncryptedShare, EncryptionKey};
+use protocols::thresholdsig::Blame;
+use protocols::transcript::Transcript;
 
 type GE = curv::elliptic::curves::secp256_k1::GE;
 type FE = curv::elliptic::curves::secp256_k1::FE;
@@ -35,11 +50,12 @@ type FE = curv::elliptic::curves::secp256_k1::FE;
 const SECURITY: usize = 256;
 
 pub struct Keys {
-    pub u_i: FE,
+    pub u_i: Secret<FE>,
     pub y_i: GE,
     pub party_index: usize,
 }
 
+#[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct KeyGenBroadcastMessage1 {
     com: BigInt,
 }
@@ -49,10 +65,28 @@ pub struct Parameters {
     pub threshold: usize,   //t
     pub share_count: usize, //n
 }
-#[derive(Clone, Serialize, Deserialize)]
+#[derive(Clone, Debug)]
 pub struct SharedKeys {
     pub y: GE,
-    pub x_i: FE,
+    pub x_i: Secret<FE>,
+}
+
+impl SharedKeys {
+    // verifiable encrypted backup of x_i to a recovery key
+    pub fn backup(&self, recovery_public_key: &GE) -> EncryptedBackup {
+        backup::backup_share(&self.x_i, recovery_public_key)
+    }
+
+    // public_share is x_i*G, computed from the VSS commitments of the key generation
+    pub fn restore(
+        backup: &EncryptedBackup,
+        recovery_private_key: &FE,
+        y: &GE,
+        public_share: &GE,
+    ) -> Result<SharedKeys, Error> {
+        let (y, x_i) = backup::restore_share(backup, recovery_private_key, y, public_share)?;
+        Ok(SharedKeys { y, x_i })
+    }
 }
 
 impl Keys {
@@ -61,95 +95,190 @@ impl Keys {
         let y = &ECPoint::generator() * &u;
 
         Keys {
-            u_i: u,
+            u_i: Secret::new(u),
             y_i: y,
             party_index: index.clone(),
         }
     }
 
-    pub fn phase1_broadcast(&self) -> (KeyGenBroadcastMessage1, BigInt) {
+    // the proof of knowledge of u_i is sent together with y_i and the blind factor
+    pub fn phase1_broadcast(
+        &self,
+        session_id: &[u8],
+    ) -> (KeyGenBroadcastMessage1, BigInt, KeyProof) {
         let blind_factor = BigInt::sample(SECURITY);
         let com = HashCommitment::create_commitment_with_user_defined_randomness(
             &self.y_i.bytes_compressed_to_big_int(),
             &blind_factor,
         );
         let bcm1 = KeyGenBroadcastMessage1 { com };
-        (bcm1, blind_factor)
+        let proof = KeyProof::prove(self.u_i.expose(), &self.y_i, session_id, self.party_index);
+        (bcm1, blind_factor, proof)
     }
 
+    // parties[i] is the index of the party that sent blind_vec[i], y_vec[i], proof_vec[i] and bc1_vec[i]
+    // and the echoes com_echo_vec[i] and decom_echo_vec[i] of the two rounds; sender_key_vec[i]
+    // is its long-term key
+    #[allow(clippy::too_many_arguments)]
     pub fn phase1_verify_com_phase2_distribute(
         &self,
         params: &Parameters,
+        session_id: &[u8],
         blind_vec: &Vec<BigInt>,
         y_vec: &Vec<GE>,
+        proof_vec: &Vec<KeyProof>,
         bc1_vec: &Vec<KeyGenBroadcastMessage1>,
+        com_echo_vec: &[EchoMessage],
+        decom_echo_vec: &[EchoMessage],
+        sender_key_vec: &[GE],
         parties: &[usize],
-    ) -> Result<(VerifiableSS<GE>, Vec<FE>, usize), Error> {
+    ) -> Result<(VerifiableSS<GE>, Vec<FE>, usize), Blame> {
         // test length:
         assert_eq!(blind_vec.len(), params.share_count);
         assert_eq!(bc1_vec.len(), params.share_count);
         assert_eq!(y_vec.len(), params.share_count);
-        // test decommitments
-        let correct_key_correct_decom_all = (0..bc1_vec.len())
-            .map(|i| {
-                HashCommitment::create_commitment_with_user_defined_randomness(
-                    &y_vec[i].bytes_compressed_to_big_int(),
-                    &blind_vec[i],
-                ) == bc1_vec[i].com
-            })
-            .all(|x| x == true);
-        /*
-        let (vss_scheme, secret_shares) = VerifiableSS::share_at_indices(
-            params.threshold,
-            params.share_count,
-            &self.u_i,
+        assert_eq!(proof_vec.len(), params.share_count);
+        assert_eq!(parties.len(), params.share_count);
+        // every party has to have received the same commitments and decommitments
+        verify_echoes(
+            session_id,
+            COMMITMENTS,
+            bc1_vec,
+            com_echo_vec,
+            sender_key_vec,
             parties,
-        );
-        */
+        )?;
+        verify_echoes(
+            session_id,
+            DECOMMITMENTS,
+            &decommitments(y_vec, blind_vec, proof_vec),
+            decom_echo_vec,
+            sender_key_vec,
+            parties,
+        )?;
+        // test decommitments and proofs of knowledge
+        let bad_parties = (0..bc1_vec.len())
+            .filter(|i| {
+                let correct_decom = HashCommitment::create_commitment_with_user_defined_randomness(
+                    &y_vec[*i].bytes_compressed_to_big_int(),
+                    &blind_vec[*i],
+                ) == bc1_vec[*i].com;
+                let correct_proof = proof_vec[*i]
+                    .verify(&y_vec[*i], session_id, parties[*i])
+                    .is_ok();
+                !(correct_decom && correct_proof)
+            })
+            .collect::<Vec<usize>>();
+        if !bad_parties.is_empty() {
+            return Err(Blame {
+                error: InvalidKey,
+                parties: bad_parties,
+            });
+        }
+
         let (vss_scheme, secret_shares) = VerifiableSS::share_at_indices(
             params.threshold,
             params.share_count,
-            &self.u_i,
+            self.u_i.expose(),
             &parties,
         );
+        Ok((vss_scheme, secret_shares, self.party_index.clone()))
+    }
 
-        match correct_key_correct_decom_all {
-            true => Ok((vss_scheme, secret_shares, self.party_index.clone())),
-            false => Err(InvalidKey),
-        }
+    // encrypt secret_shares[j] to recipient_key_vec[j], the long-term key of party parties[j]
+    pub fn phase2_encrypt_shares(
+        &self,
+        session_id: &[u8],
+        secret_shares: &[FE],
+        encryption_key: &EncryptionKey,
+        recipient_key_vec: &[GE],
+        parties: &[usize],
+    ) -> Vec<EncryptedShare> {
+        assert_eq!(secret_shares.len(), recipient_key_vec.len());
+        assert_eq!(secret_shares.len(), parties.len());
+        (0..secret_shares.len())
+            .map(|j| {
+                EncryptedShare::encrypt(
+                    &secret_shares[j],
+                    encryption_key,
+                    &recipient_key_vec[j],
+                    session_id,
+                    self.party_index,
+                    parties[j],
+                )
+            })
+            .collect::<Vec<EncryptedShare>>()
     }
 
+    // encrypted_share_vec[i] is the share sent by party parties[i] with long-term key
+    // sender_key_vec[i] and vss_echo_vec[i] its echo of the VSS commitments; a share that does
+    // not decrypt or does not match the VSS commitments of its sender is blamed on that sender
+    #[allow(clippy::too_many_arguments)]
     pub fn phase2_verify_vss_construct_keypair(
         &self,
         params: &Parameters,
+        session_id: &[u8],
         y_vec: &Vec<GE>,
-        secret_shares_vec: &Vec<FE>,
+        encrypted_share_vec: &[EncryptedShare],
         vss_scheme_vec: &Vec<VerifiableSS<GE>>,
-        index: &usize,
-    ) -> Result<SharedKeys, Error> {
+        vss_echo_vec: &[EchoMessage],
+        encryption_key: &EncryptionKey,
+        sender_key_vec: &[GE],
+        parties: &[usize],
+    ) -> Result<SharedKeys, Blame> {
         assert_eq!(y_vec.len(), params.share_count);
-        assert_eq!(secret_shares_vec.len(), params.share_count);
+        assert_eq!(encrypted_share_vec.len(), params.share_count);
         assert_eq!(vss_scheme_vec.len(), params.share_count);
+        assert_eq!(sender_key_vec.len(), params.share_count);
+        assert_eq!(parties.len(), params.share_count);
+        verify_echoes(
+            session_id,
+            VSS_COMMITMENTS,
+            vss_scheme_vec,
+            vss_echo_vec,
+            sender_key_vec,
+            parties,
+        )?;
 
-        let correct_ss_verify = (0..y_vec.len())
+        let secret_shares_vec = (0..y_vec.len())
             .map(|i| {
-                vss_scheme_vec[i]
-                    .validate_share(&secret_shares_vec[i], *index)
-                    .is_ok()
-                    && vss_scheme_vec[i].commitments[0] == y_vec[i]
+                encrypted_share_vec[i].decrypt(
+                    encryption_key,
+                    &sender_key_vec[i],
+                    session_id,
+                    parties[i],
+                    self.party_index,
+                )
             })
-            .all(|x| x == true);
-
-        match correct_ss_verify {
-            true => {
-                let mut y_vec_iter = y_vec.iter();
-                let y0 = y_vec_iter.next().unwrap();
-                let y = y_vec_iter.fold(y0.clone(), |acc, x| acc + x);
-                let x_i = secret_shares_vec.iter().fold(FE::zero(), |acc, x| acc + x);
-                Ok(SharedKeys { y, x_i })
-            }
-            false => Err(InvalidSS),
+            .collect::<Vec<Result<FE, Error>>>();
+        let bad_parties = (0..y_vec.len())
+            .filter(|i| match secret_shares_vec[*i] {
+                Ok(ref secret_share) => {
+                    vss_scheme_vec[*i]
+                        .validate_share(secret_share, self.party_index)
+                        .is_err()
+                        || vss_scheme_vec[*i].commitments[0] != y_vec[*i]
+                }
+                Err(_) => true,
+            })
+            .collect::<Vec<usize>>();
+        if !bad_parties.is_empty() {
+            return Err(Blame {
+                error: InvalidSS,
+                parties: bad_parties,
+            });
         }
+
+        let mut y_vec_iter = y_vec.iter();
+        let y0 = y_vec_iter.next().unwrap();
+        let y = y_vec_iter.fold(y0.clone(), |acc, x| acc + x);
+        let x_i = secret_shares_vec
+            .iter()
+            .fold(FE::zero(), |acc, x| acc + x.as_ref().unwrap());
+        Ok(SharedKeys {
+            y,
+            x_i: Secret::new(x_i),
+        })
     }
 
     // remove secret shares from x_i for parties that are not participating in signing
@@ -166,11 +295,24 @@ impl Keys {
         }
         SharedKeys {
             y: shared_key.y.clone(),
-            x_i: new_xi,
+            x_i: Secret::new(new_xi),
         }
     }
 }
 
+// the decommitments (y_i, blind factor, proof) of the parties, as they are signed and echoed
+pub fn decommitments(
+    y_vec: &[GE],
+    blind_vec: &[BigInt],
+    proof_vec: &[KeyProof],
+) -> Vec<(GE, BigInt, KeyProof)> {
+    assert_eq!(blind_vec.len(), y_vec.len());
+    assert_eq!(proof_vec.len(), y_vec.len());
+    (0..y_vec.len())
+        .map(|i| (y_vec[i], blind_vec[i].clone(), proof_vec[i].clone()))
+        .collect()
+}
+
 pub struct LocalSig {
     gamma_i: FE,
     e: FE,
@@ -182,26 +324,37 @@ impl LocalSig {
         local_ephemeral_key: &SharedKeys,
         local_private_key: &SharedKeys,
     ) -> LocalSig {
-        let beta_i = local_ephemeral_key.x_i.clone();
-        let alpha_i = local_private_key.x_i.clone();
-
-        let message_len_bits = message.len() * 8;
-        let R = local_ephemeral_key.y.bytes_compressed_to_big_int();
-        let X = local_private_key.y.bytes_compressed_to_big_int();
-        let X_vec = BigInt::to_bytes(&X);
-        let X_vec_len_bits = X_vec.len() * 8;
-        let e_bn = HSha256::create_hash_from_slice(
-            &BigInt::to_bytes(
-                &((((R << X_vec_len_bits) + X) << message_len_bits) + BigInt::from_bytes(message)),
-            )[..],
-        );
+        let beta_i = *local_ephemeral_key.x_i.expose();
+        let alpha_i = *local_private_key.x_i.expose();
 
-        let e: FE = ECScalar::from(&e_bn);
+        let e = challenge(&local_ephemeral_key.y, &local_private_key.y, message);
         let gamma_i = beta_i + e.clone() * alpha_i;
 
         LocalSig { gamma_i, e }
     }
 
+    // checks a single local signature of the share at `party` against the public key
+    // packages of the key and of the ephemeral key: gamma_i*G = beta_i*G + e*x_i*G
+    pub fn verify(
+        &self,
+        message: &[u8],
+        party: usize,
+        key_package: &PublicKeyPackage,
+        eph_package: &PublicKeyPackage,
+    ) -> Result<(), Error> {
+        let (public_share, eph_public_share) =
+            verification_shares(key_package, eph_package, party)?;
+        if self.e != challenge(&eph_package.y, &key_package.y, message) {
+            return Err(InvalidSig);
+        }
+        let g: GE = GE::generator();
+        if g * &self.gamma_i == eph_public_share + &(public_share * &self.e) {
+            Ok(())
+        } else {
+            Err(InvalidSig)
+        }
+    }
+
     // section 4.2 step 3
     #[allow(unused_doc_comments)]
     pub fn verify_local_sigs(
@@ -256,41 +409,92 @@ impl LocalSig {
     }
 }
 
-#[derive(Clone, Serialize, Deserialize)]
+#[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct Signature {
     pub sigma: FE,
     pub v: GE,
 }
 
 impl Signature {
+    // collects and checks local signatures as they arrive
+    pub fn aggregator(
+        message: &[u8],
+        key_package: &PublicKeyPackage,
+        eph_package: &PublicKeyPackage,
+    ) -> Result<SignatureAggregator<LocalSig, Signature>, Error> {
+        SignatureAggregator::new(
+            message,
+            key_package,
+            eph_package,
+            |local_sig: &LocalSig, message, party, key_package, eph_package| {
+                local_sig
+                    .verify(message, party, key_package, eph_package)
+                    .map(|_| local_sig.gamma_i)
+            },
+            |sigma, _message, _key_package, eph_package| Signature {
+                sigma,
+                v: eph_package.y,
+            },
+        )
+    }
+
+    // local_sig_vec[k] is the local signature of quorum.parties[k]
     pub fn generate(
         vss_sum_local_sigs: &VerifiableSS<GE>,
         local_sig_vec: &Vec<LocalSig>,
-        parties_index_vec: &[usize],
+        quorum: &SigningQuorum,
         v: GE,
-    ) -> Signature {
-        let gamma_vec = (0..parties_index_vec.len())
-            .map(|i| local_sig_vec[i].gamma_i.clone())
+    ) -> Result<Signature, Blame> {
+        assert_eq!(local_sig_vec.len(), quorum.parties.len());
+        // each local signature must be a share of the sum of the VSS schemes
+        let g: GE = GE::generator();
+        let bad_parties = (0..quorum.parties.len())
+            .filter(|k| {
+                vss_sum_local_sigs
+                    .validate_share_public(
+                        &(g * &local_sig_vec[*k].gamma_i),
+                        quorum.parties[*k] + 1,
+                    )
+                    .is_err()
+            })
+            .collect::<Vec<usize>>();
+        if !bad_parties.is_empty() {
+            return Err(Blame {
+                error: InvalidSS,
+                parties: bad_parties,
+            });
+        }
+        let gamma_vec = local_sig_vec
+            .iter()
+            .map(|local_sig| local_sig.gamma_i)
             .collect::<Vec<FE>>();
-        let reconstruct_limit = vss_sum_local_sigs.parameters.threshold.clone() + 1;
-        let sigma = vss_sum_local_sigs.reconstruct(
-            &parties_index_vec[0..reconstruct_limit.clone()],
-            &gamma_vec[0..reconstruct_limit.clone()],
-        );
-        Signature { sigma, v }
+        Ok(Signature {
+            sigma: quorum.interpolate(&gamma_vec),
+            v,
+        })
     }
 
     pub fn verify(&self, message: &[u8], pubkey_y: &GE) -> Result<(), Error> {
+        let e = challenge(&self.v, pubkey_y, message);
+        self.verify_with_challenge(&e, pubkey_y)
+    }
+
+    // verify a signature made with the encoding used before transcript version 1;
+    // kept only to migrate existing signatures
+    pub fn verify_legacy(&self, message: &[u8], pubkey_y: &GE) -> Result<(), Error> {
         let e_bn = HSha256::create_hash(&[
             &self.v.bytes_compressed_to_big_int(),
             &pubkey_y.bytes_compressed_to_big_int(),
             &BigInt::from_bytes(message),
         ]);
         let e: FE = ECScalar::from(&e_bn);
+        self.verify_with_challenge(&e, pubkey_y)
+    }
 
+    fn verify_with_challenge(&self, e: &FE, pubkey_y: &GE) -> Result<(), Error> {
         let g: GE = GE::generator();
         let sigma_g = g * &self.sigma;
-        let e_y = pubkey_y * &e;
+        let e_y = pubkey_y * e;
         let e_y_plus_v = e_y + &self.v;
 
         if e_y_plus_v == sigma_g {
@@ -300,3 +504,12 @@ impl Signature {
         }
     }
 }
+
+// e = H(R || X || message)
+fn challenge(R: &GE, X: &GE, message: &[u8]) -> FE {
+    let mut transcript = Transcript::new(b"threshold-bitcoin/challenge");
+    transcript.append_point(b"R", R);
+    transcript.append_point(b"X", X);
+    transcript.append_message(b"message", message);
+    transcript.challenge_scalar()
+}
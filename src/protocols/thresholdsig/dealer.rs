@@ -0,0 +1,125 @@
+/*
+    Multisig Schnorr
+
+    Copyright 2018 by Kzen Networks
+
+    This file is part of Multisig Schnorr library
+    (https://github.com/KZen-networks/multisig-schnorr)
+
+    Multisig Schnorr is free software: you can redistribute
+    it and/or modify it under the terms of the GNU General Public
+    License as published by the Free Software Foundation, either
+    version 3 of the License, or (at your option) any later version.
+
+    @license GPL-3.0+ <https://github.com/KZen-networks/multisig-schnorr/blob/master/LICENSE>
+*/
+
+//! Moving single keys into and out of `zilliqa_schnorr` committees.
+//!
+//! `deal` splits an existing private key with Feldman VSS, so a legacy key can be handed to a
+//! committee without changing its public key. The dealer sees the whole key and must erase it
+//! afterwards; distributed key generation remains the default. `reconstruct_secret` is the
+//! reverse: a quorum of t+1 parties hands in their shares, each share is checked against the
+//! key generation commitments, and the recombined key is checked against y.
+use curv::cryptographic_primitives::secret_sharing::feldman_vss::VerifiableSS;
+use curv::elliptic::curves::traits::*;
+use protocols::secret::Secret;
+use protocols::thresholdsig::quorum::SigningQuorum;
+use protocols::thresholdsig::zilliqa_schnorr::{Parameters, SharedKeys};
+use protocols::thresholdsig::Blame;
+use Error::{InvalidKey, InvalidSS};
+
+type GE = curv::elliptic::curves::secp256_k1::GE;
+type FE = curv::elliptic::curves::secp256_k1::FE;
+
+#[derive(Clone, Debug)]
+pub struct DealtShares {
+    pub y: GE,
+    // plays the role of the key generation vss_scheme_vec, as a single dealer
+    pub vss_scheme_vec: Vec<VerifiableSS<GE>>,
+    // shared_keys_vec[i] goes to party i, at point i + 1
+    pub shared_keys_vec: Vec<SharedKeys>,
+}
+
+pub fn deal(secret: &Secret<FE>, params: &Parameters) -> DealtShares {
+    let (vss_scheme, secret_shares) =
+        VerifiableSS::share(params.threshold, params.share_count, secret.expose());
+    let y = GE::generator() * secret.expose();
+    let shared_keys_vec = secret_shares
+        .iter()
+        .map(|x_i| SharedKeys {
+            y,
+            x_i: Secret::new(*x_i),
+        })
+        .collect::<Vec<SharedKeys>>();
+    DealtShares {
+        y,
+        vss_scheme_vec: vec![vss_scheme],
+        shared_keys_vec,
+    }
+}
+
+// shares[k] is handed in by quorum.parties[k]; blamed parties are positions in shares
+pub fn reconstruct_secret(
+    quorum: &SigningQuorum,
+    vss_scheme_vec: &[VerifiableSS<GE>],
+    y: &GE,
+    shares: &[SharedKeys],
+) -> Result<Secret<FE>, Blame> {
+    if vss_scheme_vec.is_empty()
+        || shares.len() != quorum.parties.len()
+        || vss_scheme_vec[0].parameters.threshold != quorum.threshold
+    {
+        return Err(Blame {
+            error: InvalidSS,
+            parties: Vec::new(),
+        });
+    }
+    let vss_sum = sum_commitments(vss_scheme_vec);
+    let points = quorum.points();
+    let bad_parties = (0..shares.len())
+        .filter(|k| {
+            shares[*k].y != *y
+                || vss_sum
+                    .validate_share(shares[*k].x_i.expose(), points[*k])
+                    .is_err()
+        })
+        .collect::<Vec<usize>>();
+    if !bad_parties.is_empty() {
+        return Err(Blame {
+            error: InvalidSS,
+            parties: bad_parties,
+        });
+    }
+    let x_i_vec = Secret::new(
+        shares
+            .iter()
+            .map(|share| *share.x_i.expose())
+            .collect::<Vec<FE>>(),
+    );
+    let secret = Secret::new(quorum.interpolate(x_i_vec.expose()));
+    // only reachable if the commitments themselves do not match y
+    if GE::generator() * secret.expose() != *y {
+        return Err(Blame {
+            error: InvalidKey,
+            parties: Vec::new(),
+        });
+    }
+    Ok(secret)
+}
+
+// commitments to the sum of the dealers' polynomials, against which the final shares x_i
+// are checked
+pub fn sum_commitments(vss_scheme_vec: &[VerifiableSS<GE>]) -> VerifiableSS<GE> {
+    let mut vss_sum = vss_scheme_vec[0].clone();
+    for vss_scheme in vss_scheme_vec.iter().skip(1) {
+        for (sum, commitment) in vss_sum
+            .commitments
+            .iter_mut()
+            .zip(vss_scheme.commitments.iter())
+        {
+            *sum = *sum + commitment;
+        }
+    }
+    vss_sum
+}
@@ -0,0 +1,132 @@
+/*
+    Multisig Schnorr
+
+    Copyright 2018 by Kzen Networks
+
+    This file is part of Multisig Schnorr library
+    (https://github.com/KZen-networks/multisig-schnorr)
+
+    Multisig Schnorr is free software: you can redistribute
+    it and/or modify it under the terms of the GNU General Public
+    License as published by the Free Software Foundation, either
+    version 3 of the License, or (at your option) any later version.
+
+    @license GPL-3.0+ <https://github.com/KZen-networks/multisig-schnorr/blob/master/LICENSE>
+*/
+
+//! Non-hardened child key derivation for `zilliqa_schnorr` threshold keys, in the style of BIP32.
+//!
+//! A child key is y' = y + t*G with a tweak t computed from the public key, a chain code and the
+//! child index. Adding the same t to every share x_i shifts the shared secret by t, because the
+//! Lagrange coefficients of any quorum sum to one; the constant term of the VSS commitments moves
+//! by t*G. Anyone holding the extended public key can compute child public keys, so one key
+//! generation gives unlimited receive addresses. Hardened derivation would need the secret and
+//! is not supported. The hashes are transcript based and do not reproduce BIP32 test vectors.
+use curv::cryptographic_primitives::secret_sharing::feldman_vss::VerifiableSS;
+use curv::elliptic::curves::traits::*;
+use curv::BigInt;
+use protocols::secret::Secret;
+use protocols::thresholdsig::zilliqa_schnorr::SharedKeys;
+use protocols::transcript::Transcript;
+use Error::{self, InvalidKey};
+
+type GE = curv::elliptic::curves::secp256_k1::GE;
+type FE = curv::elliptic::curves::secp256_k1::FE;
+
+pub const HARDENED_OFFSET: u32 = 1 << 31;
+
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct DerivationPath {
+    pub indices: Vec<u32>,
+}
+
+impl DerivationPath {
+    pub fn new(indices: &[u32]) -> Result<DerivationPath, Error> {
+        if indices.iter().any(|i| *i >= HARDENED_OFFSET) {
+            return Err(InvalidKey);
+        }
+        Ok(DerivationPath {
+            indices: indices.to_vec(),
+        })
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct ExtendedPublicKey {
+    pub y: GE,
+    pub chain_code: BigInt,
+}
+
+impl ExtendedPublicKey {
+    // the root chain code is derived from y, so all parties agree on it after key generation
+    pub fn new(y: &GE) -> ExtendedPublicKey {
+        let mut transcript = Transcript::new(b"threshold/root-chain-code");
+        transcript.append_point(b"y", y);
+        ExtendedPublicKey {
+            y: *y,
+            chain_code: transcript.challenge(),
+        }
+    }
+
+    // returns the tweak t and the child key y + t*G
+    pub fn derive_child(&self, index: u32) -> Result<(FE, ExtendedPublicKey), Error> {
+        if index >= HARDENED_OFFSET {
+            return Err(InvalidKey);
+        }
+        let mut transcript = Transcript::new(b"threshold/child-key");
+        transcript.append_bn(b"chain code", &self.chain_code);
+        transcript.append_point(b"y", &self.y);
+        transcript.append_u64(b"index", u64::from(index));
+        let tweak = transcript.challenge_scalar();
+        transcript.append_message(b"output", b"chain code");
+        let child = ExtendedPublicKey {
+            y: self.y + &(GE::generator() * &tweak),
+            chain_code: transcript.challenge(),
+        };
+        Ok((tweak, child))
+    }
+
+    // returns the sum of the tweaks along the path and the derived key
+    pub fn derive(&self, path: &DerivationPath) -> Result<(FE, ExtendedPublicKey), Error> {
+        path.indices
+            .iter()
+            .try_fold((FE::zero(), self.clone()), |(tweak, xpub), index| {
+                let (child_tweak, child) = xpub.derive_child(*index)?;
+                Ok((tweak + child_tweak, child))
+            })
+    }
+}
+
+// a party's view of a derived key: shares, commitments and the path that produced them
+#[derive(Clone, Debug)]
+pub struct DerivedShare {
+    pub path: DerivationPath,
+    pub public_key: ExtendedPublicKey,
+    pub shared_keys: SharedKeys,
+    pub vss_scheme_vec: Vec<VerifiableSS<GE>>,
+}
+
+pub fn derive_share(
+    xpub: &ExtendedPublicKey,
+    shared_keys: &SharedKeys,
+    vss_scheme_vec: &[VerifiableSS<GE>],
+    path: &DerivationPath,
+) -> Result<DerivedShare, Error> {
+    if xpub.y != shared_keys.y || vss_scheme_vec.is_empty() {
+        return Err(InvalidKey);
+    }
+    let (tweak, public_key) = xpub.derive(path)?;
+    let tweak_g = GE::generator() * &tweak;
+    // the shift goes into the constant term of one dealer's commitments
+    let mut vss_scheme_vec = vss_scheme_vec.to_vec();
+    vss_scheme_vec[0].commitments[0] = vss_scheme_vec[0].commitments[0] + &tweak_g;
+    Ok(DerivedShare {
+        path: path.clone(),
+        shared_keys: SharedKeys {
+            y: public_key.y,
+            x_i: Secret::new(*shared_keys.x_i.expose() + tweak),
+        },
+        public_key,
+        vss_scheme_vec,
+    })
+}
@@ -0,0 +1,206 @@
+/*
+    Multisig Schnorr
+
+    Copyright 2018 by Kzen Networks
+
+    This file is part of Multisig Schnorr library
+    (https://github.com/KZen-networks/multisig-schnorr)
+
+    Multisig Schnorr is free software: you can redistribute
+    it and/or modify it under the terms of the GNU General Public
+    License as published by the Free Software Foundation, either
+    version 3 of the License, or (at your option) any later version.
+
+    @license GPL-3.0+ <https://github.com/KZen-networks/multisig-schnorr/blob/master/LICENSE>
+*/
+#![allow(non_snake_case)]
+
+//! Echo broadcast: every broadcast of the key generation goes out with a `SignedDigest`, the
+//! sender's signature with its long-term key on the digest of the message. After the round
+//! every party sends to all others the signed digests it received, one per sender.
+//!
+//! A party cannot forge the signature of another, so an echo either relays digests their
+//! senders signed or is blamed on the party that sent it. Two different digests signed by the
+//! same sender prove that it sent different messages to different parties, and that sender is
+//! blamed. Echoes have to travel over authenticated channels.
+use curv::elliptic::curves::traits::*;
+use curv::BigInt;
+use protocols::secret::Secret;
+use protocols::thresholdsig::share_encryption::EncryptionKey;
+use protocols::thresholdsig::Blame;
+use protocols::transcript::Transcript;
+use serde::Serialize;
+use serde_json;
+use Error::{self, InvalidCom};
+
+type GE = curv::elliptic::curves::secp256_k1::GE;
+type FE = curv::elliptic::curves::secp256_k1::FE;
+
+// the round labels of the key generation broadcasts
+pub const COMMITMENTS: &[u8] = b"commitments";
+pub const DECOMMITMENTS: &[u8] = b"decommitments";
+pub const VSS_COMMITMENTS: &[u8] = b"vss-commitments";
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct SignedDigest {
+    pub digest: BigInt,
+    pub R: GE,
+    pub s: FE,
+}
+
+impl SignedDigest {
+    // signed by party `sender` for its own broadcast `message` of the round
+    pub fn sign<T: Serialize>(
+        session_id: &[u8],
+        round: &[u8],
+        sender: usize,
+        message: &T,
+        sender_key: &EncryptionKey,
+    ) -> SignedDigest {
+        let digest = digest(session_id, round, sender, message);
+        let r: Secret<FE> = Secret::new(ECScalar::new_random());
+        let R = GE::generator() * r.expose();
+        let c = challenge(
+            session_id,
+            round,
+            sender,
+            &digest,
+            &R,
+            &sender_key.public_key,
+        );
+        let s = *r.expose() + c * sender_key.private_key();
+        SignedDigest { digest, R, s }
+    }
+
+    // sG = R + cP, with P the long-term key of the sender
+    pub fn verify(
+        &self,
+        session_id: &[u8],
+        round: &[u8],
+        sender: usize,
+        sender_key: &GE,
+    ) -> Result<(), Error> {
+        let c = challenge(session_id, round, sender, &self.digest, &self.R, sender_key);
+        if GE::generator() * &self.s == self.R + &(sender_key * &c) {
+            Ok(())
+        } else {
+            Err(InvalidCom)
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct EchoMessage {
+    pub digests: Vec<SignedDigest>,
+}
+
+impl EchoMessage {
+    // received[j] and signed_vec[j] came from party parties[j] with long-term key
+    // sender_key_vec[j]; a signed digest that does not match the message is blamed on its sender
+    pub fn create<T: Serialize>(
+        session_id: &[u8],
+        round: &[u8],
+        received: &[T],
+        signed_vec: &[SignedDigest],
+        sender_key_vec: &[GE],
+        parties: &[usize],
+    ) -> Result<EchoMessage, Blame> {
+        assert_eq!(signed_vec.len(), received.len());
+        assert_eq!(sender_key_vec.len(), received.len());
+        assert_eq!(parties.len(), received.len());
+        let bad_parties = (0..received.len())
+            .filter(|j| {
+                signed_vec[*j].digest != digest(session_id, round, parties[*j], &received[*j])
+                    || signed_vec[*j]
+                        .verify(session_id, round, parties[*j], &sender_key_vec[*j])
+                        .is_err()
+            })
+            .collect::<Vec<usize>>();
+        if !bad_parties.is_empty() {
+            return Err(Blame {
+                error: InvalidCom,
+                parties: bad_parties,
+            });
+        }
+        Ok(EchoMessage {
+            digests: signed_vec.to_vec(),
+        })
+    }
+}
+
+// received[k] is the message of party parties[k] with long-term key sender_key_vec[k], and
+// echo_vec[i] the echo of party parties[i], the local one included
+pub fn verify_echoes<T: Serialize>(
+    session_id: &[u8],
+    round: &[u8],
+    received: &[T],
+    echo_vec: &[EchoMessage],
+    sender_key_vec: &[GE],
+    parties: &[usize],
+) -> Result<(), Blame> {
+    let n = received.len();
+    assert_eq!(sender_key_vec.len(), n);
+    assert_eq!(parties.len(), n);
+    // a malformed echo, or one relaying a digest its sender did not sign, is blamed on the
+    // party that sent the echo
+    let bad_echoes = (0..echo_vec.len())
+        .filter(|i| {
+            echo_vec[*i].digests.len() != n
+                || (0..n).any(|k| {
+                    echo_vec[*i].digests[k]
+                        .verify(session_id, round, parties[k], &sender_key_vec[k])
+                        .is_err()
+                })
+        })
+        .collect::<Vec<usize>>();
+    if echo_vec.len() != n || !bad_echoes.is_empty() {
+        return Err(Blame {
+            error: InvalidCom,
+            parties: bad_echoes,
+        });
+    }
+    // every echoed digest is signed by its sender, so a digest that differs from the received
+    // message proves that the sender equivocated
+    let equivocating = (0..n)
+        .filter(|k| {
+            let own = digest(session_id, round, parties[*k], &received[*k]);
+            echo_vec.iter().any(|echo| echo.digests[*k].digest != own)
+        })
+        .collect::<Vec<usize>>();
+    if equivocating.is_empty() {
+        Ok(())
+    } else {
+        Err(Blame {
+            error: InvalidCom,
+            parties: equivocating,
+        })
+    }
+}
+
+fn digest<T: Serialize>(session_id: &[u8], round: &[u8], sender: usize, message: &T) -> BigInt {
+    let encoded = serde_json::to_vec(message).expect("serialization failure");
+    let mut transcript = Transcript::new(b"threshold/echo");
+    transcript.append_message(b"session", session_id);
+    transcript.append_message(b"round", round);
+    transcript.append_u64(b"sender", sender as u64);
+    transcript.append_message(b"message", &encoded);
+    transcript.challenge()
+}
+
+fn challenge(
+    session_id: &[u8],
+    round: &[u8],
+    sender: usize,
+    digest: &BigInt,
+    R: &GE,
+    sender_key: &GE,
+) -> FE {
+    let mut transcript = Transcript::new(b"threshold/echo-signature");
+    transcript.append_message(b"session", session_id);
+    transcript.append_message(b"round", round);
+    transcript.append_u64(b"sender", sender as u64);
+    transcript.append_bn(b"digest", digest);
+    transcript.append_point(b"P", sender_key);
+    transcript.append_point(b"R", R);
+    transcript.challenge_scalar()
+}
@@ -0,0 +1,422 @@
+/*
+    Multisig Schnorr
+
+    Copyright 2018 by Kzen Networks
+
+    This file is part of Multisig Schnorr library
+    (https://github.com/KZen-networks/multisig-schnorr)
+
+    Multisig Schnorr is free software: you can redistribute
+    it and/or modify it under the terms of the GNU General Public
+    License as published by the Free Software Foundation, either
+    version 3 of the License, or (at your option) any later version.
+
+    @license GPL-3.0+ <https://github.com/KZen-networks/multisig-schnorr/blob/master/LICENSE>
+*/
+
+//! Hierarchical threshold sharing for `zilliqa_schnorr` (Tassa, "Hierarchical Threshold
+//! Secret Sharing").
+//!
+//! Every party has a rank d and receives f^(d)(i + 1), the d-th derivative of the sharing
+//! polynomial f of degree t at its point. Rank 0 shares are plain Shamir shares. A set of
+//! parties recovers f(0) only if the Birkhoff system of their shares determines the constant
+//! term; for "any 3 signers, at least one of rank 0" the ranks are 0 and 1 with t = 2, and
+//! rank 1 parties alone only ever learn f'. Both key generation and the ephemeral key of a
+//! signing session are shared this way, so the same Birkhoff coefficients recombine the local
+//! signatures. Key generation runs the commitment round and encrypted share delivery of
+//! `zilliqa_schnorr`, with the derivative shares in place of the Shamir shares.
+//! `BirkhoffQuorum` plugs into `LocalSig::verify_local_sigs_in_quorum` and
+//! `Signature::generate`.
+use curv::arithmetic::traits::*;
+use curv::cryptographic_primitives::secret_sharing::feldman_vss::{
+    ShamirSecretSharing, VerifiableSS,
+};
+use curv::elliptic::curves::traits::*;
+use curv::BigInt;
+use protocols::secret::Secret;
+use protocols::thresholdsig::echo_broadcast::{verify_echoes, EchoMessage, VSS_COMMITMENTS};
+use protocols::thresholdsig::quorum::Interpolation;
+use protocols::thresholdsig::share_encryption::EncryptionKey;
+use protocols::thresholdsig::zilliqa_schnorr::{
+    KeyGenBroadcastMessage1, KeyGenBroadcastMessage2, KeyGenMessage3, Keys, SharedKeys,
+};
+use protocols::thresholdsig::Blame;
+use Error::{self, InvalidSS};
+
+type GE = curv::elliptic::curves::secp256_k1::GE;
+type FE = curv::elliptic::curves::secp256_k1::FE;
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct HierarchicalParameters {
+    // the sharing polynomial has degree threshold
+    pub threshold: usize,
+    // ranks[i] is the derivative order of the share of party i
+    pub ranks: Vec<usize>,
+}
+
+impl HierarchicalParameters {
+    pub fn new(threshold: usize, ranks: &[usize]) -> Result<HierarchicalParameters, Error> {
+        if ranks.iter().any(|rank| *rank > threshold) {
+            return Err(InvalidSS);
+        }
+        let params = HierarchicalParameters {
+            threshold,
+            ranks: ranks.to_vec(),
+        };
+        // a policy nobody can satisfy is a configuration error
+        let all = (0..ranks.len()).collect::<Vec<usize>>();
+        params.quorum(&all)?;
+        Ok(params)
+    }
+
+    // a random polynomial with f(0) = secret, committed to coefficient by coefficient;
+    // returns the shares of `parties`
+    pub fn share(&self, secret: &FE, parties: &[usize]) -> (VerifiableSS<GE>, Vec<FE>) {
+        let mut coefficients = vec![*secret];
+        coefficients.extend((0..self.threshold).map(|_| ECScalar::new_random()));
+        let coefficients = Secret::new(coefficients);
+        let g: GE = GE::generator();
+        let vss_scheme = VerifiableSS {
+            parameters: ShamirSecretSharing {
+                threshold: self.threshold,
+                share_count: self.ranks.len(),
+            },
+            commitments: coefficients
+                .expose()
+                .iter()
+                .map(|a_k| g * a_k)
+                .collect::<Vec<GE>>(),
+        };
+        let shares = parties
+            .iter()
+            .map(|party| {
+                // the terms below the rank vanish
+                let rank = self.ranks[*party];
+                self.row(*party)[rank..]
+                    .iter()
+                    .zip(coefficients.expose()[rank..].iter())
+                    .fold(FE::zero(), |acc, (c_k, a_k)| {
+                        let c_k: FE = ECScalar::from(c_k);
+                        acc + c_k * a_k
+                    })
+            })
+            .collect::<Vec<FE>>();
+        (vss_scheme, shares)
+    }
+
+    pub fn validate_share(
+        &self,
+        vss_scheme: &VerifiableSS<GE>,
+        share: &FE,
+        party: usize,
+    ) -> Result<(), Error> {
+        self.validate_share_public(vss_scheme, &(GE::generator() * share), party)
+    }
+
+    // share_g = sum_k c_k * C_k for the derivative coefficients c_k of the party
+    pub fn validate_share_public(
+        &self,
+        vss_scheme: &VerifiableSS<GE>,
+        share_g: &GE,
+        party: usize,
+    ) -> Result<(), Error> {
+        if party >= self.ranks.len() || vss_scheme.commitments.len() != self.threshold + 1 {
+            return Err(InvalidSS);
+        }
+        let row = self.row(party);
+        let rank = self.ranks[party];
+        let mut terms = vss_scheme.commitments[rank..]
+            .iter()
+            .zip(row[rank..].iter())
+            .map(|(commitment, c_k)| {
+                let c_k: FE = ECScalar::from(c_k);
+                commitment * &c_k
+            });
+        let first = terms.next().unwrap();
+        if terms.fold(first, |acc, term| acc + &term) == *share_g {
+            Ok(())
+        } else {
+            Err(InvalidSS)
+        }
+    }
+
+    // fails unless the shares of `parties` determine f(0)
+    pub fn quorum(&self, parties: &[usize]) -> Result<BirkhoffQuorum, Error> {
+        let duplicate = (0..parties.len()).any(|i| (0..i).any(|j| parties[i] == parties[j]));
+        if parties.len() <= self.threshold
+            || duplicate
+            || parties.iter().any(|party| *party >= self.ranks.len())
+        {
+            return Err(InvalidSS);
+        }
+        let coefficients = self.birkhoff_coefficients(parties)?;
+        Ok(BirkhoffQuorum {
+            params: self.clone(),
+            parties: parties.to_vec(),
+            coefficients,
+        })
+    }
+
+    // c_k = k! / (k - d)! * x^(k - d) mod q, the weight of coefficient a_k in f^(d)(x).
+    // Kept as BigInt: curv scalars cannot be created from zero.
+    fn row(&self, party: usize) -> Vec<BigInt> {
+        let q = FE::q();
+        let rank = self.ranks[party];
+        let x = BigInt::from((party + 1) as i32);
+        (0..self.threshold + 1)
+            .map(|k| {
+                if k < rank {
+                    return BigInt::from(0);
+                }
+                let falling_factorial = ((k - rank + 1)..(k + 1))
+                    .fold(BigInt::from(1), |acc, j| {
+                        BigInt::mod_mul(&acc, &BigInt::from(j as i32), &q)
+                    });
+                BigInt::mod_mul(
+                    &falling_factorial,
+                    &BigInt::mod_pow(&x, &BigInt::from((k - rank) as i32), &q),
+                    &q,
+                )
+            })
+            .collect::<Vec<BigInt>>()
+    }
+
+    // solves sum_i beta_i * row_i = (1, 0, ..., 0), so that f(0) = sum_i beta_i * share_i
+    fn birkhoff_coefficients(&self, parties: &[usize]) -> Result<Vec<FE>, Error> {
+        let q = FE::q();
+        let zero = BigInt::from(0);
+        let m = parties.len();
+        let rows = parties
+            .iter()
+            .map(|party| self.row(*party))
+            .collect::<Vec<Vec<BigInt>>>();
+        // augmented transposed system: one equation per coefficient of f
+        let mut system = (0..self.threshold + 1)
+            .map(|k| {
+                let mut equation = rows
+                    .iter()
+                    .map(|row| row[k].clone())
+                    .collect::<Vec<BigInt>>();
+                equation.push(BigInt::from(if k == 0 { 1 } else { 0 }));
+                equation
+            })
+            .collect::<Vec<Vec<BigInt>>>();
+        let mut pivots = Vec::new();
+        for col in 0..m {
+            let r = pivots.len();
+            let pivot = match (r..system.len()).find(|row| system[*row][col] != zero) {
+                Some(pivot) => pivot,
+                None => continue,
+            };
+            system.swap(r, pivot);
+            let inverse = BigInt::mod_inv(&system[r][col], &q);
+            system[r] = system[r]
+                .iter()
+                .map(|a| BigInt::mod_mul(a, &inverse, &q))
+                .collect::<Vec<BigInt>>();
+            for row in 0..system.len() {
+                if row != r && system[row][col] != zero {
+                    let factor = system[row][col].clone();
+                    system[row] = system[row]
+                        .iter()
+                        .zip(system[r].iter())
+                        .map(|(a, b)| BigInt::mod_sub(a, &BigInt::mod_mul(&factor, b, &q), &q))
+                        .collect::<Vec<BigInt>>();
+                }
+            }
+            pivots.push(col);
+        }
+        // an equation 0 = 1 left over means f(0) is not determined
+        if system[pivots.len()..]
+            .iter()
+            .any(|equation| equation[m] != zero)
+        {
+            return Err(InvalidSS);
+        }
+        let mut coefficients = vec![FE::zero(); m];
+        for (r, col) in pivots.iter().enumerate() {
+            if system[r][m] != zero {
+                coefficients[*col] = ECScalar::from(&system[r][m]);
+            }
+        }
+        Ok(coefficients)
+    }
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct BirkhoffQuorum {
+    pub params: HierarchicalParameters,
+    // 0-based key generation indices
+    pub parties: Vec<usize>,
+    pub coefficients: Vec<FE>,
+}
+
+impl Interpolation for BirkhoffQuorum {
+    fn members(&self) -> &[usize] {
+        &self.parties
+    }
+
+    fn validate_share_public(
+        &self,
+        vss_scheme: &VerifiableSS<GE>,
+        share_g: &GE,
+        position: usize,
+    ) -> Result<(), Error> {
+        self.params
+            .validate_share_public(vss_scheme, share_g, self.parties[position])
+    }
+
+    fn interpolate(&self, shares: &[FE]) -> FE {
+        assert_eq!(shares.len(), self.parties.len());
+        // a member whose coefficient is zero does not contribute
+        shares
+            .iter()
+            .zip(self.coefficients.iter())
+            .filter(|(_, beta)| beta.to_big_int() != BigInt::from(0))
+            .fold(FE::zero(), |acc, (share, beta)| acc + *beta * share)
+    }
+}
+
+// key generation, after the commitment round of `Keys` among the parties: every party deals
+// its u_i with `params.share` and encrypts each share to its recipient. parties[i] is both the
+// index of party i in params.ranks and its `Keys` index, recipient_key_vec[i] its long-term key.
+#[allow(clippy::too_many_arguments)]
+pub fn distribute(
+    keys: &Keys,
+    params: &HierarchicalParameters,
+    session_id: &[u8],
+    decom1_vec: &[KeyGenBroadcastMessage2],
+    bc1_vec: &[KeyGenBroadcastMessage1],
+    com_echo_vec: &[EchoMessage],
+    decom_echo_vec: &[EchoMessage],
+    encryption_key: &EncryptionKey,
+    recipient_key_vec: &[GE],
+    parties: &[usize],
+) -> Result<Vec<KeyGenMessage3>, Blame> {
+    Keys::phase1_verify_com(
+        session_id,
+        decom1_vec,
+        bc1_vec,
+        com_echo_vec,
+        decom_echo_vec,
+        recipient_key_vec,
+        parties,
+    )?;
+    let (vss_scheme, secret_shares) = params.share(keys.u_i.expose(), parties);
+    Ok(keys.phase2_encrypt_shares(
+        session_id,
+        &vss_scheme,
+        &secret_shares,
+        encryption_key,
+        recipient_key_vec,
+        parties,
+    ))
+}
+
+// msg3_vec[i] is the message of the dealer parties[i], with long-term key sender_key_vec[i]
+// and key y_vec[i], and vss_echo_vec[i] its echo of the VSS commitments; each recipient
+// checks its derivative share and sums what it receives
+#[allow(clippy::too_many_arguments)]
+pub fn construct_keypair(
+    params: &HierarchicalParameters,
+    party: usize,
+    session_id: &[u8],
+    y_vec: &[GE],
+    msg3_vec: &[KeyGenMessage3],
+    vss_echo_vec: &[EchoMessage],
+    encryption_key: &EncryptionKey,
+    sender_key_vec: &[GE],
+    parties: &[usize],
+) -> Result<SharedKeys, Blame> {
+    assert_eq!(y_vec.len(), msg3_vec.len());
+    assert_eq!(y_vec.len(), sender_key_vec.len());
+    assert_eq!(y_vec.len(), parties.len());
+    let vss_scheme_vec = msg3_vec
+        .iter()
+        .map(|msg3| msg3.vss_scheme.clone())
+        .collect::<Vec<VerifiableSS<GE>>>();
+    verify_echoes(
+        session_id,
+        VSS_COMMITMENTS,
+        &vss_scheme_vec,
+        vss_echo_vec,
+        sender_key_vec,
+        parties,
+    )?;
+    let secret_shares_vec = (0..y_vec.len())
+        .map(|i| {
+            if msg3_vec[i].sender_index != parties[i] {
+                return Err(InvalidSS);
+            }
+            msg3_vec[i].encrypted_share.decrypt(
+                encryption_key,
+                &sender_key_vec[i],
+                session_id,
+                parties[i],
+                party,
+            )
+        })
+        .collect::<Vec<Result<FE, Error>>>();
+    let bad_parties = (0..y_vec.len())
+        .filter(|i| match secret_shares_vec[*i] {
+            Ok(ref share) => {
+                msg3_vec[*i].vss_scheme.commitments[0] != y_vec[*i]
+                    || params
+                        .validate_share(&msg3_vec[*i].vss_scheme, share, party)
+                        .is_err()
+            }
+            Err(_) => true,
+        })
+        .collect::<Vec<usize>>();
+    if !bad_parties.is_empty() {
+        return Err(Blame {
+            error: InvalidSS,
+            parties: bad_parties,
+        });
+    }
+    let y = y_vec[1..].iter().fold(y_vec[0], |acc, y_i| acc + y_i);
+    let x_i = secret_shares_vec
+        .iter()
+        .fold(FE::zero(), |acc, share| acc + share.as_ref().unwrap());
+    Ok(SharedKeys {
+        y,
+        x_i: Secret::new(x_i),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_birkhoff_recovers_secret_for_authorised_sets_only() {
+        // two executives (rank 0), four staff (rank 1), any three with an executive
+        let params = HierarchicalParameters::new(2, &[0, 0, 1, 1, 1, 1]).unwrap();
+        let all = (0..6).collect::<Vec<usize>>();
+        let secret: FE = ECScalar::new_random();
+        let (vss_scheme, shares) = params.share(&secret, &all);
+        for (party, share) in shares.iter().enumerate() {
+            assert!(params.validate_share(&vss_scheme, share, party).is_ok());
+        }
+        assert!(params.validate_share(&vss_scheme, &shares[2], 3).is_err());
+
+        for parties in [
+            vec![0, 2, 3],
+            vec![5, 1, 4],
+            vec![0, 1, 2],
+            vec![0, 2, 3, 4, 5],
+        ]
+        .iter()
+        {
+            let quorum = params.quorum(parties).unwrap();
+            let quorum_shares = parties.iter().map(|i| shares[*i]).collect::<Vec<FE>>();
+            assert_eq!(quorum.interpolate(&quorum_shares), secret);
+        }
+        assert!(params.quorum(&[2, 3, 4]).is_err());
+        assert!(params.quorum(&[2, 3, 4, 5]).is_err());
+        assert!(params.quorum(&[0, 2]).is_err());
+
+        // nobody of rank 0 at all
+        assert!(HierarchicalParameters::new(2, &[1, 1, 1, 1]).is_err());
+    }
+}
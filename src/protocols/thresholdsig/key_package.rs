@@ -0,0 +1,179 @@
+/*
+    Multisig Schnorr
+
+    Copyright 2018 by Kzen Networks
+
+    This file is part of Multisig Schnorr library
+    (https://github.com/KZen-networks/multisig-schnorr)
+
+    Multisig Schnorr is free software: you can redistribute
+    it and/or modify it under the terms of the GNU General Public
+    License as published by the Free Software Foundation, either
+    version 3 of the License, or (at your option) any later version.
+
+    @license GPL-3.0+ <https://github.com/KZen-networks/multisig-schnorr/blob/master/LICENSE>
+*/
+
+//! The public side of a threshold key: the group key y, the threshold and the verification
+//! share x_i*G of every index, evaluated once from the summed VSS commitments of the key
+//! generation. A coordinator holding the packages of the key and of the ephemeral key checks
+//! single local signatures as they arrive with a `SignatureAggregator`, and the package is what
+//! gets archived.
+use curv::cryptographic_primitives::secret_sharing::feldman_vss::VerifiableSS;
+use protocols::thresholdsig::dealer::sum_commitments;
+use protocols::thresholdsig::quorum::SigningQuorum;
+use protocols::thresholdsig::Blame;
+use Error::{self, InvalidSS, InvalidSig};
+
+type GE = curv::elliptic::curves::secp256_k1::GE;
+type FE = curv::elliptic::curves::secp256_k1::FE;
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct PublicKeyPackage {
+    pub y: GE,
+    pub threshold: usize,
+    // verification_shares[i] = x_i*G of the share at point i + 1
+    pub verification_shares: Vec<GE>,
+}
+
+impl PublicKeyPackage {
+    pub fn from_vss(
+        y: &GE,
+        vss_scheme_vec: &[VerifiableSS<GE>],
+        share_count: usize,
+    ) -> Result<PublicKeyPackage, Error> {
+        if vss_scheme_vec.is_empty() {
+            return Err(InvalidSS);
+        }
+        let vss_sum = sum_commitments(vss_scheme_vec);
+        if vss_sum.commitments[0] != *y {
+            return Err(InvalidSS);
+        }
+        let verification_shares = (1..share_count + 1)
+            .map(|index| vss_sum.get_point_commitment(index))
+            .collect::<Vec<GE>>();
+        Ok(PublicKeyPackage {
+            y: *y,
+            threshold: vss_sum.parameters.threshold,
+            verification_shares,
+        })
+    }
+
+    pub fn verification_share(&self, party: usize) -> Option<&GE> {
+        self.verification_shares.get(party)
+    }
+}
+
+// x_i*G and beta_i*G of the share at `party`, in the key and in the ephemeral key
+pub fn verification_shares(
+    key_package: &PublicKeyPackage,
+    eph_package: &PublicKeyPackage,
+    party: usize,
+) -> Result<(GE, GE), Error> {
+    match (
+        key_package.verification_share(party),
+        eph_package.verification_share(party),
+    ) {
+        (Some(public_share), Some(eph_public_share)) => Ok((*public_share, *eph_public_share)),
+        _ => Err(InvalidSS),
+    }
+}
+
+// Collects local signatures as they arrive. Each one is checked against the verification
+// shares and dropped with blame if invalid; the signature is output once t+1 valid ones are
+// in. Any t+1 of the valid local signatures combine, so the ephemeral key has to be shared
+// among every party that may send one. The schemes plug in how a local signature of type L
+// is checked and how the interpolated gamma becomes a signature of type S.
+#[derive(Clone, Debug)]
+pub struct SignatureAggregator<L, S> {
+    message: Vec<u8>,
+    key_package: PublicKeyPackage,
+    eph_package: PublicKeyPackage,
+    // checks the local signature of a party and returns its gamma_i
+    verify: fn(&L, &[u8], usize, &PublicKeyPackage, &PublicKeyPackage) -> Result<FE, Error>,
+    // the signature from the gamma_i interpolated at 0
+    finalize: fn(FE, &[u8], &PublicKeyPackage, &PublicKeyPackage) -> S,
+    parties: Vec<usize>,
+    gamma_vec: Vec<FE>,
+    blamed: Vec<usize>,
+}
+
+impl<L, S> SignatureAggregator<L, S> {
+    pub fn new(
+        message: &[u8],
+        key_package: &PublicKeyPackage,
+        eph_package: &PublicKeyPackage,
+        verify: fn(&L, &[u8], usize, &PublicKeyPackage, &PublicKeyPackage) -> Result<FE, Error>,
+        finalize: fn(FE, &[u8], &PublicKeyPackage, &PublicKeyPackage) -> S,
+    ) -> Result<SignatureAggregator<L, S>, Error> {
+        if key_package.threshold != eph_package.threshold {
+            return Err(InvalidSS);
+        }
+        Ok(SignatureAggregator {
+            message: message.to_vec(),
+            key_package: key_package.clone(),
+            eph_package: eph_package.clone(),
+            verify,
+            finalize,
+            parties: Vec::new(),
+            gamma_vec: Vec::new(),
+            blamed: Vec::new(),
+        })
+    }
+
+    // `party` is the 0-based key generation index of the sender, and the index in the blame.
+    // A second local signature from the same party is ignored.
+    pub fn add(&mut self, party: usize, local_sig: L) -> Result<Option<S>, Blame> {
+        if self.blamed.contains(&party) {
+            return Err(Blame {
+                error: InvalidSig,
+                parties: vec![party],
+            });
+        }
+        if !self.parties.contains(&party) {
+            match (self.verify)(
+                &local_sig,
+                &self.message,
+                party,
+                &self.key_package,
+                &self.eph_package,
+            ) {
+                Ok(gamma_i) => {
+                    self.parties.push(party);
+                    self.gamma_vec.push(gamma_i);
+                }
+                Err(error) => {
+                    self.blamed.push(party);
+                    return Err(Blame {
+                        error,
+                        parties: vec![party],
+                    });
+                }
+            }
+        }
+        Ok(self.signature())
+    }
+
+    pub fn signature(&self) -> Option<S> {
+        let t = self.key_package.threshold;
+        if self.parties.len() <= t {
+            return None;
+        }
+        let quorum = SigningQuorum::new(
+            t,
+            self.key_package.verification_shares.len(),
+            &self.parties[..t + 1],
+        )
+        .ok()?;
+        Some((self.finalize)(
+            quorum.interpolate(&self.gamma_vec[..t + 1]),
+            &self.message,
+            &self.key_package,
+            &self.eph_package,
+        ))
+    }
+
+    pub fn blamed(&self) -> &[usize] {
+        &self.blamed
+    }
+}
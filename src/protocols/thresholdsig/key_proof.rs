@@ -0,0 +1,81 @@
+#![allow(non_snake_case)]
+/*
+    Multisig Schnorr
+
+    Copyright 2018 by Kzen Networks
+
+    This file is part of Multisig Schnorr library
+    (https://github.com/KZen-networks/multisig-schnorr)
+
+    Multisig Schnorr is free software: you can redistribute
+    it and/or modify it under the terms of the GNU General Public
+    License as published by the Free Software Foundation, either
+    version 3 of the License, or (at your option) any later version.
+
+    @license GPL-3.0+ <https://github.com/KZen-networks/multisig-schnorr/blob/master/LICENSE>
+*/
+
+//! Schnorr proof of knowledge of u_i for y_i = u_i*G, sent with the key generation decommitment.
+//! The challenge binds the session id and the party index, so a proof cannot be replayed by
+//! another party or in another key generation.
+use curv::elliptic::curves::traits::*;
+use protocols::secret::Secret;
+use protocols::transcript::Transcript;
+use Error::{self, InvalidKey};
+
+type GE = curv::elliptic::curves::secp256_k1::GE;
+type FE = curv::elliptic::curves::secp256_k1::FE;
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct KeyProof {
+    pub R: GE,
+    pub s: FE,
+}
+
+impl KeyProof {
+    pub fn prove(u_i: &FE, y_i: &GE, session_id: &[u8], party_index: usize) -> KeyProof {
+        let r: Secret<FE> = Secret::new(ECScalar::new_random());
+        let R = GE::generator() * r.expose();
+        let c = challenge(&R, y_i, session_id, party_index);
+        let s = *r.expose() + c * u_i;
+        KeyProof { R, s }
+    }
+
+    // sG = R + cY
+    pub fn verify(&self, y_i: &GE, session_id: &[u8], party_index: usize) -> Result<(), Error> {
+        let c = challenge(&self.R, y_i, session_id, party_index);
+        if GE::generator() * &self.s == self.R + &(y_i * &c) {
+            Ok(())
+        } else {
+            Err(InvalidKey)
+        }
+    }
+}
+
+fn challenge(R: &GE, y_i: &GE, session_id: &[u8], party_index: usize) -> FE {
+    let mut transcript = Transcript::new(b"threshold/key-proof");
+    transcript.append_message(b"session", session_id);
+    transcript.append_u64(b"party", party_index as u64);
+    transcript.append_point(b"y", y_i);
+    transcript.append_point(b"R", R);
+    transcript.challenge_scalar()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_key_proof_is_bound_to_session_and_party() {
+        let u_i: FE = ECScalar::new_random();
+        let y_i = GE::generator() * &u_i;
+        let proof = KeyProof::prove(&u_i, &y_i, b"session-1", 2);
+        assert!(proof.verify(&y_i, b"session-1", 2).is_ok());
+        assert!(proof.verify(&y_i, b"session-2", 2).is_err());
+        assert!(proof.verify(&y_i, b"session-1", 3).is_err());
+        let other: FE = ECScalar::new_random();
+        assert!(proof
+            .verify(&(GE::generator() * &other), b"session-1", 2)
+            .is_err());
+    }
+}
@@ -28,3 +28,40 @@ mod test_zilliqa;
 
 /// variant (1)
 pub mod zilliqa_schnorr;
+
+// proofs of knowledge of the key generation secrets
+pub mod key_proof;
+// encrypted point-to-point delivery of VSS secret shares
+pub mod share_encryption;
+// echo round detecting equivocation in broadcast rounds
+pub mod echo_broadcast;
+// signing with any t+1 of the n key holders
+pub mod quorum;
+// pool of ephemeral keys generated ahead of signing
+pub mod presign;
+// non-hardened child keys derived from a threshold key
+pub mod derivation;
+// splitting existing keys and recombining shares
+pub mod dealer;
+// parties holding several shares
+pub mod weighted;
+// rank based policies recombined with Birkhoff interpolation
+pub mod hierarchical;
+// recovering a lost share at its original index
+pub mod repair;
+// public verification shares of a threshold key
+pub mod key_package;
+// robust asynchronous signing coordinator
+pub mod roast;
+// handing a MuSig aggregate key over to a threshold committee
+pub mod musig_conversion;
+
+use Error;
+
+/// An error attributed to the parties that caused it. `parties` are positions in the
+/// message vectors handed to the failing function.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Blame {
+    pub error: Error,
+    pub parties: Vec<usize>,
+}
@@ -0,0 +1,157 @@
+/*
+    Multisig Schnorr
+
+    Copyright 2018 by Kzen Networks
+
+    This file is part of Multisig Schnorr library
+    (https://github.com/KZen-networks/multisig-schnorr)
+
+    Multisig Schnorr is free software: you can redistribute
+    it and/or modify it under the terms of the GNU General Public
+    License as published by the Free Software Foundation, either
+    version 3 of the License, or (at your option) any later version.
+
+    @license GPL-3.0+ <https://github.com/KZen-networks/multisig-schnorr/blob/master/LICENSE>
+*/
+
+//! Hand an n-of-n `musig_three_rounds` key over to a t-of-n committee, keeping apk.
+//!
+//! apk = sum_i a_i * X_i, so the aggregate secret is sum_i a_i * x_i. Every MuSig participant
+//! Feldman-shares a_i * x_i to the committee with `KeyPair::share_weighted_key`; a committee
+//! member checks that the constant commitment of participant i is a_i * X_i, which anyone can
+//! compute from the MuSig public keys, and adds up its shares. The committee then signs with
+//! the `bitcoin_schnorr` flow (ephemeral key shared among a quorum) but with the MuSig
+//! challenge, so the signatures verify with `musig_three_rounds::verify` under apk. The old
+//! n-of-n keys still sign for apk, so the MuSig participants should erase them after the
+//! handover.
+use curv::cryptographic_primitives::secret_sharing::feldman_vss::VerifiableSS;
+use curv::elliptic::curves::traits::*;
+use curv::BigInt;
+use protocols::aggsig::musig_three_rounds::{EphemeralKey, KeyAgg};
+use protocols::secret::Secret;
+use protocols::thresholdsig::bitcoin_schnorr::SharedKeys;
+use protocols::thresholdsig::key_package::PublicKeyPackage;
+use protocols::thresholdsig::quorum::SigningQuorum;
+use protocols::thresholdsig::Blame;
+use Error::{InvalidSS, InvalidSig};
+
+type GE = curv::elliptic::curves::secp256_k1::GE;
+type FE = curv::elliptic::curves::secp256_k1::FE;
+
+// a_i * X_i for every MuSig key, in the order used for the key aggregation
+pub fn weighted_public_keys(pks: &[GE]) -> Vec<GE> {
+    (0..pks.len())
+        .map(|i| {
+            let a_i: FE = ECScalar::from(&KeyAgg::key_aggregation_n(pks, i).hash);
+            pks[i] * &a_i
+        })
+        .collect::<Vec<GE>>()
+}
+
+// run by committee member `index` (0-based, share at point index + 1); vss_scheme_vec[i] and
+// secret_shares_vec[i] come from the holder of pks[i]. Blamed parties are MuSig positions.
+pub fn construct_keypair(
+    pks: &[GE],
+    vss_scheme_vec: &[VerifiableSS<GE>],
+    secret_shares_vec: &[FE],
+    index: usize,
+) -> Result<SharedKeys, Blame> {
+    assert_eq!(pks.len(), vss_scheme_vec.len());
+    assert_eq!(pks.len(), secret_shares_vec.len());
+    let weighted_pks = weighted_public_keys(pks);
+    let bad_parties = (0..pks.len())
+        .filter(|i| {
+            vss_scheme_vec[*i].commitments[0] != weighted_pks[*i]
+                || vss_scheme_vec[*i]
+                    .validate_share(&secret_shares_vec[*i], index + 1)
+                    .is_err()
+        })
+        .collect::<Vec<usize>>();
+    if !bad_parties.is_empty() {
+        return Err(Blame {
+            error: InvalidSS,
+            parties: bad_parties,
+        });
+    }
+    let x_i = secret_shares_vec
+        .iter()
+        .fold(FE::zero(), |acc, share| acc + share);
+    Ok(SharedKeys {
+        y: KeyAgg::key_aggregation_n(pks, 0).apk,
+        x_i: Secret::new(x_i),
+    })
+}
+
+// gamma_i = k_i + c * x_i with the MuSig challenge c = H(R.x || apk || message)
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct MusigLocalSig {
+    pub gamma_i: FE,
+    pub c: FE,
+}
+
+impl MusigLocalSig {
+    pub fn compute(
+        message: &[u8],
+        local_ephemeral_key: &SharedKeys,
+        local_private_key: &SharedKeys,
+    ) -> MusigLocalSig {
+        let c: FE = ECScalar::from(&EphemeralKey::hash_0(
+            &local_ephemeral_key.y,
+            &local_private_key.y,
+            message,
+            true,
+        ));
+        MusigLocalSig {
+            gamma_i: *local_ephemeral_key.x_i.expose() + c * local_private_key.x_i.expose(),
+            c,
+        }
+    }
+}
+
+// local_sig_vec[k] is the local signature of quorum.parties[k]; each one is checked against
+// the verification shares of the key and of the ephemeral key. Returns (R.x, s) for
+// musig_three_rounds::verify.
+pub fn generate(
+    message: &[u8],
+    local_sig_vec: &[MusigLocalSig],
+    quorum: &SigningQuorum,
+    key_package: &PublicKeyPackage,
+    eph_package: &PublicKeyPackage,
+) -> Result<(BigInt, BigInt), Blame> {
+    assert_eq!(local_sig_vec.len(), quorum.parties.len());
+    let c: FE = ECScalar::from(&EphemeralKey::hash_0(
+        &eph_package.y,
+        &key_package.y,
+        message,
+        true,
+    ));
+    let g: GE = GE::generator();
+    let bad_parties = (0..local_sig_vec.len())
+        .filter(|k| {
+            let party = quorum.parties[*k];
+            match (
+                key_package.verification_share(party),
+                eph_package.verification_share(party),
+            ) {
+                (Some(public_share), Some(eph_public_share)) => {
+                    local_sig_vec[*k].c != c
+                        || g * &local_sig_vec[*k].gamma_i
+                            != *eph_public_share + &(public_share * &c)
+                }
+                _ => true,
+            }
+        })
+        .collect::<Vec<usize>>();
+    if !bad_parties.is_empty() {
+        return Err(Blame {
+            error: InvalidSig,
+            parties: bad_parties,
+        });
+    }
+    let gamma_vec = local_sig_vec
+        .iter()
+        .map(|local_sig| local_sig.gamma_i)
+        .collect::<Vec<FE>>();
+    let s = quorum.interpolate(&gamma_vec);
+    Ok((eph_package.y.x_coor().unwrap(), s.to_big_int()))
+}
@@ -0,0 +1,132 @@
+/*
+    Multisig Schnorr
+
+    Copyright 2018 by Kzen Networks
+
+    This file is part of Multisig Schnorr library
+    (https://github.com/KZen-networks/multisig-schnorr)
+
+    Multisig Schnorr is free software: you can redistribute
+    it and/or modify it under the terms of the GNU General Public
+    License as published by the Free Software Foundation, either
+    version 3 of the License, or (at your option) any later version.
+
+    @license GPL-3.0+ <https://github.com/KZen-networks/multisig-schnorr/blob/master/LICENSE>
+*/
+
+//! Offline preprocessing for `zilliqa_schnorr`: the ephemeral key generation of a signing
+//! session does not depend on the message, so a quorum can run it ahead of time and keep the
+//! results in a pool. Online signing is then a single round of `LocalSig` exchange.
+//!
+//! A pre-signature id is derived from the ephemeral key and its VSS commitments, so every
+//! member of the quorum computes the same id without coordination. The ephemeral share is only
+//! reachable through `KeyStore::sign_with_presignature`, which records the id in the nonce
+//! ledger before signing, so each id is handed out once, also across crashes and restored
+//! copies of the pool.
+//!
+//! All ephemeral keys V of a pool are public before any message is fixed. Signing with several
+//! of them concurrently lets an adversary who picks the messages run the ROS / Wagner attack
+//! on the open nonces and forge a signature. A quorum must use one pre-signature at a time and
+//! start the next session only after the previous one completed or was abandoned.
+use curv::arithmetic::traits::Converter;
+use curv::cryptographic_primitives::secret_sharing::feldman_vss::VerifiableSS;
+use curv::BigInt;
+use protocols::thresholdsig::quorum::SigningQuorum;
+use protocols::thresholdsig::zilliqa_schnorr::SharedKeys;
+use protocols::transcript::Transcript;
+
+type GE = curv::elliptic::curves::secp256_k1::GE;
+
+#[derive(Clone, Debug)]
+pub struct PreSignature {
+    pub id: String,
+    pub quorum: SigningQuorum,
+    // this party's share of the ephemeral key V = eph_shared_key.y
+    pub(crate) eph_shared_key: SharedKeys,
+    pub eph_vss_vec: Vec<VerifiableSS<GE>>,
+}
+
+impl PreSignature {
+    pub fn new(
+        quorum: SigningQuorum,
+        eph_shared_key: SharedKeys,
+        eph_vss_vec: Vec<VerifiableSS<GE>>,
+    ) -> PreSignature {
+        let mut transcript = Transcript::new(b"threshold/presignature");
+        for party in quorum.parties.iter() {
+            transcript.append_u64(b"party", *party as u64);
+        }
+        transcript.append_point(b"V", &eph_shared_key.y);
+        for vss_scheme in eph_vss_vec.iter() {
+            for commitment in vss_scheme.commitments.iter() {
+                transcript.append_point(b"commitment", commitment);
+            }
+        }
+        PreSignature {
+            id: transcript.challenge().to_hex(),
+            quorum,
+            eph_shared_key,
+            eph_vss_vec,
+        }
+    }
+
+    pub fn v(&self) -> GE {
+        self.eph_shared_key.y
+    }
+}
+
+// the used ids are kept in the keystore's nonce ledger, not in the pool
+#[derive(Clone, Debug, Default)]
+pub struct PreSignaturePool {
+    pub presignatures: Vec<PreSignature>,
+}
+
+impl PreSignaturePool {
+    pub fn new() -> PreSignaturePool {
+        PreSignaturePool::default()
+    }
+
+    // false if a pre-signature with the same id is in the pool
+    pub fn add(&mut self, presignature: PreSignature) -> bool {
+        if self.get(&presignature.id).is_some() {
+            return false;
+        }
+        self.presignatures.push(presignature);
+        true
+    }
+
+    pub fn get(&self, id: &str) -> Option<&PreSignature> {
+        self.presignatures.iter().find(|presig| presig.id == id)
+    }
+
+    pub fn ids(&self) -> Vec<String> {
+        self.presignatures
+            .iter()
+            .map(|presig| presig.id.clone())
+            .collect::<Vec<String>>()
+    }
+
+    pub fn len(&self) -> usize {
+        self.presignatures.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.presignatures.is_empty()
+    }
+
+    // removes the pre-signature from the pool, only the keystore takes pre-signatures
+    pub(crate) fn take(&mut self, id: &str) -> Option<PreSignature> {
+        let position = self
+            .presignatures
+            .iter()
+            .position(|presig| presig.id == id)?;
+        Some(self.presignatures.remove(position))
+    }
+}
+
+// the nonce ledger entry of a used pre-signature id
+pub fn ledger_key(id: &str) -> BigInt {
+    let mut transcript = Transcript::new(b"threshold/presignature-used");
+    transcript.append_message(b"id", id.as_bytes());
+    transcript.challenge()
+}
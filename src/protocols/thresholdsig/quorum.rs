@@ -0,0 +1,188 @@
+/*
+    Multisig Schnorr
+
+    Copyright 2018 by Kzen Networks
+
+    This file is part of Multisig Schnorr library
+    (https://github.com/KZen-networks/multisig-schnorr)
+
+    Multisig Schnorr is free software: you can redistribute
+    it and/or modify it under the terms of the GNU General Public
+    License as published by the Free Software Foundation, either
+    version 3 of the License, or (at your option) any later version.
+
+    @license GPL-3.0+ <https://github.com/KZen-networks/multisig-schnorr/blob/master/LICENSE>
+*/
+
+//! A signing session runs among a quorum of at least t+1 of the n key holders. The ephemeral
+//! key is shared among the quorum only (at the points returned by `points`) and the local
+//! signatures are combined with Lagrange coefficients for the quorum, so the parties outside
+//! the quorum can be offline.
+use curv::cryptographic_primitives::secret_sharing::feldman_vss::VerifiableSS;
+use curv::elliptic::curves::traits::*;
+use curv::BigInt;
+use Error::{self, InvalidSS};
+
+type GE = curv::elliptic::curves::secp256_k1::GE;
+type FE = curv::elliptic::curves::secp256_k1::FE;
+
+// how the shares held by a quorum are checked and recombined
+pub trait Interpolation {
+    // 0-based key generation indices of the members
+    fn members(&self) -> &[usize];
+
+    // checks share*G of the member at `position` against the VSS commitments
+    fn validate_share_public(
+        &self,
+        vss_scheme: &VerifiableSS<GE>,
+        share_g: &GE,
+        position: usize,
+    ) -> Result<(), Error>;
+
+    // shares[k] belongs to the k-th member
+    fn interpolate(&self, shares: &[FE]) -> FE;
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct SigningQuorum {
+    pub threshold: usize,
+    // 0-based key generation indices, the share of party i is at point i + 1
+    pub parties: Vec<usize>,
+}
+
+impl SigningQuorum {
+    pub fn new(
+        threshold: usize,
+        share_count: usize,
+        parties: &[usize],
+    ) -> Result<SigningQuorum, Error> {
+        let duplicate = (0..parties.len()).any(|i| (0..i).any(|j| parties[i] == parties[j]));
+        if parties.len() <= threshold || duplicate || parties.iter().any(|i| *i >= share_count) {
+            return Err(InvalidSS);
+        }
+        Ok(SigningQuorum {
+            threshold,
+            parties: parties.to_vec(),
+        })
+    }
+
+    // the points at which the ephemeral key is shared among the quorum
+    pub fn points(&self) -> Vec<usize> {
+        self.parties.iter().map(|i| i + 1).collect::<Vec<usize>>()
+    }
+
+    // lambda_i = prod_{j != i} x_j / (x_j - x_i), the coefficient of the i-th member's share at 0
+    pub fn lagrange_coefficient(&self, position: usize) -> FE {
+        let points = self
+            .points()
+            .iter()
+            .map(|x| ECScalar::from(&BigInt::from(*x as i32)))
+            .collect::<Vec<FE>>();
+        let x_i = points[position];
+        let (num, denum) = points
+            .iter()
+            .enumerate()
+            .filter(|(j, _)| *j != position)
+            .fold(
+                (
+                    ECScalar::from(&BigInt::from(1)),
+                    ECScalar::from(&BigInt::from(1)),
+                ),
+                |(num, denum): (FE, FE), (_, x_j)| (num * x_j, denum * x_j.sub(&x_i.get_element())),
+            );
+        num * denum.invert()
+    }
+
+    // shares[k] belongs to parties[k]
+    pub fn interpolate(&self, shares: &[FE]) -> FE {
+        assert_eq!(shares.len(), self.parties.len());
+        shares
+            .iter()
+            .enumerate()
+            .fold(FE::zero(), |acc, (k, share)| {
+                acc + self.lagrange_coefficient(k) * share
+            })
+    }
+}
+
+// plain Shamir sharing, recombined with Lagrange coefficients
+impl Interpolation for SigningQuorum {
+    fn members(&self) -> &[usize] {
+        &self.parties
+    }
+
+    fn validate_share_public(
+        &self,
+        vss_scheme: &VerifiableSS<GE>,
+        share_g: &GE,
+        position: usize,
+    ) -> Result<(), Error> {
+        vss_scheme
+            .validate_share_public(share_g, self.parties[position] + 1)
+            .map_err(|_| InvalidSS)
+    }
+
+    fn interpolate(&self, shares: &[FE]) -> FE {
+        SigningQuorum::interpolate(self, shares)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_interpolate_any_quorum() {
+        let secret: FE = ECScalar::new_random();
+        let (_, shares) = VerifiableSS::share(2, 5, &secret);
+        for parties in [
+            vec![0, 1, 2],
+            vec![4, 2, 0],
+            vec![1, 3, 4],
+            vec![0, 1, 2, 3, 4],
+        ]
+        .iter()
+        {
+            let quorum = SigningQuorum::new(2, 5, parties).unwrap();
+            let quorum_shares = parties.iter().map(|i| shares[*i]).collect::<Vec<FE>>();
+            assert_eq!(quorum.interpolate(&quorum_shares), secret);
+        }
+    }
+
+    #[test]
+    fn test_quorum_without_offline_parties() {
+        // parties 1 and 3 are offline, the ephemeral key is shared among the quorum only
+        let x: FE = ECScalar::new_random();
+        let (vss_scheme, x_shares) = VerifiableSS::share(2, 5, &x);
+        let quorum = SigningQuorum::new(2, 5, &[0, 2, 4]).unwrap();
+        let k: FE = ECScalar::new_random();
+        let (_, k_shares) = VerifiableSS::share_at_indices(2, 3, &k, &quorum.points());
+
+        // the local signatures s_i = k_i + e*x_i combine to k + e*x
+        let e: FE = ECScalar::new_random();
+        let local_sigs = (0..3)
+            .map(|position| k_shares[position] + &(e * &x_shares[quorum.parties[position]]))
+            .collect::<Vec<FE>>();
+        assert_eq!(
+            Interpolation::interpolate(&quorum, &local_sigs),
+            k + &(e * &x)
+        );
+
+        // shares are checked at the key generation point of the member, not its position
+        let share_g = GE::generator() * &x_shares[2];
+        assert!(quorum
+            .validate_share_public(&vss_scheme, &share_g, 1)
+            .is_ok());
+        assert!(quorum
+            .validate_share_public(&vss_scheme, &share_g, 0)
+            .is_err());
+        assert_eq!(quorum.members(), &[0, 2, 4]);
+    }
+
+    #[test]
+    fn test_invalid_quorum() {
+        assert!(SigningQuorum::new(2, 5, &[0, 1]).is_err());
+        assert!(SigningQuorum::new(2, 5, &[0, 1, 1]).is_err());
+        assert!(SigningQuorum::new(2, 5, &[0, 1, 5]).is_err());
+    }
+}
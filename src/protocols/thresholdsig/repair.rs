@@ -0,0 +1,133 @@
+/*
+    Multisig Schnorr
+
+    Copyright 2018 by Kzen Networks
+
+    This file is part of Multisig Schnorr library
+    (https://github.com/KZen-networks/multisig-schnorr)
+
+    Multisig Schnorr is free software: you can redistribute
+    it and/or modify it under the terms of the GNU General Public
+    License as published by the Free Software Foundation, either
+    version 3 of the License, or (at your option) any later version.
+
+    @license GPL-3.0+ <https://github.com/KZen-networks/multisig-schnorr/blob/master/LICENSE>
+*/
+
+//! Repair of a lost `zilliqa_schnorr` share at its original index, without a new key
+//! generation (Laing and Stinson, "Enrollment Procedures for Threshold Signatures").
+//!
+//! t+1 helpers take part. Helper i computes delta_i = lambda_i(r) * x_i, its contribution to
+//! f(r) for the lost index r, and splits it into random summands, one per helper (phase 1).
+//! Each helper adds up the summands it received and sends the sum sigma_j to the repaired
+//! party (phase 2), which adds the sigmas to get x_r (phase 3). A helper only sees one random
+//! summand of every other delta, so no helper learns x_r or another helper's share. Phase 1
+//! messages are point-to-point and must be encrypted, e.g. with `EncryptedShare`.
+use curv::cryptographic_primitives::secret_sharing::feldman_vss::VerifiableSS;
+use curv::elliptic::curves::traits::*;
+use curv::BigInt;
+use protocols::secret::Secret;
+use protocols::thresholdsig::dealer::sum_commitments;
+use protocols::thresholdsig::zilliqa_schnorr::SharedKeys;
+use Error::{self, InvalidSS};
+
+type GE = curv::elliptic::curves::secp256_k1::GE;
+type FE = curv::elliptic::curves::secp256_k1::FE;
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct RepairSession {
+    // 0-based key generation indices of the helpers
+    pub helpers: Vec<usize>,
+    // 0-based index of the lost share
+    pub lost: usize,
+}
+
+impl RepairSession {
+    pub fn new(
+        threshold: usize,
+        share_count: usize,
+        helpers: &[usize],
+        lost: usize,
+    ) -> Result<RepairSession, Error> {
+        let duplicate = (0..helpers.len()).any(|i| (0..i).any(|j| helpers[i] == helpers[j]));
+        if helpers.len() != threshold + 1
+            || duplicate
+            || lost >= share_count
+            || helpers.contains(&lost)
+            || helpers.iter().any(|i| *i >= share_count)
+        {
+            return Err(InvalidSS);
+        }
+        Ok(RepairSession {
+            helpers: helpers.to_vec(),
+            lost,
+        })
+    }
+
+    // phase 1 for the helper at `position`: summands[k] goes to helpers[k]
+    pub fn split_contribution(&self, position: usize, shared_keys: &SharedKeys) -> Vec<Secret<FE>> {
+        let delta = self.lagrange_coefficient(position) * shared_keys.x_i.expose();
+        let mut summands = (1..self.helpers.len())
+            .map(|_| Secret::new(ECScalar::new_random()))
+            .collect::<Vec<Secret<FE>>>();
+        let masks = summands
+            .iter()
+            .fold(FE::zero(), |acc, summand| acc + summand.expose());
+        summands.insert(position, Secret::new(delta.sub(&masks.get_element())));
+        summands
+    }
+
+    // phase 2: received[k] is the summand sent by helpers[k]
+    pub fn sum_contributions(&self, received: &[Secret<FE>]) -> Secret<FE> {
+        assert_eq!(received.len(), self.helpers.len());
+        Secret::new(
+            received
+                .iter()
+                .fold(FE::zero(), |acc, summand| acc + summand.expose()),
+        )
+    }
+
+    // phase 3, run by the repaired party; vss_scheme_vec is from the original key generation
+    pub fn recover(
+        &self,
+        sigma_vec: &[Secret<FE>],
+        y: &GE,
+        vss_scheme_vec: &[VerifiableSS<GE>],
+    ) -> Result<SharedKeys, Error> {
+        if sigma_vec.len() != self.helpers.len() || vss_scheme_vec.is_empty() {
+            return Err(InvalidSS);
+        }
+        let x_i = sigma_vec
+            .iter()
+            .fold(FE::zero(), |acc, sigma| acc + sigma.expose());
+        let vss_sum = sum_commitments(vss_scheme_vec);
+        if vss_sum.commitments[0] != *y || vss_sum.validate_share(&x_i, self.lost + 1).is_err() {
+            return Err(InvalidSS);
+        }
+        Ok(SharedKeys {
+            y: *y,
+            x_i: Secret::new(x_i),
+        })
+    }
+
+    // lambda_i(r) = prod_{j != i} (r - x_j) / (x_i - x_j)
+    fn lagrange_coefficient(&self, position: usize) -> FE {
+        let point = |index: usize| -> FE { ECScalar::from(&BigInt::from((index + 1) as i32)) };
+        let one: FE = ECScalar::from(&BigInt::from(1));
+        let r = point(self.lost);
+        let x_i = point(self.helpers[position]);
+        let (num, denum) = self
+            .helpers
+            .iter()
+            .enumerate()
+            .filter(|(k, _)| *k != position)
+            .fold((one, one), |(num, denum): (FE, FE), (_, helper)| {
+                let x_j = point(*helper);
+                (
+                    num * r.sub(&x_j.get_element()),
+                    denum * x_i.sub(&x_j.get_element()),
+                )
+            });
+        num * denum.invert()
+    }
+}
@@ -0,0 +1,373 @@
+#![allow(non_snake_case)]
+/*
+    Multisig Schnorr
+
+    Copyright 2018 by Kzen Networks
+
+    This file is part of Multisig Schnorr library
+    (https://github.com/KZen-networks/multisig-schnorr)
+
+    Multisig Schnorr is free software: you can redistribute
+    it and/or modify it under the terms of the GNU General Public
+    License as published by the Free Software Foundation, either
+    version 3 of the License, or (at your option) any later version.
+
+    @license GPL-3.0+ <https://github.com/KZen-networks/multisig-schnorr/blob/master/LICENSE>
+*/
+
+//! Robust asynchronous threshold signing for `zilliqa_schnorr` keys (Ruffing et al., "ROAST:
+//! Robust Asynchronous Schnorr Threshold Signatures").
+//!
+//! The ephemeral key generation of `LocalSig` needs every quorum member online, so a silent
+//! member stalls it. Here every signer instead sends a fresh nonce commitment (D, E) with each
+//! message to the coordinator, as in FROST. The coordinator keeps the signers that answered and
+//! have an unused commitment. As soon as t+1 of them are available it starts a session with
+//! them, and any number of sessions may run at the same time. Each signature share is checked
+//! against the verification share of its sender, and a sender of an invalid share is never
+//! included again, as is a signer that answers without the share it was asked for. A silent
+//! signer only blocks the sessions it is in; the others come back with their next commitment.
+//! With t+1 honest signers that eventually answer, some session completes. The output is a
+//! plain `zilliqa_schnorr::Signature` under y.
+use curv::elliptic::curves::traits::*;
+use protocols::secret::Secret;
+use protocols::thresholdsig::key_package::PublicKeyPackage;
+use protocols::thresholdsig::quorum::SigningQuorum;
+use protocols::thresholdsig::zilliqa_schnorr::{challenge, SharedKeys, Signature};
+use protocols::thresholdsig::Blame;
+use protocols::transcript::Transcript;
+use Error::{self, InvalidKey, InvalidSS, InvalidSig};
+
+type GE = curv::elliptic::curves::secp256_k1::GE;
+type FE = curv::elliptic::curves::secp256_k1::FE;
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct NonceCommitment {
+    pub party: usize,
+    pub D: GE,
+    pub E: GE,
+}
+
+// the secret side of a NonceCommitment, used for one signature share only
+#[derive(Debug)]
+pub struct SigningNonces {
+    commitment: NonceCommitment,
+    d: Secret<FE>,
+    e: Secret<FE>,
+}
+
+impl SigningNonces {
+    pub fn create(party: usize) -> SigningNonces {
+        let d: FE = ECScalar::new_random();
+        let e: FE = ECScalar::new_random();
+        let g: GE = GE::generator();
+        SigningNonces {
+            commitment: NonceCommitment {
+                party,
+                D: g * &d,
+                E: g * &e,
+            },
+            d: Secret::new(d),
+            e: Secret::new(e),
+        }
+    }
+
+    pub fn commitment(&self) -> &NonceCommitment {
+        &self.commitment
+    }
+}
+
+// sent by the coordinator to every member of a new session
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct SessionStart {
+    pub session_id: usize,
+    // one per member, in quorum order
+    pub commitments: Vec<NonceCommitment>,
+}
+
+impl SessionStart {
+    pub fn quorum(&self, threshold: usize, share_count: usize) -> Result<SigningQuorum, Error> {
+        let parties = self
+            .commitments
+            .iter()
+            .map(|commitment| commitment.party)
+            .collect::<Vec<usize>>();
+        SigningQuorum::new(threshold, share_count, &parties)
+    }
+
+    // rho_i binds the nonce of member i to the message and to all commitments of the session
+    fn binding_factors(&self, message: &[u8], y: &GE) -> Vec<FE> {
+        (0..self.commitments.len())
+            .map(|position| {
+                let mut transcript = Transcript::new(b"roast/binding-factor");
+                transcript.append_point(b"y", y);
+                transcript.append_message(b"message", message);
+                for commitment in self.commitments.iter() {
+                    transcript.append_u64(b"party", commitment.party as u64);
+                    transcript.append_point(b"D", &commitment.D);
+                    transcript.append_point(b"E", &commitment.E);
+                }
+                transcript.append_u64(b"position", position as u64);
+                transcript.challenge_scalar()
+            })
+            .collect::<Vec<FE>>()
+    }
+
+    // R = sum_i D_i + rho_i * E_i
+    fn group_commitment(&self, binding_factors: &[FE]) -> GE {
+        let mut terms = self
+            .commitments
+            .iter()
+            .zip(binding_factors.iter())
+            .map(|(commitment, rho)| commitment.D + &(commitment.E * rho));
+        let first = terms.next().unwrap();
+        terms.fold(first, |acc, term| acc + &term)
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct SignatureShare {
+    pub z: FE,
+}
+
+// a signer's message to the coordinator: the share for a session it was asked to sign in, if
+// any, and the commitment for its next session
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct SignerResponse {
+    pub share: Option<(usize, SignatureShare)>,
+    pub next: NonceCommitment,
+}
+
+// signer side: keeps the nonces behind the commitments it handed out
+#[derive(Debug)]
+pub struct RoastSigner {
+    pub party: usize,
+    threshold: usize,
+    share_count: usize,
+    shared_keys: SharedKeys,
+    nonces: Vec<SigningNonces>,
+}
+
+impl RoastSigner {
+    pub fn new(
+        party: usize,
+        threshold: usize,
+        share_count: usize,
+        shared_keys: &SharedKeys,
+    ) -> RoastSigner {
+        RoastSigner {
+            party,
+            threshold,
+            share_count,
+            shared_keys: shared_keys.clone(),
+            nonces: Vec::new(),
+        }
+    }
+
+    // the first message of the signer, before it is in any session
+    pub fn ready(&mut self) -> SignerResponse {
+        SignerResponse {
+            share: None,
+            next: self.next_commitment(),
+        }
+    }
+
+    // z_i = d_i + rho_i * e_i - c * lambda_i * x_i; the nonces are removed before use
+    pub fn sign(&mut self, message: &[u8], start: &SessionStart) -> Result<SignerResponse, Error> {
+        let position = start
+            .commitments
+            .iter()
+            .position(|commitment| commitment.party == self.party)
+            .ok_or(InvalidSS)?;
+        let nonce_index = self
+            .nonces
+            .iter()
+            .position(|nonces| nonces.commitment == start.commitments[position])
+            .ok_or(InvalidKey)?;
+        let nonces = self.nonces.remove(nonce_index);
+        let quorum = start.quorum(self.threshold, self.share_count)?;
+        let binding_factors = start.binding_factors(message, &self.shared_keys.y);
+        let R = start.group_commitment(&binding_factors);
+        let c = challenge(&R, &self.shared_keys.y, message);
+        let k_i = *nonces.d.expose() + binding_factors[position] * nonces.e.expose();
+        let z = k_i.sub(
+            &(c * quorum.lagrange_coefficient(position) * self.shared_keys.x_i.expose())
+                .get_element(),
+        );
+        Ok(SignerResponse {
+            share: Some((start.session_id, SignatureShare { z })),
+            next: self.next_commitment(),
+        })
+    }
+
+    fn next_commitment(&mut self) -> NonceCommitment {
+        let nonces = SigningNonces::create(self.party);
+        let commitment = nonces.commitment().clone();
+        self.nonces.push(nonces);
+        commitment
+    }
+}
+
+#[derive(Clone, Debug)]
+struct OpenSession {
+    start: SessionStart,
+    quorum: SigningQuorum,
+    binding_factors: Vec<FE>,
+    R: GE,
+    shares: Vec<Option<FE>>,
+}
+
+#[derive(Clone, Debug)]
+pub struct RoastCoordinator {
+    message: Vec<u8>,
+    key_package: PublicKeyPackage,
+    // signers with an unused commitment and no outstanding request
+    responsive: Vec<NonceCommitment>,
+    // parties that sent their first message
+    joined: Vec<usize>,
+    // pending[i] is the session party i was asked to sign in and has not answered yet
+    pending: Vec<Option<usize>>,
+    malicious: Vec<usize>,
+    sessions: Vec<OpenSession>,
+    signature: Option<Signature>,
+}
+
+impl RoastCoordinator {
+    pub fn new(message: &[u8], key_package: &PublicKeyPackage) -> RoastCoordinator {
+        RoastCoordinator {
+            message: message.to_vec(),
+            key_package: key_package.clone(),
+            responsive: Vec::new(),
+            joined: Vec::new(),
+            pending: vec![None; key_package.verification_shares.len()],
+            malicious: Vec::new(),
+            sessions: Vec::new(),
+            signature: None,
+        }
+    }
+
+    // handles a message of `party` and returns the sessions to start. Only the first message of
+    // a party comes without a share; after that it answers with the share of the session it was
+    // asked to sign in. A blame names the party by its key generation index; such a party is
+    // ignored from then on.
+    pub fn receive(
+        &mut self,
+        party: usize,
+        response: SignerResponse,
+    ) -> Result<Vec<SessionStart>, Blame> {
+        if self.malicious.contains(&party)
+            || response.next.party != party
+            || party >= self.key_package.verification_shares.len()
+        {
+            return Err(self.blame(party, InvalidKey));
+        }
+        if self.signature.is_some() {
+            return Ok(Vec::new());
+        }
+        match (response.share, self.pending[party]) {
+            (None, None) if !self.joined.contains(&party) => self.joined.push(party),
+            (Some((session_id, share)), Some(pending)) if session_id == pending => {
+                self.add_share(party, session_id, &share)?;
+                self.pending[party] = None;
+                if self.signature.is_some() {
+                    return Ok(Vec::new());
+                }
+            }
+            _ => return Err(self.blame(party, InvalidSS)),
+        }
+        self.responsive.push(response.next);
+        let t = self.key_package.threshold;
+        if self.responsive.len() <= t {
+            return Ok(Vec::new());
+        }
+        let start = SessionStart {
+            session_id: self.sessions.len(),
+            commitments: self.responsive.drain(..).collect::<Vec<NonceCommitment>>(),
+        };
+        let quorum = start
+            .quorum(t, self.key_package.verification_shares.len())
+            .expect("responsive signers are distinct");
+        for member in quorum.parties.iter() {
+            self.pending[*member] = Some(start.session_id);
+        }
+        let binding_factors = start.binding_factors(&self.message, &self.key_package.y);
+        let R = start.group_commitment(&binding_factors);
+        self.sessions.push(OpenSession {
+            shares: vec![None; quorum.parties.len()],
+            start: start.clone(),
+            quorum,
+            binding_factors,
+            R,
+        });
+        Ok(vec![start])
+    }
+
+    pub fn signature(&self) -> Option<&Signature> {
+        self.signature.as_ref()
+    }
+
+    pub fn malicious(&self) -> &[usize] {
+        &self.malicious
+    }
+
+    // members of open sessions that were asked for a share and did not answer
+    pub fn unresponsive(&self) -> Vec<usize> {
+        (0..self.pending.len())
+            .filter(|party| self.pending[*party].is_some() && !self.malicious.contains(party))
+            .collect::<Vec<usize>>()
+    }
+
+    // z_i*G + c * lambda_i * x_i*G = D_i + rho_i * E_i
+    fn add_share(
+        &mut self,
+        party: usize,
+        session_id: usize,
+        share: &SignatureShare,
+    ) -> Result<(), Blame> {
+        let (position, valid) = {
+            let session = match self.sessions.get(session_id) {
+                Some(session) => session,
+                None => return Err(self.blame(party, InvalidSS)),
+            };
+            let position = match session.quorum.parties.iter().position(|p| *p == party) {
+                Some(position) if session.shares[position].is_none() => position,
+                _ => return Err(self.blame(party, InvalidSS)),
+            };
+            let c = challenge(&session.R, &self.key_package.y, &self.message);
+            let commitment = &session.start.commitments[position];
+            let lhs = GE::generator() * &share.z
+                + &(self.key_package.verification_shares[party]
+                    * &(c * session.quorum.lagrange_coefficient(position)));
+            let rhs = commitment.D + &(commitment.E * &session.binding_factors[position]);
+            (position, lhs == rhs)
+        };
+        if !valid {
+            return Err(self.blame(party, InvalidSig));
+        }
+        let session = &mut self.sessions[session_id];
+        session.shares[position] = Some(share.z);
+        if session.shares.iter().all(|share| share.is_some()) {
+            let s = session
+                .shares
+                .iter()
+                .fold(FE::zero(), |acc, z| acc + z.as_ref().unwrap());
+            self.signature = Some(Signature {
+                s,
+                e: challenge(&session.R, &self.key_package.y, &self.message),
+            });
+        }
+        Ok(())
+    }
+
+    // the party's unused commitment is dropped, so it is in no later session
+    fn blame(&mut self, party: usize, error: Error) -> Blame {
+        if !self.malicious.contains(&party) {
+            self.malicious.push(party);
+        }
+        self.responsive
+            .retain(|commitment| commitment.party != party);
+        Blame {
+            error,
+            parties: vec![party],
+        }
+    }
+}
@@ -0,0 +1,198 @@
+/*
+    Multisig Schnorr
+
+    Copyright 2018 by Kzen Networks
+
+    This file is part of Multisig Schnorr library
+    (https://github.com/KZen-networks/multisig-schnorr)
+
+    Multisig Schnorr is free software: you can redistribute
+    it and/or modify it under the terms of the GNU General Public
+    License as published by the Free Software Foundation, either
+    version 3 of the License, or (at your option) any later version.
+
+    @license GPL-3.0+ <https://github.com/KZen-networks/multisig-schnorr/blob/master/LICENSE>
+*/
+
+//! Point-to-point encryption of VSS secret shares.
+//!
+//! The sender picks an ephemeral key e and derives the ChaCha20-Poly1305 key from both e*P_r
+//! and s*P_r, where s is the sender's long-term key and P_r the recipient's long-term public
+//! key. Only the recipient can decrypt, and a share only decrypts under the sender key it was
+//! made with. The session id and both party indices go into the key derivation, so a ciphertext
+//! cannot be moved to another session or position.
+use chacha20poly1305::aead::{Aead, NewAead};
+use chacha20poly1305::{ChaCha20Poly1305, Key, Nonce};
+use curv::arithmetic::traits::Converter;
+use curv::elliptic::curves::traits::*;
+use curv::BigInt;
+use protocols::secret::Secret;
+use protocols::transcript::{self, Transcript};
+use Error::{self, InvalidSS};
+
+type GE = curv::elliptic::curves::secp256_k1::GE;
+type FE = curv::elliptic::curves::secp256_k1::FE;
+
+// every key is used for a single message, so a fixed nonce is safe
+const NONCE: [u8; 12] = [0u8; 12];
+
+// long-term key of a party, used only to receive and authenticate shares and to sign the
+// broadcasts of the key generation
+#[derive(Clone, Debug)]
+pub struct EncryptionKey {
+    pub public_key: GE,
+    private_key: Secret<FE>,
+}
+
+impl EncryptionKey {
+    pub fn create() -> EncryptionKey {
+        EncryptionKey::create_from_private_key(ECScalar::new_random())
+    }
+
+    pub fn create_from_private_key(private_key: FE) -> EncryptionKey {
+        EncryptionKey {
+            public_key: GE::generator() * &private_key,
+            private_key: Secret::new(private_key),
+        }
+    }
+
+    pub(crate) fn private_key(&self) -> &FE {
+        self.private_key.expose()
+    }
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct EncryptedShare {
+    pub ephemeral_key: GE,
+    pub ciphertext: Vec<u8>,
+}
+
+impl EncryptedShare {
+    pub fn encrypt(
+        share: &FE,
+        sender_key: &EncryptionKey,
+        recipient_public_key: &GE,
+        session_id: &[u8],
+        sender_index: usize,
+        recipient_index: usize,
+    ) -> EncryptedShare {
+        let ephemeral_private_key: Secret<FE> = Secret::new(ECScalar::new_random());
+        let ephemeral_key = GE::generator() * ephemeral_private_key.expose();
+        let key = derive_key(
+            &(recipient_public_key * ephemeral_private_key.expose()),
+            &(recipient_public_key * sender_key.private_key.expose()),
+            &ephemeral_key,
+            &sender_key.public_key,
+            recipient_public_key,
+            session_id,
+            sender_index,
+            recipient_index,
+        );
+        let plaintext = Secret::new(transcript::to_fixed_bytes(
+            &share.to_big_int(),
+            transcript::SCALAR_BYTES,
+        ));
+        let cipher = ChaCha20Poly1305::new(Key::from_slice(key.expose()));
+        let ciphertext = cipher
+            .encrypt(Nonce::from_slice(&NONCE), &plaintext.expose()[..])
+            .expect("encryption failure");
+        EncryptedShare {
+            ephemeral_key,
+            ciphertext,
+        }
+    }
+
+    pub fn decrypt(
+        &self,
+        recipient_key: &EncryptionKey,
+        sender_public_key: &GE,
+        session_id: &[u8],
+        sender_index: usize,
+        recipient_index: usize,
+    ) -> Result<FE, Error> {
+        let key = derive_key(
+            &(self.ephemeral_key * recipient_key.private_key.expose()),
+            &(sender_public_key * recipient_key.private_key.expose()),
+            &self.ephemeral_key,
+            sender_public_key,
+            &recipient_key.public_key,
+            session_id,
+            sender_index,
+            recipient_index,
+        );
+        let cipher = ChaCha20Poly1305::new(Key::from_slice(key.expose()));
+        let plaintext = cipher
+            .decrypt(Nonce::from_slice(&NONCE), &self.ciphertext[..])
+            .map_err(|_| InvalidSS)?;
+        let plaintext = Secret::new(plaintext);
+        if plaintext.expose().len() != transcript::SCALAR_BYTES {
+            return Err(InvalidSS);
+        }
+        Ok(ECScalar::from(&BigInt::from_bytes(plaintext.expose())))
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+fn derive_key(
+    ephemeral_dh: &GE,
+    static_dh: &GE,
+    ephemeral_key: &GE,
+    sender_public_key: &GE,
+    recipient_public_key: &GE,
+    session_id: &[u8],
+    sender_index: usize,
+    recipient_index: usize,
+) -> Secret<Vec<u8>> {
+    let mut transcript = Transcript::new(b"threshold/share-encryption");
+    transcript.append_message(b"session", session_id);
+    transcript.append_u64(b"sender", sender_index as u64);
+    transcript.append_u64(b"recipient", recipient_index as u64);
+    transcript.append_point(b"E", ephemeral_key);
+    transcript.append_point(b"sender key", sender_public_key);
+    transcript.append_point(b"recipient key", recipient_public_key);
+    transcript.append_point(b"ephemeral dh", ephemeral_dh);
+    transcript.append_point(b"static dh", static_dh);
+    Secret::new(transcript::to_fixed_bytes(
+        &transcript.challenge(),
+        transcript::SCALAR_BYTES,
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_share_round_trip() {
+        let sender = EncryptionKey::create();
+        let recipient = EncryptionKey::create();
+        let share: FE = ECScalar::new_random();
+        let encrypted =
+            EncryptedShare::encrypt(&share, &sender, &recipient.public_key, b"session", 1, 2);
+        let decrypted = encrypted
+            .decrypt(&recipient, &sender.public_key, b"session", 1, 2)
+            .unwrap();
+        assert_eq!(decrypted, share);
+
+        // wrong recipient, wrong claimed sender, wrong context
+        let other = EncryptionKey::create();
+        assert!(encrypted
+            .decrypt(&other, &sender.public_key, b"session", 1, 2)
+            .is_err());
+        assert!(encrypted
+            .decrypt(&recipient, &other.public_key, b"session", 1, 2)
+            .is_err());
+        assert!(encrypted
+            .decrypt(&recipient, &sender.public_key, b"other", 1, 2)
+            .is_err());
+        assert!(encrypted
+            .decrypt(&recipient, &sender.public_key, b"session", 3, 2)
+            .is_err());
+
+        let mut tampered = encrypted.clone();
+        tampered.ciphertext[0] ^= 1;
+        assert!(tampered
+            .decrypt(&recipient, &sender.public_key, b"session", 1, 2)
+            .is_err());
+    }
+}
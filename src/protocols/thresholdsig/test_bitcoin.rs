@@ -19,6 +19,14 @@ use curv::cryptographic_primitives::secret_sharing::feldman_vss::VerifiableSS;
 use curv::elliptic::curves::secp256_k1::FE;
 use curv::elliptic::curves::secp256_k1::GE;
 use protocols::thresholdsig::bitcoin_schnorr::*;
+use protocols::thresholdsig::echo_broadcast::{
+    EchoMessage, COMMITMENTS, DECOMMITMENTS, VSS_COMMITMENTS,
+};
+use protocols::thresholdsig::quorum::SigningQuorum;
+use protocols::thresholdsig::share_encryption::{EncryptedShare, EncryptionKey};
+use protocols::thresholdsig::test_zilliqa::echo_message;
+
+const SESSION_ID: &[u8] = b"test-keygen";
 
 #[test]
 #[allow(unused_doc_comments)]
@@ -53,7 +61,9 @@ fn test_t2_n4() {
 
     assert!(verify_local_sig.is_ok());
     let vss_sum_local_sigs = verify_local_sig.unwrap();
-    let signature = Signature::generate(&vss_sum_local_sigs, &local_sig_vec, &parties_index_vec, V);
+    let quorum = SigningQuorum::new(t, n, &parties_index_vec).unwrap();
+    let signature = Signature::generate(&vss_sum_local_sigs, &local_sig_vec, &quorum, V)
+        .expect("invalid local signatures");
     let verify_sig = signature.verify(&message, &Y);
     assert!(verify_sig.is_ok());
 }
@@ -82,7 +92,7 @@ fn test_t2_n5_sign_with_4() {
     let message: [u8; 4] = [79, 77, 69, 82];
 
     // each party computes and share a local sig, we collected them here to a vector as each party should do AFTER receiving all local sigs
-    let local_sig_vec = (0..num_parties.clone())
+    let mut local_sig_vec = (0..num_parties.clone())
         .map(|i| {
             LocalSig::compute(
                 &message,
@@ -103,9 +113,397 @@ fn test_t2_n5_sign_with_4() {
     let vss_sum_local_sigs = verify_local_sig.unwrap();
 
     /// each party / dealer can generate the signature
-    let signature = Signature::generate(&vss_sum_local_sigs, &local_sig_vec, &parties_index_vec, V);
+    let quorum = SigningQuorum::new(t, n, &parties_index_vec).unwrap();
+    let signature = Signature::generate(&vss_sum_local_sigs, &local_sig_vec, &quorum, V)
+        .expect("invalid local signatures");
     let verify_sig = signature.verify(&message, &Y);
     assert!(verify_sig.is_ok());
+
+    // a local signature made with the share of a party outside the quorum is blamed
+    local_sig_vec[1] =
+        LocalSig::compute(&message, &eph_shared_keys_vec[1], &priv_shared_keys_vec[2]);
+    let blame = Signature::generate(&vss_sum_local_sigs, &local_sig_vec, &quorum, V).unwrap_err();
+    assert_eq!(blame.parties, vec![1]);
+}
+
+#[test]
+fn test_message_with_leading_zero_byte() {
+    let t = 1;
+    let n = 3;
+    let parties_index_vec: [usize; 3] = [0, 1, 2];
+    let parties_points_vec = vec![1, 2, 3];
+    let (_priv_keys_vec, priv_shared_keys_vec, Y, key_gen_vss_vec) =
+        keygen_t_n_parties(t, n, &parties_points_vec);
+    let (_eph_keys_vec, eph_shared_keys_vec, V, eph_vss_vec) =
+        keygen_t_n_parties(t, n, &parties_points_vec);
+    let message = [0x00, 0x01];
+    let local_sig_vec = (0..n)
+        .map(|i| LocalSig::compute(&message, &eph_shared_keys_vec[i], &priv_shared_keys_vec[i]))
+        .collect::<Vec<LocalSig>>();
+    let vss_sum_local_sigs = LocalSig::verify_local_sigs(
+        &local_sig_vec,
+        &parties_index_vec,
+        &key_gen_vss_vec,
+        &eph_vss_vec,
+    )
+    .unwrap();
+    let quorum = SigningQuorum::new(t, n, &parties_index_vec).unwrap();
+    let signature = Signature::generate(&vss_sum_local_sigs, &local_sig_vec, &quorum, V)
+        .expect("invalid local signatures");
+    assert!(signature.verify(&message, &Y).is_ok());
+    // the same integer, but a different message
+    assert!(signature.verify(&[0x01], &Y).is_err());
+    assert!(signature.verify_legacy(&message, &Y).is_err());
+}
+
+#[test]
+fn test_keygen_blames_party_without_key_proof() {
+    use protocols::thresholdsig::key_proof::KeyProof;
+
+    let params = Parameters {
+        threshold: 1,
+        share_count: 3,
+    };
+    let parties = [1, 2, 3];
+    let party_keys_vec = parties
+        .iter()
+        .map(|i| Keys::phase1_create(*i))
+        .collect::<Vec<Keys>>();
+    let encryption_key_vec = (0..3)
+        .map(|_| EncryptionKey::create())
+        .collect::<Vec<EncryptionKey>>();
+    let encryption_public_key_vec = encryption_key_vec
+        .iter()
+        .map(|key| key.public_key)
+        .collect::<Vec<GE>>();
+    let mut bc1_vec = Vec::new();
+    let mut blind_vec = Vec::new();
+    let mut proof_vec = Vec::new();
+    for keys in party_keys_vec.iter() {
+        let (bc1, blind, proof) = keys.phase1_broadcast(SESSION_ID);
+        bc1_vec.push(bc1);
+        blind_vec.push(blind);
+        proof_vec.push(proof);
+    }
+    let y_vec = party_keys_vec
+        .iter()
+        .map(|keys| keys.y_i)
+        .collect::<Vec<GE>>();
+    let com_echo = echo_message(
+        SESSION_ID,
+        COMMITMENTS,
+        &bc1_vec,
+        &encryption_key_vec,
+        &parties,
+    );
+    // the decommitment round is echoed with whatever proofs the parties sent
+    let verify = |proof_vec: &Vec<KeyProof>| {
+        let decom_echo = echo_message(
+            SESSION_ID,
+            DECOMMITMENTS,
+            &decommitments(&y_vec, &blind_vec, proof_vec),
+            &encryption_key_vec,
+            &parties,
+        );
+        party_keys_vec[0].phase1_verify_com_phase2_distribute(
+            &params,
+            SESSION_ID,
+            &blind_vec,
+            &y_vec,
+            proof_vec,
+            &bc1_vec,
+            &vec![com_echo.clone(); 3],
+            &vec![decom_echo; 3],
+            &encryption_public_key_vec,
+            &parties,
+        )
+    };
+
+    // party 2 decommits correctly but replays a proof made for another session
+    proof_vec[1] = KeyProof::prove(
+        party_keys_vec[1].u_i.expose(),
+        &y_vec[1],
+        b"other-session",
+        party_keys_vec[1].party_index,
+    );
+    let blame = verify(&proof_vec).unwrap_err();
+    assert_eq!(blame.parties, vec![1]);
+
+    // or a proof bound to another party index
+    proof_vec[1] = KeyProof::prove(
+        party_keys_vec[1].u_i.expose(),
+        &y_vec[1],
+        SESSION_ID,
+        party_keys_vec[2].party_index,
+    );
+    let blame = verify(&proof_vec).unwrap_err();
+    assert_eq!(blame.parties, vec![1]);
+
+    // with its own proof the same commitment is accepted
+    proof_vec[1] = KeyProof::prove(
+        party_keys_vec[1].u_i.expose(),
+        &y_vec[1],
+        SESSION_ID,
+        party_keys_vec[1].party_index,
+    );
+    assert!(verify(&proof_vec).is_ok());
+}
+
+#[test]
+fn test_keygen_echo_blames_equivocating_party() {
+    let params = Parameters {
+        threshold: 1,
+        share_count: 3,
+    };
+    let parties = [1, 2, 3];
+    let party_keys_vec = parties
+        .iter()
+        .map(|i| Keys::phase1_create(*i))
+        .collect::<Vec<Keys>>();
+    let encryption_key_vec = (0..3)
+        .map(|_| EncryptionKey::create())
+        .collect::<Vec<EncryptionKey>>();
+    let encryption_public_key_vec = encryption_key_vec
+        .iter()
+        .map(|key| key.public_key)
+        .collect::<Vec<GE>>();
+    let mut bc1_vec = Vec::new();
+    let mut blind_vec = Vec::new();
+    let mut proof_vec = Vec::new();
+    for keys in party_keys_vec.iter() {
+        let (bc1, blind, proof) = keys.phase1_broadcast(SESSION_ID);
+        bc1_vec.push(bc1);
+        blind_vec.push(blind);
+        proof_vec.push(proof);
+    }
+    let y_vec = party_keys_vec
+        .iter()
+        .map(|keys| keys.y_i)
+        .collect::<Vec<GE>>();
+    let decom_echo = echo_message(
+        SESSION_ID,
+        DECOMMITMENTS,
+        &decommitments(&y_vec, &blind_vec, &proof_vec),
+        &encryption_key_vec,
+        &parties,
+    );
+
+    // party 3 signs a commitment to a different key towards party 1
+    let mut received_vec = vec![bc1_vec.clone(); 3];
+    received_vec[0][2] = Keys::phase1_create(3).phase1_broadcast(SESSION_ID).0;
+    let com_echo_vec = received_vec
+        .iter()
+        .map(|received| {
+            echo_message(
+                SESSION_ID,
+                COMMITMENTS,
+                received,
+                &encryption_key_vec,
+                &parties,
+            )
+        })
+        .collect::<Vec<EchoMessage>>();
+    for i in 0..3 {
+        let blame = party_keys_vec[i]
+            .phase1_verify_com_phase2_distribute(
+                &params,
+                SESSION_ID,
+                &blind_vec,
+                &y_vec,
+                &proof_vec,
+                &received_vec[i],
+                &com_echo_vec,
+                &vec![decom_echo.clone(); 3],
+                &encryption_public_key_vec,
+                &parties,
+            )
+            .unwrap_err();
+        assert_eq!(blame.parties, vec![2]);
+    }
+}
+
+#[test]
+fn test_aggregator_outputs_bitcoin_signature() {
+    use protocols::thresholdsig::key_package::PublicKeyPackage;
+
+    // the collecting and blaming is shared with zilliqa_schnorr, only the output differs
+    let t = 1;
+    let n = 3;
+    let points = [1, 2, 3];
+    let (_priv_keys_vec, priv_shared_keys_vec, Y, key_gen_vss_vec) =
+        keygen_t_n_parties(t, n, &points);
+    let (_eph_keys_vec, eph_shared_keys_vec, V, eph_vss_vec) = keygen_t_n_parties(t, n, &points);
+    let key_package = PublicKeyPackage::from_vss(&Y, &key_gen_vss_vec, n).unwrap();
+    let eph_package = PublicKeyPackage::from_vss(&V, &eph_vss_vec, n).unwrap();
+    let message: [u8; 4] = [79, 77, 69, 82];
+    let mut aggregator = Signature::aggregator(&message, &key_package, &eph_package).unwrap();
+
+    let local_sig = LocalSig::compute(&message, &eph_shared_keys_vec[2], &priv_shared_keys_vec[2]);
+    assert!(aggregator.add(2, local_sig).unwrap().is_none());
+    let local_sig = LocalSig::compute(&message, &eph_shared_keys_vec[0], &priv_shared_keys_vec[0]);
+    let signature = aggregator.add(0, local_sig).unwrap().unwrap();
+    assert_eq!(signature.v, V);
+    assert!(signature.verify(&message, &Y).is_ok());
+}
+
+#[test]
+fn test_musig_key_converted_to_threshold_keeps_apk() {
+    use protocols::aggsig::musig_three_rounds::{self, KeyAgg, KeyPair};
+    use protocols::thresholdsig::key_package::PublicKeyPackage;
+    use protocols::thresholdsig::musig_conversion::*;
+
+    // an existing 3-of-3 MuSig key
+    let musig_keys = (0..3).map(|_| KeyPair::create()).collect::<Vec<KeyPair>>();
+    let pks = musig_keys
+        .iter()
+        .map(|keys| keys.public_key)
+        .collect::<Vec<GE>>();
+    let apk = KeyAgg::key_aggregation_n(&pks, 0).apk;
+
+    // handed over to a 2-of-4 committee
+    let t = 1;
+    let n = 4;
+    let (vss_scheme_vec, secret_shares_vec): (Vec<VerifiableSS<GE>>, Vec<Vec<FE>>) = musig_keys
+        .iter()
+        .enumerate()
+        .map(|(i, keys)| keys.share_weighted_key(&KeyAgg::key_aggregation_n(&pks, i).hash, t, n))
+        .unzip();
+    let committee_keys_vec = (0..n)
+        .map(|j| {
+            let shares = secret_shares_vec
+                .iter()
+                .map(|shares| shares[j])
+                .collect::<Vec<FE>>();
+            construct_keypair(&pks, &vss_scheme_vec, &shares, j).unwrap()
+        })
+        .collect::<Vec<SharedKeys>>();
+    assert!(committee_keys_vec.iter().all(|keys| keys.y == apk));
+    let key_package = PublicKeyPackage::from_vss(&apk, &vss_scheme_vec, n).unwrap();
+
+    let quorum = SigningQuorum::new(t, n, &[1, 3]).unwrap();
+    let (_eph_keys_vec, eph_shared_keys_vec, V, eph_vss_vec) =
+        keygen_t_n_parties(t, quorum.parties.len(), &quorum.points());
+    let eph_package = PublicKeyPackage::from_vss(&V, &eph_vss_vec, n).unwrap();
+    let message: [u8; 4] = [79, 77, 69, 82];
+    let mut local_sig_vec = (0..quorum.parties.len())
+        .map(|k| {
+            MusigLocalSig::compute(
+                &message,
+                &eph_shared_keys_vec[k],
+                &committee_keys_vec[quorum.parties[k]],
+            )
+        })
+        .collect::<Vec<MusigLocalSig>>();
+    let (r_x, s) = generate(
+        &message,
+        &local_sig_vec,
+        &quorum,
+        &key_package,
+        &eph_package,
+    )
+    .unwrap();
+    assert!(musig_three_rounds::verify(&s, &r_x, &apk, &message, true).is_ok());
+
+    // a share that is not a_i * x_i is caught by the committee
+    let mut bad_vss_scheme_vec = vss_scheme_vec.clone();
+    bad_vss_scheme_vec.swap(0, 1);
+    let shares = secret_shares_vec
+        .iter()
+        .map(|shares| shares[0])
+        .collect::<Vec<FE>>();
+    let blame = construct_keypair(&pks, &bad_vss_scheme_vec, &shares, 0).unwrap_err();
+    assert_eq!(blame.parties, vec![0, 1]);
+
+    local_sig_vec[0] = local_sig_vec[1].clone();
+    assert!(generate(
+        &message,
+        &local_sig_vec,
+        &quorum,
+        &key_package,
+        &eph_package
+    )
+    .is_err());
+}
+
+#[test]
+fn test_keygen_blames_share_that_does_not_decrypt() {
+    let parties = [1, 2, 3];
+    let party_keys_vec = parties
+        .iter()
+        .map(|i| Keys::phase1_create(*i))
+        .collect::<Vec<Keys>>();
+    let encryption_key_vec = (0..3)
+        .map(|_| EncryptionKey::create())
+        .collect::<Vec<EncryptionKey>>();
+    let encryption_public_key_vec = encryption_key_vec
+        .iter()
+        .map(|key| key.public_key)
+        .collect::<Vec<GE>>();
+
+    let params = Parameters {
+        threshold: 1,
+        share_count: 3,
+    };
+    let y_vec = party_keys_vec
+        .iter()
+        .map(|keys| keys.y_i)
+        .collect::<Vec<GE>>();
+
+    // the shares every party sends to party 1
+    let mut vss_scheme_vec = Vec::new();
+    let mut encrypted_share_vec = Vec::new();
+    for i in 0..3 {
+        let (vss_scheme, secret_shares) =
+            VerifiableSS::share_at_indices(1, 3, party_keys_vec[i].u_i.expose(), &parties);
+        vss_scheme_vec.push(vss_scheme);
+        encrypted_share_vec.push(
+            party_keys_vec[i]
+                .phase2_encrypt_shares(
+                    SESSION_ID,
+                    &secret_shares,
+                    &encryption_key_vec[i],
+                    &encryption_public_key_vec,
+                    &parties,
+                )
+                .remove(0),
+        );
+    }
+    let vss_echo = echo_message(
+        SESSION_ID,
+        VSS_COMMITMENTS,
+        &vss_scheme_vec,
+        &encryption_key_vec,
+        &parties,
+    );
+    assert!(party_keys_vec[0]
+        .phase2_verify_vss_construct_keypair(
+            &params,
+            SESSION_ID,
+            &y_vec,
+            &encrypted_share_vec,
+            &vss_scheme_vec,
+            &vec![vss_echo.clone(); 3],
+            &encryption_key_vec[0],
+            &encryption_public_key_vec,
+            &parties,
+        )
+        .is_ok());
+
+    encrypted_share_vec[2].ciphertext[0] ^= 1;
+    let blame = party_keys_vec[0]
+        .phase2_verify_vss_construct_keypair(
+            &params,
+            SESSION_ID,
+            &y_vec,
+            &encrypted_share_vec,
+            &vss_scheme_vec,
+            &vec![vss_echo.clone(); 3],
+            &encryption_key_vec[0],
+            &encryption_public_key_vec,
+            &parties,
+        )
+        .unwrap_err();
+    assert_eq!(blame.parties, vec![2]);
 }
 
 #[allow(dead_code)]
@@ -125,10 +523,12 @@ pub fn keygen_t_n_parties(
 
     let mut bc1_vec = Vec::new();
     let mut blind_vec = Vec::new();
+    let mut proof_vec = Vec::new();
     for i in 0..n.clone() {
-        let (bc1, blind) = party_keys_vec[i].phase1_broadcast();
+        let (bc1, blind, proof) = party_keys_vec[i].phase1_broadcast(SESSION_ID);
         bc1_vec.push(bc1);
         blind_vec.push(blind);
+        proof_vec.push(proof);
     }
 
     let y_vec = (0..n.clone())
@@ -138,38 +538,82 @@ pub fn keygen_t_n_parties(
     let head = y_vec_iter.next().unwrap();
     let tail = y_vec_iter;
     let y_sum = tail.fold(head.clone(), |acc, x| acc + x);
+    // long-term keys used to deliver the secret shares and to sign the broadcasts
+    let encryption_key_vec = (0..n)
+        .map(|_| EncryptionKey::create())
+        .collect::<Vec<EncryptionKey>>();
+    let encryption_public_key_vec = encryption_key_vec
+        .iter()
+        .map(|key| key.public_key)
+        .collect::<Vec<GE>>();
+
+    // echo rounds: here every party received the same broadcasts
+    let com_echo = echo_message(
+        SESSION_ID,
+        COMMITMENTS,
+        &bc1_vec,
+        &encryption_key_vec,
+        parties,
+    );
+    let decom_echo = echo_message(
+        SESSION_ID,
+        DECOMMITMENTS,
+        &decommitments(&y_vec, &blind_vec, &proof_vec),
+        &encryption_key_vec,
+        parties,
+    );
     let mut vss_scheme_vec = Vec::new();
-    let mut secret_shares_vec = Vec::new();
-    let mut index_vec = Vec::new();
+    let mut encrypted_shares_vec = Vec::new();
     for i in 0..n.clone() {
-        let (vss_scheme, secret_shares, index) = party_keys_vec[i]
-            .phase1_verify_com_phase2_distribute(&parames, &blind_vec, &y_vec, &bc1_vec, parties)
+        let (vss_scheme, secret_shares, _index) = party_keys_vec[i]
+            .phase1_verify_com_phase2_distribute(
+                &parames,
+                SESSION_ID,
+                &blind_vec,
+                &y_vec,
+                &proof_vec,
+                &bc1_vec,
+                &vec![com_echo.clone(); n],
+                &vec![decom_echo.clone(); n],
+                &encryption_public_key_vec,
+                parties,
+            )
             .expect("invalid key");
         vss_scheme_vec.push(vss_scheme);
-        secret_shares_vec.push(secret_shares);
-        index_vec.push(index);
+        encrypted_shares_vec.push(party_keys_vec[i].phase2_encrypt_shares(
+            SESSION_ID,
+            &secret_shares,
+            &encryption_key_vec[i],
+            &encryption_public_key_vec,
+            parties,
+        ));
     }
 
-    let party_shares = (0..n.clone())
-        .map(|i| {
-            (0..n.clone())
-                .map(|j| {
-                    let vec_j = &secret_shares_vec[j];
-                    vec_j[i].clone()
-                })
-                .collect::<Vec<FE>>()
-        })
-        .collect::<Vec<Vec<FE>>>();
+    let vss_echo = echo_message(
+        SESSION_ID,
+        VSS_COMMITMENTS,
+        &vss_scheme_vec,
+        &encryption_key_vec,
+        parties,
+    );
 
+    // party i receives the i-th share of every party
     let mut shared_keys_vec = Vec::new();
     for i in 0..n.clone() {
+        let encrypted_share_vec = (0..n.clone())
+            .map(|j| encrypted_shares_vec[j][i].clone())
+            .collect::<Vec<EncryptedShare>>();
         let shared_keys = party_keys_vec[i]
             .phase2_verify_vss_construct_keypair(
                 &parames,
+                SESSION_ID,
                 &y_vec,
-                &party_shares[i],
+                &encrypted_share_vec,
                 &vss_scheme_vec,
-                &index_vec[i],
+                &vec![vss_echo.clone(); n],
+                &encryption_key_vec[i],
+                &encryption_public_key_vec,
+                parties,
             )
             .expect("invalid vss");
         shared_keys_vec.push(shared_keys);
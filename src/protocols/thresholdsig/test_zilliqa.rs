@@ -15,10 +15,18 @@
     @license GPL-3.0+ <https://github.com/KZen-networks/multisig-schnorr/blob/master/LICENSE>
 */
 use curv::cryptographic_primitives::secret_sharing::feldman_vss::VerifiableSS;
+use protocols::thresholdsig::echo_broadcast::{
+    verify_echoes, EchoMessage, SignedDigest, COMMITMENTS, DECOMMITMENTS, VSS_COMMITMENTS,
+};
+use protocols::thresholdsig::hierarchical;
+use protocols::thresholdsig::quorum::SigningQuorum;
+use protocols::thresholdsig::share_encryption::EncryptionKey;
 use protocols::thresholdsig::zilliqa_schnorr::*;
+use serde::Serialize;
 
-use curv::elliptic::curves::secp256_k1::FE;
-use curv::elliptic::curves::secp256_k1::GE;
+use curv::elliptic::curves::secp256_k1::{FE, GE};
+
+const SESSION_ID: &[u8] = b"test-keygen";
 
 #[test]
 #[allow(unused_doc_comments)]
@@ -53,14 +61,16 @@ fn test_t2_n4() {
 
     assert!(verify_local_sig.is_ok());
     let vss_sum_local_sigs = verify_local_sig.unwrap();
+    let quorum = SigningQuorum::new(t, n, &parties_index_vec).unwrap();
     let signature = Signature::generate(
         &vss_sum_local_sigs,
         &local_sig_vec,
-        &parties_index_vec,
+        &quorum,
         &V,
         &Y,
         &message,
-    );
+    )
+    .expect("invalid local signatures");
     let verify_sig = signature.verify(&message, &Y);
     assert!(verify_sig.is_ok());
 }
@@ -89,7 +99,7 @@ fn test_t2_n5_sign_with_4() {
     let message: [u8; 4] = [79, 77, 69, 82];
 
     // each party computes and share a local sig, we collected them here to a vector as each party should do AFTER receiving all local sigs
-    let local_sig_vec = (0..num_parties.clone())
+    let mut local_sig_vec = (0..num_parties.clone())
         .map(|i| {
             LocalSig::compute(
                 &message,
@@ -110,16 +120,1232 @@ fn test_t2_n5_sign_with_4() {
     let vss_sum_local_sigs = verify_local_sig.unwrap();
 
     /// each party / dealer can generate the signature
+    let quorum = SigningQuorum::new(t, n, &parties_index_vec).unwrap();
     let signature = Signature::generate(
         &vss_sum_local_sigs,
         &local_sig_vec,
-        &parties_index_vec,
+        &quorum,
         &V,
         &Y,
         &message,
-    );
+    )
+    .expect("invalid local signatures");
     let verify_sig = signature.verify(&message, &Y);
     assert!(verify_sig.is_ok());
+
+    // a local signature made with the share of a party outside the quorum is blamed
+    local_sig_vec[1] =
+        LocalSig::compute(&message, &eph_shared_keys_vec[1], &priv_shared_keys_vec[2]);
+    let blame = Signature::generate(
+        &vss_sum_local_sigs,
+        &local_sig_vec,
+        &quorum,
+        &V,
+        &Y,
+        &message,
+    )
+    .unwrap_err();
+    assert_eq!(blame.parties, vec![1]);
+}
+
+#[test]
+fn test_message_with_leading_zero_byte() {
+    let t = 1;
+    let n = 3;
+    let parties_index_vec: [usize; 3] = [0, 1, 2];
+    let parties_points_vec = vec![1, 2, 3];
+    let (_priv_keys_vec, priv_shared_keys_vec, Y, key_gen_vss_vec) =
+        keygen_t_n_parties(t, n, &parties_points_vec);
+    let (_eph_keys_vec, eph_shared_keys_vec, V, eph_vss_vec) =
+        keygen_t_n_parties(t, n, &parties_points_vec);
+    let message = [0x00, 0x01];
+    let local_sig_vec = (0..n)
+        .map(|i| LocalSig::compute(&message, &eph_shared_keys_vec[i], &priv_shared_keys_vec[i]))
+        .collect::<Vec<LocalSig>>();
+    let vss_sum_local_sigs = LocalSig::verify_local_sigs(
+        &local_sig_vec,
+        &parties_index_vec,
+        &key_gen_vss_vec,
+        &eph_vss_vec,
+    )
+    .unwrap();
+    let quorum = SigningQuorum::new(t, n, &parties_index_vec).unwrap();
+    let signature = Signature::generate(
+        &vss_sum_local_sigs,
+        &local_sig_vec,
+        &quorum,
+        &V,
+        &Y,
+        &message,
+    )
+    .expect("invalid local signatures");
+    assert!(signature.verify(&message, &Y).is_ok());
+    // the same integer, but a different message
+    assert!(signature.verify(&[0x01], &Y).is_err());
+    assert!(signature.verify_legacy(&message, &Y).is_err());
+}
+
+#[test]
+fn test_keygen_blames_party_without_key_proof() {
+    use protocols::thresholdsig::key_proof::KeyProof;
+
+    let params = Parameters {
+        threshold: 1,
+        share_count: 3,
+    };
+    let parties = [1, 2, 3];
+    let party_keys_vec = parties
+        .iter()
+        .map(|i| Keys::phase1_create(*i))
+        .collect::<Vec<Keys>>();
+    let encryption_key_vec = (0..3)
+        .map(|_| EncryptionKey::create())
+        .collect::<Vec<EncryptionKey>>();
+    let encryption_public_key_vec = encryption_key_vec
+        .iter()
+        .map(|key| key.public_key)
+        .collect::<Vec<GE>>();
+    let (bc1_vec, mut decom1_vec): (Vec<_>, Vec<_>) = party_keys_vec
+        .iter()
+        .map(|keys| keys.phase1_broadcast(SESSION_ID))
+        .unzip();
+
+    // party 3 replays a proof made for another party index
+    decom1_vec[2].proof = KeyProof::prove(
+        party_keys_vec[2].u_i.expose(),
+        &party_keys_vec[2].y_i,
+        SESSION_ID,
+        1,
+    );
+    let com_echo = echo_message(
+        SESSION_ID,
+        COMMITMENTS,
+        &bc1_vec,
+        &encryption_key_vec,
+        &parties,
+    );
+    let decom_echo = echo_message(
+        SESSION_ID,
+        DECOMMITMENTS,
+        &decom1_vec,
+        &encryption_key_vec,
+        &parties,
+    );
+    let blame = party_keys_vec[0]
+        .phase1_verify_com_phase2_distribute(
+            &params,
+            SESSION_ID,
+            &decom1_vec,
+            &bc1_vec,
+            &vec![com_echo; 3],
+            &vec![decom_echo; 3],
+            &encryption_public_key_vec,
+            &parties,
+        )
+        .unwrap_err();
+    assert_eq!(blame.parties, vec![2]);
+
+    // proofs from another key generation session are rejected for everyone
+    let com_echo = echo_message(
+        b"other",
+        COMMITMENTS,
+        &bc1_vec,
+        &encryption_key_vec,
+        &parties,
+    );
+    let decom_echo = echo_message(
+        b"other",
+        DECOMMITMENTS,
+        &decom1_vec,
+        &encryption_key_vec,
+        &parties,
+    );
+    let blame = party_keys_vec[0]
+        .phase1_verify_com_phase2_distribute(
+            &params,
+            b"other",
+            &decom1_vec,
+            &bc1_vec,
+            &vec![com_echo; 3],
+            &vec![decom_echo; 3],
+            &encryption_public_key_vec,
+            &parties,
+        )
+        .unwrap_err();
+    assert_eq!(blame.parties, vec![0, 1, 2]);
+}
+
+#[test]
+fn test_keygen_blames_undecryptable_share() {
+    let params = Parameters {
+        threshold: 1,
+        share_count: 3,
+    };
+    let parties = [1, 2, 3];
+    let party_keys_vec = parties
+        .iter()
+        .map(|i| Keys::phase1_create(*i))
+        .collect::<Vec<Keys>>();
+    let encryption_key_vec = (0..3)
+        .map(|_| EncryptionKey::create())
+        .collect::<Vec<EncryptionKey>>();
+    let encryption_public_key_vec = encryption_key_vec
+        .iter()
+        .map(|key| key.public_key)
+        .collect::<Vec<GE>>();
+    let (bc1_vec, decom1_vec): (Vec<_>, Vec<_>) = party_keys_vec
+        .iter()
+        .map(|keys| keys.phase1_broadcast(SESSION_ID))
+        .unzip();
+    let y_vec = decom1_vec
+        .iter()
+        .map(|decom| decom.y_i)
+        .collect::<Vec<GE>>();
+    let com_echo = echo_message(
+        SESSION_ID,
+        COMMITMENTS,
+        &bc1_vec,
+        &encryption_key_vec,
+        &parties,
+    );
+    let decom_echo = echo_message(
+        SESSION_ID,
+        DECOMMITMENTS,
+        &decom1_vec,
+        &encryption_key_vec,
+        &parties,
+    );
+
+    // the messages every party sends to party 1
+    let mut msg3_vec = (0..3)
+        .map(|i| {
+            let (vss_scheme, secret_shares, _) = party_keys_vec[i]
+                .phase1_verify_com_phase2_distribute(
+                    &params,
+                    SESSION_ID,
+                    &decom1_vec,
+                    &bc1_vec,
+                    &vec![com_echo.clone(); 3],
+                    &vec![decom_echo.clone(); 3],
+                    &encryption_public_key_vec,
+                    &parties,
+                )
+                .unwrap();
+            party_keys_vec[i]
+                .phase2_encrypt_shares(
+                    SESSION_ID,
+                    &vss_scheme,
+                    &secret_shares,
+                    &encryption_key_vec[i],
+                    &encryption_public_key_vec,
+                    &parties,
+                )
+                .remove(0)
+        })
+        .collect::<Vec<KeyGenMessage3>>();
+    let vss_scheme_vec = msg3_vec
+        .iter()
+        .map(|msg3| msg3.vss_scheme.clone())
+        .collect::<Vec<VerifiableSS<GE>>>();
+    let vss_echo_vec = vec![
+        echo_message(
+            SESSION_ID,
+            VSS_COMMITMENTS,
+            &vss_scheme_vec,
+            &encryption_key_vec,
+            &parties,
+        );
+        3
+    ];
+    assert!(party_keys_vec[0]
+        .phase2_verify_vss_construct_keypair(
+            &params,
+            SESSION_ID,
+            &y_vec,
+            &msg3_vec,
+            &vss_echo_vec,
+            &encryption_key_vec[0],
+            &encryption_public_key_vec,
+            &parties,
+        )
+        .is_ok());
+
+    // party 3 sends a share that does not decrypt, only party 3 is blamed
+    msg3_vec[2].encrypted_share.ciphertext[0] ^= 1;
+    let blame = party_keys_vec[0]
+        .phase2_verify_vss_construct_keypair(
+            &params,
+            SESSION_ID,
+            &y_vec,
+            &msg3_vec,
+            &vss_echo_vec,
+            &encryption_key_vec[0],
+            &encryption_public_key_vec,
+            &parties,
+        )
+        .unwrap_err();
+    assert_eq!(blame.parties, vec![2]);
+
+    // a share intended for party 2 cannot be decrypted by party 1
+    assert!(party_keys_vec[0]
+        .phase2_verify_vss_construct_keypair(
+            &params,
+            SESSION_ID,
+            &y_vec,
+            &msg3_vec,
+            &vss_echo_vec,
+            &encryption_key_vec[1],
+            &encryption_public_key_vec,
+            &parties,
+        )
+        .is_err());
+
+    // party 2 claims to be party 3: the share would decrypt, but the sender index is checked
+    msg3_vec[2].encrypted_share.ciphertext[0] ^= 1;
+    msg3_vec[1].sender_index = parties[2];
+    let blame = party_keys_vec[0]
+        .phase2_verify_vss_construct_keypair(
+            &params,
+            SESSION_ID,
+            &y_vec,
+            &msg3_vec,
+            &vss_echo_vec,
+            &encryption_key_vec[0],
+            &encryption_public_key_vec,
+            &parties,
+        )
+        .unwrap_err();
+    assert_eq!(blame.parties, vec![1]);
+}
+
+#[test]
+fn test_keygen_echo_detects_equivocation() {
+    let params = Parameters {
+        threshold: 1,
+        share_count: 4,
+    };
+    let parties = [1, 2, 3, 4];
+    let party_keys_vec = parties
+        .iter()
+        .map(|i| Keys::phase1_create(*i))
+        .collect::<Vec<Keys>>();
+    let encryption_key_vec = (0..4)
+        .map(|_| EncryptionKey::create())
+        .collect::<Vec<EncryptionKey>>();
+    let encryption_public_key_vec = encryption_key_vec
+        .iter()
+        .map(|key| key.public_key)
+        .collect::<Vec<GE>>();
+    let (bc1_vec, decom1_vec): (Vec<_>, Vec<_>) = party_keys_vec
+        .iter()
+        .map(|keys| keys.phase1_broadcast(SESSION_ID))
+        .unzip();
+    let decom_echo = echo_message(
+        SESSION_ID,
+        DECOMMITMENTS,
+        &decom1_vec,
+        &encryption_key_vec,
+        &parties,
+    );
+
+    // party 3 signs a commitment to a different key towards party 1
+    let mut received_vec = vec![bc1_vec.clone(); 4];
+    received_vec[0][2] = Keys::phase1_create(3).phase1_broadcast(SESSION_ID).0;
+    let echo_vec = received_vec
+        .iter()
+        .map(|received| {
+            echo_message(
+                SESSION_ID,
+                COMMITMENTS,
+                received,
+                &encryption_key_vec,
+                &parties,
+            )
+        })
+        .collect::<Vec<EchoMessage>>();
+
+    // every party aborts and blames party 3, whose two signed digests differ
+    for i in 0..4 {
+        let blame = party_keys_vec[i]
+            .phase1_verify_com_phase2_distribute(
+                &params,
+                SESSION_ID,
+                &decom1_vec,
+                &received_vec[i],
+                &echo_vec,
+                &vec![decom_echo.clone(); 4],
+                &encryption_public_key_vec,
+                &parties,
+            )
+            .unwrap_err();
+        assert_eq!(blame.parties, vec![2]);
+    }
+
+    // a commitment that does not match its signed digest is blamed on its sender
+    let signed_vec = (0..4)
+        .map(|j| {
+            SignedDigest::sign(
+                SESSION_ID,
+                COMMITMENTS,
+                parties[j],
+                &bc1_vec[j],
+                &encryption_key_vec[j],
+            )
+        })
+        .collect::<Vec<SignedDigest>>();
+    let blame = EchoMessage::create(
+        SESSION_ID,
+        COMMITMENTS,
+        &received_vec[0],
+        &signed_vec,
+        &encryption_public_key_vec,
+        &parties,
+    )
+    .unwrap_err();
+    assert_eq!(blame.parties, vec![2]);
+
+    // party 4 cannot frame party 3: a digest party 3 did not sign is blamed on the echo
+    let mut echo_vec = vec![
+        echo_message(
+            SESSION_ID,
+            COMMITMENTS,
+            &bc1_vec,
+            &encryption_key_vec,
+            &parties,
+        );
+        4
+    ];
+    echo_vec[3].digests[2].digest = echo_vec[3].digests[1].digest.clone();
+    let blame = verify_echoes(
+        SESSION_ID,
+        COMMITMENTS,
+        &bc1_vec,
+        &echo_vec,
+        &encryption_public_key_vec,
+        &parties,
+    )
+    .unwrap_err();
+    assert_eq!(blame.parties, vec![3]);
+
+    // and a malformed echo is blamed on the party that sent it
+    echo_vec[3] = echo_vec[0].clone();
+    echo_vec[3].digests.pop();
+    let blame = verify_echoes(
+        SESSION_ID,
+        COMMITMENTS,
+        &bc1_vec,
+        &echo_vec,
+        &encryption_public_key_vec,
+        &parties,
+    )
+    .unwrap_err();
+    assert_eq!(blame.parties, vec![3]);
+
+    // the VSS commitments sent with the shares are checked the same way
+    let vss_scheme_vec = party_keys_vec
+        .iter()
+        .map(|keys| VerifiableSS::share(1, 4, keys.u_i.expose()).0)
+        .collect::<Vec<VerifiableSS<GE>>>();
+    let mut received_vec = vec![vss_scheme_vec.clone(); 4];
+    received_vec[3][1] = VerifiableSS::share(1, 4, party_keys_vec[1].u_i.expose()).0;
+    let echo_vec = received_vec
+        .iter()
+        .map(|received| {
+            echo_message(
+                SESSION_ID,
+                VSS_COMMITMENTS,
+                received,
+                &encryption_key_vec,
+                &parties,
+            )
+        })
+        .collect::<Vec<EchoMessage>>();
+    for received in received_vec.iter() {
+        let blame = verify_echoes(
+            SESSION_ID,
+            VSS_COMMITMENTS,
+            received,
+            &echo_vec,
+            &encryption_public_key_vec,
+            &parties,
+        )
+        .unwrap_err();
+        assert_eq!(blame.parties, vec![1]);
+    }
+}
+
+#[test]
+fn test_sign_with_derived_child_key() {
+    use protocols::thresholdsig::derivation::*;
+    use Error::InvalidSig;
+
+    let t = 1;
+    let n = 3;
+    let (_priv_keys_vec, priv_shared_keys_vec, Y, key_gen_vss_vec) =
+        keygen_t_n_parties(t, n, &[1, 2, 3]);
+    let xpub = ExtendedPublicKey::new(&Y);
+
+    // a watch-only holder of xpub computes the same child key as the signers
+    let path = DerivationPath::new(&[0, 7]).unwrap();
+    let (_tweak, child_xpub) = xpub.derive(&path).unwrap();
+    let derived_vec = priv_shared_keys_vec
+        .iter()
+        .map(|shared_keys| derive_share(&xpub, shared_keys, &key_gen_vss_vec, &path).unwrap())
+        .collect::<Vec<DerivedShare>>();
+    assert!(derived_vec
+        .iter()
+        .all(|derived| derived.public_key == child_xpub));
+    assert_ne!(child_xpub.y, Y);
+
+    let quorum = SigningQuorum::new(t, n, &[1, 2]).unwrap();
+    let (_eph_keys_vec, eph_shared_keys_vec, V, eph_vss_vec) =
+        keygen_t_n_parties(t, quorum.parties.len(), &quorum.points());
+    let message: [u8; 4] = [79, 77, 69, 82];
+    let msg1_vec = (0..quorum.parties.len())
+        .map(|k| {
+            SignMessage1::create(
+                &message,
+                &eph_shared_keys_vec[k],
+                &derived_vec[quorum.parties[k]],
+            )
+            .unwrap()
+        })
+        .collect::<Vec<SignMessage1>>();
+    // the receivers check the local signatures against the child key of the path
+    assert!(msg1_vec
+        .iter()
+        .all(|msg1| msg1.verify_path(&message, &xpub, &V) == Ok(child_xpub.y)));
+    let local_sig_vec = msg1_vec
+        .iter()
+        .map(|msg1| msg1.local_sig)
+        .collect::<Vec<LocalSig>>();
+    let vss_sum_local_sigs = LocalSig::verify_local_sigs(
+        &local_sig_vec,
+        &quorum.parties,
+        &derived_vec[0].vss_scheme_vec,
+        &eph_vss_vec,
+    )
+    .unwrap();
+    let signature = Signature::generate(
+        &vss_sum_local_sigs,
+        &local_sig_vec,
+        &quorum,
+        &V,
+        &child_xpub.y,
+        &message,
+    )
+    .unwrap();
+    assert!(signature.verify(&message, &child_xpub.y).is_ok());
+    assert!(signature.verify(&message, &Y).is_err());
+
+    // sibling paths give unrelated keys, hardened indices are refused
+    let sibling = DerivationPath::new(&[0, 8]).unwrap();
+    assert_ne!(xpub.derive(&sibling).unwrap().1.y, child_xpub.y);
+    let mut forged = msg1_vec[0].clone();
+    forged.path = sibling;
+    assert_eq!(forged.verify_path(&message, &xpub, &V), Err(InvalidSig));
+    // a local signature under the root key does not pass for the child key
+    let mut forged = msg1_vec[0].clone();
+    forged.local_sig =
+        LocalSig::compute(&message, &eph_shared_keys_vec[0], &priv_shared_keys_vec[1]);
+    assert_eq!(forged.verify_path(&message, &xpub, &V), Err(InvalidSig));
+    assert_eq!(msg1_vec[0].verify_path(&[0x00], &xpub, &V), Err(InvalidSig));
+    // the message travels as bytes, a leading zero byte is not dropped
+    let padded = [&[0x00][..], &message[..]].concat();
+    assert_eq!(msg1_vec[0].message, message.to_vec());
+    assert_eq!(msg1_vec[0].verify_path(&padded, &xpub, &V), Err(InvalidSig));
+    assert!(DerivationPath::new(&[HARDENED_OFFSET]).is_err());
+    assert!(xpub.derive_child(HARDENED_OFFSET + 1).is_err());
+}
+
+#[test]
+fn test_dealt_key_signs_and_reconstructs() {
+    use curv::elliptic::curves::traits::*;
+    use protocols::secret::Secret;
+    use protocols::thresholdsig::dealer::*;
+
+    let t = 1;
+    let n = 3;
+    let legacy_key = Secret::new(ECScalar::new_random());
+    let legacy_y = GE::generator() * legacy_key.expose();
+    let dealt = deal(
+        &legacy_key,
+        &Parameters {
+            threshold: t,
+            share_count: n,
+        },
+    );
+    assert_eq!(dealt.y, legacy_y);
+
+    // the committee signs under the legacy public key
+    let quorum = SigningQuorum::new(t, n, &[0, 2]).unwrap();
+    let (_eph_keys_vec, eph_shared_keys_vec, V, eph_vss_vec) =
+        keygen_t_n_parties(t, quorum.parties.len(), &quorum.points());
+    let message: [u8; 4] = [79, 77, 69, 82];
+    let local_sig_vec = (0..quorum.parties.len())
+        .map(|k| {
+            LocalSig::compute(
+                &message,
+                &eph_shared_keys_vec[k],
+                &dealt.shared_keys_vec[quorum.parties[k]],
+            )
+        })
+        .collect::<Vec<LocalSig>>();
+    let vss_sum_local_sigs = LocalSig::verify_local_sigs(
+        &local_sig_vec,
+        &quorum.parties,
+        &dealt.vss_scheme_vec,
+        &eph_vss_vec,
+    )
+    .unwrap();
+    let signature = Signature::generate(
+        &vss_sum_local_sigs,
+        &local_sig_vec,
+        &quorum,
+        &V,
+        &legacy_y,
+        &message,
+    )
+    .unwrap();
+    assert!(signature.verify(&message, &legacy_y).is_ok());
+
+    // and any quorum can export it again
+    let quorum = SigningQuorum::new(t, n, &[2, 1]).unwrap();
+    let mut shares = quorum
+        .parties
+        .iter()
+        .map(|i| dealt.shared_keys_vec[*i].clone())
+        .collect::<Vec<SharedKeys>>();
+    let exported = reconstruct_secret(&quorum, &dealt.vss_scheme_vec, &legacy_y, &shares).unwrap();
+    assert_eq!(exported.expose(), legacy_key.expose());
+
+    // a key from distributed key generation exports the same way
+    let (_priv_keys_vec, priv_shared_keys_vec, Y, key_gen_vss_vec) =
+        keygen_t_n_parties(t, n, &[1, 2, 3]);
+    let dkg_shares = quorum
+        .parties
+        .iter()
+        .map(|i| priv_shared_keys_vec[*i].clone())
+        .collect::<Vec<SharedKeys>>();
+    let dkg_key = reconstruct_secret(&quorum, &key_gen_vss_vec, &Y, &dkg_shares).unwrap();
+    assert_eq!(GE::generator() * dkg_key.expose(), Y);
+
+    // a wrong share is blamed on the party that handed it in
+    shares[1] = dealt.shared_keys_vec[0].clone();
+    let blame = reconstruct_secret(&quorum, &dealt.vss_scheme_vec, &legacy_y, &shares).unwrap_err();
+    assert_eq!(blame.parties, vec![1]);
+}
+
+#[test]
+fn test_weighted_officer_signs_with_one_clerk() {
+    use protocols::thresholdsig::key_package::PublicKeyPackage;
+    use protocols::thresholdsig::weighted::*;
+
+    // the officer holds two shares, the clerks one each; a quorum needs weight 3
+    let params = WeightedParameters::new(2, &[2, 1, 1, 1]).unwrap();
+    let share_count = params.parameters().share_count;
+    let parties = [1, 2, 3, 4];
+    let party_keys_vec = parties
+        .iter()
+        .map(|i| Keys::phase1_create(*i))
+        .collect::<Vec<Keys>>();
+    let encryption_key_vec = (0..parties.len())
+        .map(|_| EncryptionKey::create())
+        .collect::<Vec<EncryptionKey>>();
+    let encryption_public_key_vec = encryption_key_vec
+        .iter()
+        .map(|key| key.public_key)
+        .collect::<Vec<GE>>();
+
+    let (bc1_vec, decom1_vec): (Vec<_>, Vec<_>) = party_keys_vec
+        .iter()
+        .map(|keys| keys.phase1_broadcast(SESSION_ID))
+        .unzip();
+    let com_echo = echo_message(
+        SESSION_ID,
+        COMMITMENTS,
+        &bc1_vec,
+        &encryption_key_vec,
+        &parties,
+    );
+    let decom_echo = echo_message(
+        SESSION_ID,
+        DECOMMITMENTS,
+        &decom1_vec,
+        &encryption_key_vec,
+        &parties,
+    );
+    let y_vec = party_keys_vec
+        .iter()
+        .map(|keys| keys.y_i)
+        .collect::<Vec<GE>>();
+
+    // msg3_vec_vec[i][k] is the share k dealt by party i
+    let msg3_vec_vec = (0..parties.len())
+        .map(|i| {
+            params
+                .distribute(
+                    &party_keys_vec[i],
+                    SESSION_ID,
+                    &decom1_vec,
+                    &bc1_vec,
+                    &vec![com_echo.clone(); parties.len()],
+                    &vec![decom_echo.clone(); parties.len()],
+                    &encryption_key_vec[i],
+                    &encryption_public_key_vec,
+                    &parties,
+                )
+                .unwrap()
+        })
+        .collect::<Vec<Vec<KeyGenMessage3>>>();
+    assert!(msg3_vec_vec
+        .iter()
+        .all(|msg3_vec| msg3_vec.len() == share_count));
+    let key_gen_vss_vec = msg3_vec_vec
+        .iter()
+        .map(|msg3_vec| msg3_vec[0].vss_scheme.clone())
+        .collect::<Vec<VerifiableSS<GE>>>();
+    let vss_echo_vec = vec![
+        echo_message(
+            SESSION_ID,
+            VSS_COMMITMENTS,
+            &key_gen_vss_vec,
+            &encryption_key_vec,
+            &parties,
+        );
+        parties.len()
+    ];
+    let shared_keys_vec = (0..parties.len())
+        .map(|party| {
+            let received = params
+                .share_indices(party)
+                .unwrap()
+                .iter()
+                .map(|k| {
+                    msg3_vec_vec
+                        .iter()
+                        .map(|msg3_vec| msg3_vec[*k].clone())
+                        .collect::<Vec<KeyGenMessage3>>()
+                })
+                .collect::<Vec<Vec<KeyGenMessage3>>>();
+            params
+                .construct_keypairs(
+                    party,
+                    SESSION_ID,
+                    &y_vec,
+                    &received,
+                    &vss_echo_vec,
+                    &encryption_key_vec[party],
+                    &encryption_public_key_vec,
+                    &parties,
+                )
+                .unwrap()
+        })
+        .collect::<Vec<Vec<SharedKeys>>>();
+    assert_eq!(shared_keys_vec[0].len(), 2);
+    assert_eq!(shared_keys_vec[3].len(), 1);
+    let Y = shared_keys_vec[0][0].y;
+    let key_package = PublicKeyPackage::from_vss(&Y, &key_gen_vss_vec, share_count).unwrap();
+
+    // a share dealt to the officer does not open for a clerk
+    let stolen = (0..parties.len())
+        .map(|i| msg3_vec_vec[i][0].clone())
+        .collect::<Vec<KeyGenMessage3>>();
+    assert!(params
+        .construct_keypairs(
+            1,
+            SESSION_ID,
+            &y_vec,
+            &[stolen],
+            &vss_echo_vec,
+            &encryption_key_vec[1],
+            &encryption_public_key_vec,
+            &parties,
+        )
+        .is_err());
+
+    // the officer signs for two shares but sends one contribution
+    let signers = [0, 3];
+    let quorum = params.quorum(&signers).unwrap();
+    let (_eph_keys_vec, eph_shared_keys_vec, V, eph_vss_vec) =
+        keygen_t_n_parties(params.threshold, quorum.parties.len(), &quorum.points());
+    let eph_package = PublicKeyPackage::from_vss(&V, &eph_vss_vec, share_count).unwrap();
+    let message: [u8; 4] = [79, 77, 69, 82];
+    let mut contributions = Vec::new();
+    let mut position = 0;
+    for party in signers.iter() {
+        let weight = params.weights[*party];
+        contributions.push(
+            WeightedLocalSig::compute(
+                &params,
+                &quorum,
+                *party,
+                &message,
+                &eph_shared_keys_vec[position..position + weight],
+                &shared_keys_vec[*party],
+            )
+            .unwrap(),
+        );
+        position += weight;
+    }
+    let signature = params
+        .generate_signature(
+            &signers,
+            &contributions,
+            &message,
+            &key_package,
+            &eph_package,
+        )
+        .unwrap();
+    assert!(signature.verify(&message, &Y).is_ok());
+
+    // a contribution made with the clerks' shares is blamed on the officer
+    let clerk_keys = vec![shared_keys_vec[1][0].clone(), shared_keys_vec[2][0].clone()];
+    contributions[0] = WeightedLocalSig::compute(
+        &params,
+        &quorum,
+        0,
+        &message,
+        &eph_shared_keys_vec[0..2],
+        &clerk_keys,
+    )
+    .unwrap();
+    let blame = params
+        .generate_signature(
+            &signers,
+            &contributions,
+            &message,
+            &key_package,
+            &eph_package,
+        )
+        .unwrap_err();
+    assert_eq!(blame.parties, vec![0]);
+
+    // a clerk outside the quorum cannot contribute, and two clerks are not a quorum
+    assert!(WeightedLocalSig::compute(
+        &params,
+        &quorum,
+        1,
+        &message,
+        &eph_shared_keys_vec[2..3],
+        &shared_keys_vec[1],
+    )
+    .is_err());
+    assert!(params.quorum(&[1, 2, 3]).is_ok());
+    assert!(params.quorum(&[1, 2]).is_err());
+}
+
+#[test]
+fn test_hierarchical_sign_requires_an_executive() {
+    // two executives and three staff, any three signers with at least one executive
+    let params = hierarchical::HierarchicalParameters::new(2, &[0, 0, 1, 1, 1]).unwrap();
+    let all = (0..5).collect::<Vec<usize>>();
+    let (priv_shared_keys_vec, Y, key_gen_vss_vec) = hierarchical_keygen(&params, &all);
+
+    let quorum = params.quorum(&[3, 1, 4]).unwrap();
+    let (eph_shared_keys_vec, V, eph_vss_vec) = hierarchical_keygen(&params, &quorum.parties);
+    let message: [u8; 4] = [79, 77, 69, 82];
+    let mut local_sig_vec = (0..quorum.parties.len())
+        .map(|k| {
+            LocalSig::compute(
+                &message,
+                &eph_shared_keys_vec[k],
+                &priv_shared_keys_vec[quorum.parties[k]],
+            )
+        })
+        .collect::<Vec<LocalSig>>();
+    let vss_sum_local_sigs = LocalSig::verify_local_sigs_in_quorum(
+        &local_sig_vec,
+        &quorum,
+        &key_gen_vss_vec,
+        &eph_vss_vec,
+    )
+    .unwrap();
+    let signature = Signature::generate(
+        &vss_sum_local_sigs,
+        &local_sig_vec,
+        &quorum,
+        &V,
+        &Y,
+        &message,
+    )
+    .unwrap();
+    assert!(signature.verify(&message, &Y).is_ok());
+
+    // a staff member signing with an executive's share is caught
+    local_sig_vec[0] =
+        LocalSig::compute(&message, &eph_shared_keys_vec[0], &priv_shared_keys_vec[0]);
+    assert!(Signature::generate(
+        &vss_sum_local_sigs,
+        &local_sig_vec,
+        &quorum,
+        &V,
+        &Y,
+        &message,
+    )
+    .is_err());
+
+    // all of the staff together are not authorised
+    assert!(params.quorum(&[2, 3, 4]).is_err());
+}
+
+#[test]
+fn test_repair_lost_share() {
+    use protocols::secret::Secret;
+    use protocols::thresholdsig::repair::RepairSession;
+
+    let t = 2;
+    let n = 5;
+    let (_priv_keys_vec, priv_shared_keys_vec, Y, key_gen_vss_vec) =
+        keygen_t_n_parties(t, n, &[1, 2, 3, 4, 5]);
+
+    // party 3 lost its device, parties 0, 1 and 4 help
+    let session = RepairSession::new(t, n, &[0, 1, 4], 3).unwrap();
+    let summands_vec = (0..session.helpers.len())
+        .map(|k| session.split_contribution(k, &priv_shared_keys_vec[session.helpers[k]]))
+        .collect::<Vec<Vec<Secret<FE>>>>();
+    let mut sigma_vec = (0..session.helpers.len())
+        .map(|j| {
+            let received = summands_vec
+                .iter()
+                .map(|summands| summands[j].clone())
+                .collect::<Vec<Secret<FE>>>();
+            session.sum_contributions(&received)
+        })
+        .collect::<Vec<Secret<FE>>>();
+    let repaired = session.recover(&sigma_vec, &Y, &key_gen_vss_vec).unwrap();
+    assert_eq!(repaired.x_i.expose(), priv_shared_keys_vec[3].x_i.expose());
+    assert_eq!(repaired.y, Y);
+
+    // a helper sending a wrong sum is detected by the commitments
+    sigma_vec[1] = Secret::new(*sigma_vec[1].expose() + *sigma_vec[0].expose());
+    assert!(session.recover(&sigma_vec, &Y, &key_gen_vss_vec).is_err());
+
+    assert!(RepairSession::new(t, n, &[0, 1], 3).is_err());
+    assert!(RepairSession::new(t, n, &[0, 1, 3], 3).is_err());
+}
+
+#[test]
+fn test_public_key_package_verifies_local_sigs() {
+    use curv::elliptic::curves::traits::*;
+    use protocols::thresholdsig::key_package::PublicKeyPackage;
+    use serde_json;
+
+    let t = 1;
+    let n = 3;
+    let (_priv_keys_vec, priv_shared_keys_vec, Y, key_gen_vss_vec) =
+        keygen_t_n_parties(t, n, &[1, 2, 3]);
+    let key_package = PublicKeyPackage::from_vss(&Y, &key_gen_vss_vec, n).unwrap();
+    assert_eq!(key_package.threshold, t);
+    for (i, shared_keys) in priv_shared_keys_vec.iter().enumerate() {
+        assert_eq!(
+            key_package.verification_shares[i],
+            GE::generator() * shared_keys.x_i.expose()
+        );
+    }
+    let archived = serde_json::to_string(&key_package).unwrap();
+    let key_package: PublicKeyPackage = serde_json::from_str(&archived).unwrap();
+
+    let quorum = SigningQuorum::new(t, n, &[0, 2]).unwrap();
+    let (_eph_keys_vec, eph_shared_keys_vec, V, eph_vss_vec) =
+        keygen_t_n_parties(t, quorum.parties.len(), &quorum.points());
+    let eph_package = PublicKeyPackage::from_vss(&V, &eph_vss_vec, n).unwrap();
+    let message: [u8; 4] = [79, 77, 69, 82];
+    let local_sig_vec = (0..quorum.parties.len())
+        .map(|k| {
+            LocalSig::compute(
+                &message,
+                &eph_shared_keys_vec[k],
+                &priv_shared_keys_vec[quorum.parties[k]],
+            )
+        })
+        .collect::<Vec<LocalSig>>();
+    for (k, local_sig) in local_sig_vec.iter().enumerate() {
+        assert!(local_sig
+            .verify(&message, quorum.parties[k], &key_package, &eph_package)
+            .is_ok());
+    }
+    // wrong index, wrong message
+    assert!(local_sig_vec[0]
+        .verify(&message, 1, &key_package, &eph_package)
+        .is_err());
+    assert!(local_sig_vec[0]
+        .verify(&[1, 2, 3], 0, &key_package, &eph_package)
+        .is_err());
+    assert!(PublicKeyPackage::from_vss(&V, &key_gen_vss_vec, n).is_err());
+}
+
+#[test]
+fn test_aggregator_skips_bad_local_sig() {
+    use protocols::thresholdsig::key_package::PublicKeyPackage;
+    use Error::InvalidSig;
+
+    let t = 1;
+    let n = 4;
+    let points = [1, 2, 3, 4];
+    let (_priv_keys_vec, priv_shared_keys_vec, Y, key_gen_vss_vec) =
+        keygen_t_n_parties(t, n, &points);
+    // all four are asked to sign, any two valid local signatures are enough
+    let (_eph_keys_vec, eph_shared_keys_vec, V, eph_vss_vec) = keygen_t_n_parties(t, n, &points);
+    let key_package = PublicKeyPackage::from_vss(&Y, &key_gen_vss_vec, n).unwrap();
+    let eph_package = PublicKeyPackage::from_vss(&V, &eph_vss_vec, n).unwrap();
+    let message: [u8; 4] = [79, 77, 69, 82];
+    let mut aggregator = Signature::aggregator(&message, &key_package, &eph_package).unwrap();
+
+    // party 3 answers first, with a local signature made with party 0's key share
+    let bad = LocalSig::compute(&message, &eph_shared_keys_vec[3], &priv_shared_keys_vec[0]);
+    let blame = aggregator.add(3, bad).unwrap_err();
+    assert_eq!(blame.error, InvalidSig);
+    assert_eq!(blame.parties, vec![3]);
+    let local_sig = LocalSig::compute(&message, &eph_shared_keys_vec[1], &priv_shared_keys_vec[1]);
+    assert!(aggregator.add(1, local_sig).unwrap().is_none());
+    let local_sig = LocalSig::compute(&message, &eph_shared_keys_vec[1], &priv_shared_keys_vec[1]);
+    assert!(aggregator.add(1, local_sig).unwrap().is_none());
+    let local_sig = LocalSig::compute(&message, &eph_shared_keys_vec[2], &priv_shared_keys_vec[2]);
+    let signature = aggregator.add(2, local_sig).unwrap().unwrap();
+    assert!(signature.verify(&message, &Y).is_ok());
+    assert_eq!(aggregator.blamed(), &[3]);
+
+    // a blamed party stays excluded
+    let local_sig = LocalSig::compute(&message, &eph_shared_keys_vec[3], &priv_shared_keys_vec[3]);
+    assert!(aggregator.add(3, local_sig).is_err());
+}
+
+#[test]
+fn test_roast_terminates_with_silent_and_malicious_signers() {
+    // parties 1 and 5 go silent after their first message, party 3 sends bad shares
+    let behaviours = [
+        Behaviour::Honest,
+        Behaviour::Silent,
+        Behaviour::Honest,
+        Behaviour::Malicious,
+        Behaviour::Honest,
+        Behaviour::Silent,
+        Behaviour::Honest,
+    ];
+    let coordinator = simulate_roast(2, &behaviours);
+    assert_eq!(coordinator.malicious(), &[3]);
+    let unresponsive = coordinator.unresponsive();
+    assert!(unresponsive
+        .iter()
+        .all(|i| behaviours[*i] == Behaviour::Silent));
+}
+
+#[test]
+fn test_roast_blames_signer_that_resends_ready() {
+    // party 2 answers every request with another first message
+    let behaviours = [
+        Behaviour::Honest,
+        Behaviour::Honest,
+        Behaviour::Replaying,
+        Behaviour::Honest,
+        Behaviour::Silent,
+    ];
+    let coordinator = simulate_roast(2, &behaviours);
+    assert_eq!(coordinator.malicious(), &[2]);
+}
+
+#[allow(dead_code)]
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Behaviour {
+    Honest,
+    Silent,
+    Malicious,
+    Replaying,
+}
+
+// runs ROAST with one signer per behaviour until the coordinator outputs a signature, which
+// is checked. A blamed party has to be dishonest and is in no later session.
+#[allow(dead_code)]
+fn simulate_roast(
+    t: usize,
+    behaviours: &[Behaviour],
+) -> protocols::thresholdsig::roast::RoastCoordinator {
+    use protocols::thresholdsig::key_package::PublicKeyPackage;
+    use protocols::thresholdsig::roast::*;
+
+    let n = behaviours.len();
+    let (_priv_keys_vec, priv_shared_keys_vec, Y, key_gen_vss_vec) =
+        keygen_t_n_parties(t, n, &(1..n + 1).collect::<Vec<usize>>());
+    let key_package = PublicKeyPackage::from_vss(&Y, &key_gen_vss_vec, n).unwrap();
+    let message: [u8; 4] = [79, 77, 69, 82];
+
+    let mut signers = (0..n)
+        .map(|i| RoastSigner::new(i, t, n, &priv_shared_keys_vec[i]))
+        .collect::<Vec<RoastSigner>>();
+    let mut coordinator = RoastCoordinator::new(&message, &key_package);
+    let mut network = Simulator::new(
+        (0..n)
+            .map(|i| (i, signers[i].ready()))
+            .collect::<Vec<(usize, SignerResponse)>>(),
+    );
+    for i in 0..n {
+        if behaviours[i] == Behaviour::Replaying {
+            let response = signers[i].ready();
+            network.send(i, response);
+        }
+    }
+
+    let mut blamed = Vec::new();
+    let mut steps = 0;
+    while coordinator.signature().is_none() {
+        steps += 1;
+        assert!(steps < 1000, "coordinator did not terminate");
+        let (party, response) = network.deliver().expect("network ran dry");
+        let starts = match coordinator.receive(party, response) {
+            Ok(starts) => starts,
+            Err(blame) => {
+                assert_eq!(blame.parties, vec![party]);
+                assert_ne!(behaviours[party], Behaviour::Honest);
+                blamed.push(party);
+                continue;
+            }
+        };
+        for start in starts.iter() {
+            for commitment in start.commitments.iter() {
+                let member = commitment.party;
+                assert!(!blamed.contains(&member));
+                let response = match behaviours[member] {
+                    Behaviour::Honest => signers[member].sign(&message, start).unwrap(),
+                    Behaviour::Silent => continue,
+                    Behaviour::Malicious => {
+                        let mut response = signers[member].sign(&message, start).unwrap();
+                        let share = response.share.as_mut().unwrap();
+                        share.1.z = share.1.z + share.1.z;
+                        response
+                    }
+                    Behaviour::Replaying => signers[member].ready(),
+                };
+                network.send(member, response);
+            }
+        }
+    }
+    assert!(coordinator
+        .signature()
+        .unwrap()
+        .verify(&message, &Y)
+        .is_ok());
+    coordinator
+}
+
+// in-process network delivering messages to the coordinator in a scrambled order
+#[allow(dead_code)]
+struct Simulator<M> {
+    queue: Vec<(usize, M)>,
+    counter: usize,
+}
+
+#[allow(dead_code)]
+impl<M> Simulator<M> {
+    fn new(queue: Vec<(usize, M)>) -> Simulator<M> {
+        Simulator { queue, counter: 0 }
+    }
+
+    fn send(&mut self, party: usize, message: M) {
+        self.queue.push((party, message));
+    }
+
+    fn deliver(&mut self) -> Option<(usize, M)> {
+        if self.queue.is_empty() {
+            return None;
+        }
+        // deterministic reordering, so replays are reproducible
+        self.counter += 1;
+        let index = (self.counter * 7) % self.queue.len();
+        Some(self.queue.remove(index))
+    }
+}
+
+// hierarchical key generation among `parties`
+#[allow(dead_code)]
+fn hierarchical_keygen(
+    params: &hierarchical::HierarchicalParameters,
+    parties: &[usize],
+) -> (Vec<SharedKeys>, GE, Vec<VerifiableSS<GE>>) {
+    let keys_vec = parties
+        .iter()
+        .map(|i| Keys::phase1_create(*i))
+        .collect::<Vec<Keys>>();
+    let (bc1_vec, decom1_vec): (Vec<_>, Vec<_>) = keys_vec
+        .iter()
+        .map(|keys| keys.phase1_broadcast(SESSION_ID))
+        .unzip();
+    let y_vec = keys_vec.iter().map(|keys| keys.y_i).collect::<Vec<GE>>();
+    let encryption_key_vec = (0..parties.len())
+        .map(|_| EncryptionKey::create())
+        .collect::<Vec<EncryptionKey>>();
+    let encryption_public_key_vec = encryption_key_vec
+        .iter()
+        .map(|key| key.public_key)
+        .collect::<Vec<GE>>();
+    let com_echo = echo_message(
+        SESSION_ID,
+        COMMITMENTS,
+        &bc1_vec,
+        &encryption_key_vec,
+        parties,
+    );
+    let decom_echo = echo_message(
+        SESSION_ID,
+        DECOMMITMENTS,
+        &decom1_vec,
+        &encryption_key_vec,
+        parties,
+    );
+
+    let msg3_vec_vec = (0..parties.len())
+        .map(|i| {
+            hierarchical::distribute(
+                &keys_vec[i],
+                params,
+                SESSION_ID,
+                &decom1_vec,
+                &bc1_vec,
+                &vec![com_echo.clone(); parties.len()],
+                &vec![decom_echo.clone(); parties.len()],
+                &encryption_key_vec[i],
+                &encryption_public_key_vec,
+                parties,
+            )
+            .expect("invalid key")
+        })
+        .collect::<Vec<Vec<KeyGenMessage3>>>();
+    let vss_scheme_vec = msg3_vec_vec
+        .iter()
+        .map(|msg3_vec| msg3_vec[0].vss_scheme.clone())
+        .collect::<Vec<VerifiableSS<GE>>>();
+    let vss_echo_vec = vec![
+        echo_message(
+            SESSION_ID,
+            VSS_COMMITMENTS,
+            &vss_scheme_vec,
+            &encryption_key_vec,
+            parties,
+        );
+        parties.len()
+    ];
+    let shared_keys_vec = (0..parties.len())
+        .map(|k| {
+            let party_msg3_vec = msg3_vec_vec
+                .iter()
+                .map(|msg3_vec| msg3_vec[k].clone())
+                .collect::<Vec<KeyGenMessage3>>();
+            hierarchical::construct_keypair(
+                params,
+                parties[k],
+                SESSION_ID,
+                &y_vec,
+                &party_msg3_vec,
+                &vss_echo_vec,
+                &encryption_key_vec[k],
+                &encryption_public_key_vec,
+                parties,
+            )
+            .expect("invalid hierarchical key share")
+        })
+        .collect::<Vec<SharedKeys>>();
+    let y = shared_keys_vec[0].y;
+    (shared_keys_vec, y, vss_scheme_vec)
 }
 
 #[allow(dead_code)]
@@ -140,7 +1366,7 @@ pub fn keygen_t_n_parties(
     let mut bc1_vec = Vec::new();
     let mut decom1_vec = Vec::new();
     for i in 0..n.clone() {
-        let (bc1, decom1) = party_keys_vec[i].phase1_broadcast();
+        let (bc1, decom1) = party_keys_vec[i].phase1_broadcast(SESSION_ID);
         bc1_vec.push(bc1);
         decom1_vec.push(decom1);
     }
@@ -148,38 +1374,87 @@ pub fn keygen_t_n_parties(
     let y_vec = (0..n.clone())
         .map(|i| party_keys_vec[i].y_i.clone())
         .collect::<Vec<GE>>();
+    // long-term keys used to deliver the secret shares and to sign the broadcasts
+    let encryption_key_vec = (0..n)
+        .map(|_| EncryptionKey::create())
+        .collect::<Vec<EncryptionKey>>();
+    let encryption_public_key_vec = encryption_key_vec
+        .iter()
+        .map(|key| key.public_key)
+        .collect::<Vec<GE>>();
+
+    // echo rounds: here every party received the same broadcasts
+    let com_echo = echo_message(
+        SESSION_ID,
+        COMMITMENTS,
+        &bc1_vec,
+        &encryption_key_vec,
+        parties,
+    );
+    let decom_echo = echo_message(
+        SESSION_ID,
+        DECOMMITMENTS,
+        &decom1_vec,
+        &encryption_key_vec,
+        parties,
+    );
+
     let mut vss_scheme_vec = Vec::new();
-    let mut secret_shares_vec = Vec::new();
-    let mut index_vec = Vec::new();
+    let mut msg3_vec_vec = Vec::new();
     for i in 0..n.clone() {
-        let (vss_scheme, secret_shares, index) = party_keys_vec[i]
-            .phase1_verify_com_phase2_distribute(&parames, &decom1_vec, &bc1_vec, parties)
+        let (vss_scheme, secret_shares, _index) = party_keys_vec[i]
+            .phase1_verify_com_phase2_distribute(
+                &parames,
+                SESSION_ID,
+                &decom1_vec,
+                &bc1_vec,
+                &vec![com_echo.clone(); n],
+                &vec![decom_echo.clone(); n],
+                &encryption_public_key_vec,
+                parties,
+            )
             .expect("invalid key");
+        let msg3_vec = party_keys_vec[i].phase2_encrypt_shares(
+            SESSION_ID,
+            &vss_scheme,
+            &secret_shares,
+            &encryption_key_vec[i],
+            &encryption_public_key_vec,
+            parties,
+        );
         vss_scheme_vec.push(vss_scheme);
-        secret_shares_vec.push(secret_shares);
-        index_vec.push(index);
+        msg3_vec_vec.push(msg3_vec);
     }
 
-    let party_shares = (0..n.clone())
+    let vss_echo = echo_message(
+        SESSION_ID,
+        VSS_COMMITMENTS,
+        &vss_scheme_vec,
+        &encryption_key_vec,
+        parties,
+    );
+
+    // party i receives the i-th message of every party
+    let party_msg3_vec = (0..n.clone())
         .map(|i| {
             (0..n.clone())
-                .map(|j| {
-                    let vec_j = &secret_shares_vec[j];
-                    vec_j[i].clone()
-                })
-                .collect::<Vec<FE>>()
+                .map(|j| msg3_vec_vec[j][i].clone())
+                .collect::<Vec<KeyGenMessage3>>()
         })
-        .collect::<Vec<Vec<FE>>>();
+        .collect::<Vec<Vec<KeyGenMessage3>>>();
 
     let mut shared_keys_vec = Vec::new();
     for i in 0..n.clone() {
         let shared_keys = party_keys_vec[i]
             .phase2_verify_vss_construct_keypair(
                 &parames,
+                SESSION_ID,
                 &y_vec,
-                &party_shares[i],
-                &vss_scheme_vec,
-                &index_vec[i],
+                &party_msg3_vec[i],
+                &vec![vss_echo.clone(); n],
+                &encryption_key_vec[i],
+                &encryption_public_key_vec,
+                parties,
             )
             .expect("invalid vss");
         shared_keys_vec.push(shared_keys);
@@ -192,3 +1467,39 @@ pub fn keygen_t_n_parties(
         vss_scheme_vec,
     )
 }
+
+// the echo of a party that received `received` in the round, with every sender signing the
+// message this party received from it
+#[allow(dead_code)]
+pub fn echo_message<T: Serialize>(
+    session_id: &[u8],
+    round: &[u8],
+    received: &[T],
+    encryption_key_vec: &[EncryptionKey],
+    parties: &[usize],
+) -> EchoMessage {
+    let signed_vec = (0..received.len())
+        .map(|j| {
+            SignedDigest::sign(
+                session_id,
+                round,
+                parties[j],
+                &received[j],
+                &encryption_key_vec[j],
+            )
+        })
+        .collect::<Vec<SignedDigest>>();
+    let encryption_public_key_vec = encryption_key_vec
+        .iter()
+        .map(|key| key.public_key)
+        .collect::<Vec<GE>>();
+    EchoMessage::create(
+        session_id,
+        round,
+        received,
+        &signed_vec,
+        &encryption_public_key_vec,
+        parties,
+    )
+    .expect("invalid signed digest")
+}
@@ -0,0 +1,346 @@
+/*
+    Multisig Schnorr
+
+    Copyright 2018 by Kzen Networks
+
+    This file is part of Multisig Schnorr library
+    (https://github.com/KZen-networks/multisig-schnorr)
+
+    Multisig Schnorr is free software: you can redistribute
+    it and/or modify it under the terms of the GNU General Public
+    License as published by the Free Software Foundation, either
+    version 3 of the License, or (at your option) any later version.
+
+    @license GPL-3.0+ <https://github.com/KZen-networks/multisig-schnorr/blob/master/LICENSE>
+*/
+
+//! Weighted thresholds for `zilliqa_schnorr`: a party with weight w holds w shares, at w
+//! consecutive evaluation points. A key can be signed for by any set of parties whose weights
+//! add up to more than the threshold.
+//!
+//! Key generation runs the commitment round of `Keys` among the parties. `distribute` then
+//! shares each u_i at every share point and encrypts each share to the party owning it, and
+//! `construct_keypairs` hands a party the `SharedKeys` of each of its w shares. When signing,
+//! a party computes a local signature per share and combines them with the Lagrange
+//! coefficients of its shares in the quorum, so it sends a single `WeightedLocalSig`. The
+//! contributions add up to the signature, and a bad one is blamed on the party that sent it.
+use curv::cryptographic_primitives::secret_sharing::feldman_vss::VerifiableSS;
+use curv::elliptic::curves::traits::*;
+use protocols::thresholdsig::echo_broadcast::EchoMessage;
+use protocols::thresholdsig::key_package::PublicKeyPackage;
+use protocols::thresholdsig::quorum::SigningQuorum;
+use protocols::thresholdsig::share_encryption::EncryptionKey;
+use protocols::thresholdsig::zilliqa_schnorr::{
+    challenge, KeyGenBroadcastMessage1, KeyGenBroadcastMessage2, KeyGenMessage3, Keys, LocalSig,
+    Parameters, SharedKeys, Signature,
+};
+use protocols::thresholdsig::Blame;
+use Error::{self, InvalidSS, InvalidSig};
+
+type GE = curv::elliptic::curves::secp256_k1::GE;
+type FE = curv::elliptic::curves::secp256_k1::FE;
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct WeightedParameters {
+    // the total weight of a quorum has to exceed the threshold
+    pub threshold: usize,
+    pub weights: Vec<usize>,
+}
+
+impl WeightedParameters {
+    pub fn new(threshold: usize, weights: &[usize]) -> Result<WeightedParameters, Error> {
+        if weights.iter().any(|w| *w == 0) || weights.iter().sum::<usize>() <= threshold {
+            return Err(InvalidSS);
+        }
+        Ok(WeightedParameters {
+            threshold,
+            weights: weights.to_vec(),
+        })
+    }
+
+    // the parameters of the underlying sharing, one share per unit of weight
+    pub fn parameters(&self) -> Parameters {
+        Parameters {
+            threshold: self.threshold,
+            share_count: self.weights.iter().sum(),
+        }
+    }
+
+    // 0-based share indices of a party, the share at index k is evaluated at k + 1
+    pub fn share_indices(&self, party: usize) -> Result<Vec<usize>, Error> {
+        if party >= self.weights.len() {
+            return Err(InvalidSS);
+        }
+        let offset = self.weights[..party].iter().sum::<usize>();
+        Ok((offset..offset + self.weights[party]).collect::<Vec<usize>>())
+    }
+
+    pub fn owner(&self, share_index: usize) -> Option<usize> {
+        (0..self.weights.len()).find(|party| {
+            self.share_indices(*party)
+                .map(|share_indices| share_indices.contains(&share_index))
+                .unwrap_or(false)
+        })
+    }
+
+    // the quorum of all shares held by the signers
+    pub fn quorum(&self, signers: &[usize]) -> Result<SigningQuorum, Error> {
+        let share_indices = signers
+            .iter()
+            .map(|party| self.share_indices(*party))
+            .collect::<Result<Vec<Vec<usize>>, Error>>()?
+            .concat();
+        SigningQuorum::new(
+            self.threshold,
+            self.parameters().share_count,
+            &share_indices,
+        )
+    }
+
+    // 1-based evaluation points of all shares
+    pub fn points(&self) -> Vec<usize> {
+        (1..self.parameters().share_count + 1).collect::<Vec<usize>>()
+    }
+
+    // positions of the shares of `party` in the quorum
+    fn positions(&self, quorum: &SigningQuorum, party: usize) -> Result<Vec<usize>, Error> {
+        self.share_indices(party)?
+            .iter()
+            .map(|k| {
+                quorum
+                    .parties
+                    .iter()
+                    .position(|share_index| share_index == k)
+                    .ok_or(InvalidSS)
+            })
+            .collect()
+    }
+
+    // dealer side of the key generation, after the commitment round of `Keys` among the
+    // parties: u_i is shared at every share point and each share is encrypted to its owner.
+    // parties[i] is the `Keys` index of party i and recipient_key_vec[i] its long-term key.
+    #[allow(clippy::too_many_arguments)]
+    pub fn distribute(
+        &self,
+        keys: &Keys,
+        session_id: &[u8],
+        decom1_vec: &[KeyGenBroadcastMessage2],
+        bc1_vec: &[KeyGenBroadcastMessage1],
+        com_echo_vec: &[EchoMessage],
+        decom_echo_vec: &[EchoMessage],
+        encryption_key: &EncryptionKey,
+        recipient_key_vec: &[GE],
+        parties: &[usize],
+    ) -> Result<Vec<KeyGenMessage3>, Blame> {
+        assert_eq!(parties.len(), self.weights.len());
+        assert_eq!(recipient_key_vec.len(), self.weights.len());
+        Keys::phase1_verify_com(
+            session_id,
+            decom1_vec,
+            bc1_vec,
+            com_echo_vec,
+            decom_echo_vec,
+            recipient_key_vec,
+            parties,
+        )?;
+        let points = self.points();
+        let (vss_scheme, secret_shares) = VerifiableSS::share_at_indices(
+            self.threshold,
+            points.len(),
+            keys.u_i.expose(),
+            &points,
+        );
+        let share_key_vec = (0..points.len())
+            .map(|k| recipient_key_vec[self.owner(k).unwrap()])
+            .collect::<Vec<GE>>();
+        Ok(keys.phase2_encrypt_shares(
+            session_id,
+            &vss_scheme,
+            &secret_shares,
+            encryption_key,
+            &share_key_vec,
+            &points,
+        ))
+    }
+
+    // receiver side: msg3_vec_vec[s][i] is the message of party i for the s-th share of
+    // `party` and vss_echo_vec[i] its echo of the VSS commitments. Returns the keys of every
+    // share of `party`, in share index order.
+    #[allow(clippy::too_many_arguments)]
+    pub fn construct_keypairs(
+        &self,
+        party: usize,
+        session_id: &[u8],
+        y_vec: &[GE],
+        msg3_vec_vec: &[Vec<KeyGenMessage3>],
+        vss_echo_vec: &[EchoMessage],
+        encryption_key: &EncryptionKey,
+        sender_key_vec: &[GE],
+        parties: &[usize],
+    ) -> Result<Vec<SharedKeys>, Blame> {
+        let share_indices = self.share_indices(party).map_err(|error| Blame {
+            error,
+            parties: Vec::new(),
+        })?;
+        assert_eq!(msg3_vec_vec.len(), share_indices.len());
+        share_indices
+            .iter()
+            .zip(msg3_vec_vec.iter())
+            .map(|(k, msg3_vec)| {
+                Keys::phase2_construct_keypair_at(
+                    k + 1,
+                    session_id,
+                    y_vec,
+                    msg3_vec,
+                    vss_echo_vec,
+                    encryption_key,
+                    sender_key_vec,
+                    parties,
+                )
+            })
+            .collect()
+    }
+
+    // contributions[j] is the local signature of signers[j]; a blame names positions in signers
+    pub fn generate_signature(
+        &self,
+        signers: &[usize],
+        contributions: &[WeightedLocalSig],
+        message: &[u8],
+        key_package: &PublicKeyPackage,
+        eph_package: &PublicKeyPackage,
+    ) -> Result<Signature, Blame> {
+        assert_eq!(signers.len(), contributions.len());
+        let quorum = self.quorum(signers).map_err(|error| Blame {
+            error,
+            parties: Vec::new(),
+        })?;
+        let bad_parties = (0..signers.len())
+            .filter(|j| {
+                contributions[*j]
+                    .verify(
+                        self,
+                        &quorum,
+                        signers[*j],
+                        message,
+                        key_package,
+                        eph_package,
+                    )
+                    .is_err()
+            })
+            .collect::<Vec<usize>>();
+        if !bad_parties.is_empty() {
+            return Err(Blame {
+                error: InvalidSS,
+                parties: bad_parties,
+            });
+        }
+        Ok(Signature {
+            s: contributions
+                .iter()
+                .fold(FE::zero(), |acc, contribution| acc + &contribution.gamma),
+            e: challenge(&eph_package.y, &key_package.y, message),
+        })
+    }
+}
+
+// the one contribution of a party to a weighted signature
+#[derive(Clone, Copy, Debug, Serialize, Deserialize)]
+pub struct WeightedLocalSig {
+    gamma: FE,
+    e: FE,
+}
+
+impl WeightedLocalSig {
+    // eph_shared_keys[k] and shared_keys[k] belong to the party's k-th share
+    pub fn compute(
+        params: &WeightedParameters,
+        quorum: &SigningQuorum,
+        party: usize,
+        message: &[u8],
+        eph_shared_keys: &[SharedKeys],
+        shared_keys: &[SharedKeys],
+    ) -> Result<WeightedLocalSig, Error> {
+        let positions = params.positions(quorum, party)?;
+        if eph_shared_keys.len() != positions.len() || shared_keys.len() != positions.len() {
+            return Err(InvalidSS);
+        }
+        let local_sig_vec = eph_shared_keys
+            .iter()
+            .zip(shared_keys.iter())
+            .map(|(eph_key, key)| LocalSig::compute(message, eph_key, key))
+            .collect::<Vec<LocalSig>>();
+        let gamma = positions.iter().zip(local_sig_vec.iter()).fold(
+            FE::zero(),
+            |acc, (position, local_sig)| {
+                acc + &(quorum.lagrange_coefficient(*position) * &local_sig.gamma_i)
+            },
+        );
+        Ok(WeightedLocalSig {
+            gamma,
+            e: local_sig_vec[0].e,
+        })
+    }
+
+    // gamma*G + e*X = B, where X and B combine the verification shares of the party's shares
+    // in the key and in the ephemeral key with the same Lagrange coefficients
+    pub fn verify(
+        &self,
+        params: &WeightedParameters,
+        quorum: &SigningQuorum,
+        party: usize,
+        message: &[u8],
+        key_package: &PublicKeyPackage,
+        eph_package: &PublicKeyPackage,
+    ) -> Result<(), Error> {
+        if self.e != challenge(&eph_package.y, &key_package.y, message) {
+            return Err(InvalidSig);
+        }
+        let positions = params.positions(quorum, party)?;
+        let combine = |package: &PublicKeyPackage| -> Result<GE, Error> {
+            let terms = positions
+                .iter()
+                .map(|position| {
+                    package
+                        .verification_share(quorum.parties[*position])
+                        .map(|share| *share * &quorum.lagrange_coefficient(*position))
+                        .ok_or(InvalidSS)
+                })
+                .collect::<Result<Vec<GE>, Error>>()?;
+            let (first, rest) = terms.split_first().ok_or(InvalidSS)?;
+            Ok(rest.iter().fold(*first, |acc, term| acc + term))
+        };
+        let public_share = combine(key_package)?;
+        let eph_public_share = combine(eph_package)?;
+        if GE::generator() * &self.gamma + &(public_share * &self.e) == eph_public_share {
+            Ok(())
+        } else {
+            Err(InvalidSig)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_quorum_counts_weight() {
+        let params = WeightedParameters::new(2, &[2, 1, 1, 1]).unwrap();
+        assert_eq!(params.parameters().share_count, 5);
+        assert_eq!(params.share_indices(0), Ok(vec![0, 1]));
+        assert_eq!(params.share_indices(3), Ok(vec![4]));
+        assert_eq!(params.share_indices(4), Err(InvalidSS));
+        assert_eq!(params.owner(1), Some(0));
+        assert_eq!(params.owner(5), None);
+
+        assert!(params.quorum(&[0]).is_err());
+        assert!(params.quorum(&[1, 2]).is_err());
+        assert_eq!(params.quorum(&[0, 3]).unwrap().parties, vec![0, 1, 4]);
+        assert!(params.quorum(&[1, 2, 3]).is_ok());
+        assert!(params.quorum(&[4]).is_err());
+
+        assert!(WeightedParameters::new(2, &[1, 1]).is_err());
+        assert!(WeightedParameters::new(1, &[2, 0]).is_err());
+    }
+}
@@ -31,15 +31,29 @@ use curv::cryptographic_primitives::hashing::hash_sha256::HSha256;
 use curv::cryptographic_primitives::hashing::traits::Hash;
 pub use curv::cryptographic_primitives::secret_sharing::feldman_vss::VerifiableSS;
 pub use curv::BigInt;
+use protocols::backup::{self, EncryptedBackup};
+use protocols::secret::Secret;
+use protocols::thresholdsig::derivation::{DerivationPath, DerivedShare, ExtendedPublicKey};
+use protocols::thresholdsig::echo_broadcast::{
+    verify_echoes, EchoMessage, COMMITMENTS, DECOMMITMENTS, VSS_COMMITMENTS,
+};
+use protocols::thresholdsig::key_package::{
+    verification_shares, PublicKeyPackage, SignatureAggregator,
+};
+use protocols::thresholdsig::key_proof::KeyProof;
+use protocols::thresholdsig::quorum::{Interpolation, SigningQuorum};
+use protocols::thresholdsig::share_encryption::{EncryptedShare, EncryptionKey};
+use protocols::thresholdsig::Blame;
+use protocols::transcript::Transcript;
 
 type GE = curv::elliptic::curves::secp256_k1::GE;
 type FE = curv::elliptic::curves::secp256_k1::FE;
 
 const SECURITY: usize = 256;
 
-#[derive(Clone, Debug, Serialize, Deserialize)]
+#[derive(Clone, Debug)]
 pub struct Keys {
-    pub u_i: FE,
+    pub u_i: Secret<FE>,
     pub y_i: GE,
     pub party_index: usize,
 }
@@ -53,52 +67,111 @@ pub struct KeyGenBroadcastMessage1 {
 pub struct KeyGenBroadcastMessage2 {
     pub y_i: GE,
     pub blind_factor: BigInt,
+    pub proof: KeyProof,
 }
 
 #[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct KeyGenMessage3 {
     pub vss_scheme: VerifiableSS<GE>,
-    pub secret_share: FE, // different per party, thus not a broadcast message
+    pub sender_index: usize,
+    pub encrypted_share: EncryptedShare, // different per party, thus not a broadcast message
 }
 
 #[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct SignMessage1 {
-    pub message: BigInt,
+    pub message: Vec<u8>,
+    // the child key the quorum signs under, empty for the root key
+    pub path: DerivationPath,
     pub local_sig: LocalSig,
 }
 
+impl SignMessage1 {
+    // the local signature under the child key of `derived`; the path travels with it so every
+    // member checks the local signature against the same child key
+    pub fn create(
+        message: &[u8],
+        local_ephemaral_key: &SharedKeys,
+        derived: &DerivedShare,
+    ) -> Result<SignMessage1, Error> {
+        if derived.shared_keys.y != derived.public_key.y {
+            return Err(InvalidKey);
+        }
+        Ok(SignMessage1 {
+            message: message.to_vec(),
+            path: derived.path.clone(),
+            local_sig: LocalSig::compute(message, local_ephemaral_key, &derived.shared_keys),
+        })
+    }
+
+    // checks that the local signature is for message under the child key of the path and
+    // returns that key, the Y of Signature::generate
+    pub fn verify_path(
+        &self,
+        message: &[u8],
+        xpub: &ExtendedPublicKey,
+        v: &GE,
+    ) -> Result<GE, Error> {
+        let (_, child) = xpub.derive(&self.path)?;
+        if self.message != message || self.local_sig.e != challenge(v, &child.y, message) {
+            return Err(InvalidSig);
+        }
+        Ok(child.y)
+    }
+}
+
 #[derive(Debug)]
 pub struct Parameters {
     pub threshold: usize,   //t
     pub share_count: usize, //n
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone)]
 pub struct SharedKeys {
     pub y: GE,
-    pub x_i: FE,
+    pub x_i: Secret<FE>,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone)]
 pub struct Share {
     pub id: String,
     pub shared_key: SharedKeys,
     pub vss_scheme_vec: Vec<VerifiableSS<GE>>,
 }
 
+impl SharedKeys {
+    // verifiable encrypted backup of x_i to a recovery key
+    pub fn backup(&self, recovery_public_key: &GE) -> EncryptedBackup {
+        backup::backup_share(&self.x_i, recovery_public_key)
+    }
+
+    // public_share is x_i*G, computed from the VSS commitments of the key generation
+    pub fn restore(
+        backup: &EncryptedBackup,
+        recovery_private_key: &FE,
+        y: &GE,
+        public_share: &GE,
+    ) -> Result<SharedKeys, Error> {
+        let (y, x_i) = backup::restore_share(backup, recovery_private_key, y, public_share)?;
+        Ok(SharedKeys { y, x_i })
+    }
+}
+
 impl Keys {
     pub fn phase1_create(index: usize) -> Keys {
         let u: FE = ECScalar::new_random();
         let y = &ECPoint::generator() * &u;
 
         Keys {
-            u_i: u,
+            u_i: Secret::new(u),
             y_i: y,
             party_index: index.clone(),
         }
     }
 
-    pub fn phase1_broadcast(&self) -> (KeyGenBroadcastMessage1, KeyGenBroadcastMessage2) {
+    pub fn phase1_broadcast(
+        &self,
+        session_id: &[u8],
+    ) -> (KeyGenBroadcastMessage1, KeyGenBroadcastMessage2) {
         let blind_factor = BigInt::sample(SECURITY);
         let com = HashCommitment::create_commitment_with_user_defined_randomness(
             &self.y_i.bytes_compressed_to_big_int(),
@@ -108,81 +181,231 @@ impl Keys {
         let decom1 = KeyGenBroadcastMessage2 {
             y_i: self.y_i,
             blind_factor,
+            proof: KeyProof::prove(self.u_i.expose(), &self.y_i, session_id, self.party_index),
         };
         (bcm1, decom1)
     }
 
+    // parties[i] is the index of the party that sent decom1_vec[i], bc1_vec[i] and the echoes
+    // com_echo_vec[i] and decom_echo_vec[i] of the two rounds; sender_key_vec[i] is its
+    // long-term key
+    #[allow(clippy::too_many_arguments)]
     pub fn phase1_verify_com_phase2_distribute(
         &self,
         params: &Parameters,
+        session_id: &[u8],
         decom1_vec: &Vec<KeyGenBroadcastMessage2>,
         bc1_vec: &Vec<KeyGenBroadcastMessage1>,
+        com_echo_vec: &[EchoMessage],
+        decom_echo_vec: &[EchoMessage],
+        sender_key_vec: &[GE],
         parties: &[usize],
-    ) -> Result<(VerifiableSS<GE>, Vec<FE>, usize), Error> {
+    ) -> Result<(VerifiableSS<GE>, Vec<FE>, usize), Blame> {
         // test length:
         assert_eq!(decom1_vec.len(), params.share_count);
         assert_eq!(bc1_vec.len(), params.share_count);
-        // test decommitments
-        let correct_key_correct_decom_all = (0..bc1_vec.len())
-            .map(|i| {
-                HashCommitment::create_commitment_with_user_defined_randomness(
-                    &decom1_vec[i].y_i.bytes_compressed_to_big_int(),
-                    &decom1_vec[i].blind_factor,
-                ) == bc1_vec[i].com
-            })
-            .all(|x| x == true);
-        /*
-        let (vss_scheme, secret_shares) = VerifiableSS::share_at_indices(
-            params.threshold,
-            params.share_count,
-            &self.u_i,
+        assert_eq!(parties.len(), params.share_count);
+        Keys::phase1_verify_com(
+            session_id,
+            decom1_vec,
+            bc1_vec,
+            com_echo_vec,
+            decom_echo_vec,
+            sender_key_vec,
             parties,
-        );
-        */
+        )?;
+
         let (vss_scheme, secret_shares) = VerifiableSS::share_at_indices(
             params.threshold,
             params.share_count,
-            &self.u_i,
+            self.u_i.expose(),
             &parties,
         );
+        Ok((vss_scheme, secret_shares, self.party_index.clone()))
+    }
 
-        match correct_key_correct_decom_all {
-            true => Ok((vss_scheme, secret_shares, self.party_index.clone())),
-            false => Err(InvalidKey),
+    // the checks of the commitment round, also used by key generations that share u_i at
+    // other points than the party indices
+    pub fn phase1_verify_com(
+        session_id: &[u8],
+        decom1_vec: &[KeyGenBroadcastMessage2],
+        bc1_vec: &[KeyGenBroadcastMessage1],
+        com_echo_vec: &[EchoMessage],
+        decom_echo_vec: &[EchoMessage],
+        sender_key_vec: &[GE],
+        parties: &[usize],
+    ) -> Result<(), Blame> {
+        assert_eq!(decom1_vec.len(), bc1_vec.len());
+        assert_eq!(parties.len(), bc1_vec.len());
+        // every party has to have received the same commitments and decommitments
+        verify_echoes(
+            session_id,
+            COMMITMENTS,
+            bc1_vec,
+            com_echo_vec,
+            sender_key_vec,
+            parties,
+        )?;
+        verify_echoes(
+            session_id,
+            DECOMMITMENTS,
+            decom1_vec,
+            decom_echo_vec,
+            sender_key_vec,
+            parties,
+        )?;
+        // test decommitments and proofs of knowledge
+        let bad_parties = (0..bc1_vec.len())
+            .filter(|i| {
+                let correct_decom = HashCommitment::create_commitment_with_user_defined_randomness(
+                    &decom1_vec[*i].y_i.bytes_compressed_to_big_int(),
+                    &decom1_vec[*i].blind_factor,
+                ) == bc1_vec[*i].com;
+                let correct_proof = decom1_vec[*i]
+                    .proof
+                    .verify(&decom1_vec[*i].y_i, session_id, parties[*i])
+                    .is_ok();
+                !(correct_decom && correct_proof)
+            })
+            .collect::<Vec<usize>>();
+        if bad_parties.is_empty() {
+            Ok(())
+        } else {
+            Err(Blame {
+                error: InvalidKey,
+                parties: bad_parties,
+            })
         }
     }
 
+    // encrypt secret_shares[j] to recipient_key_vec[j], the long-term key of party parties[j]
+    pub fn phase2_encrypt_shares(
+        &self,
+        session_id: &[u8],
+        vss_scheme: &VerifiableSS<GE>,
+        secret_shares: &[FE],
+        encryption_key: &EncryptionKey,
+        recipient_key_vec: &[GE],
+        parties: &[usize],
+    ) -> Vec<KeyGenMessage3> {
+        assert_eq!(secret_shares.len(), recipient_key_vec.len());
+        assert_eq!(secret_shares.len(), parties.len());
+        (0..secret_shares.len())
+            .map(|j| KeyGenMessage3 {
+                vss_scheme: vss_scheme.clone(),
+                sender_index: self.party_index,
+                encrypted_share: EncryptedShare::encrypt(
+                    &secret_shares[j],
+                    encryption_key,
+                    &recipient_key_vec[j],
+                    session_id,
+                    self.party_index,
+                    parties[j],
+                ),
+            })
+            .collect::<Vec<KeyGenMessage3>>()
+    }
+
+    // msg3_vec[i] is the message of party parties[i], with long-term key sender_key_vec[i] and
+    // key y_vec[i], and vss_echo_vec[i] its echo of the VSS commitments; a message that claims
+    // another sender index is blamed on its sender
+    #[allow(clippy::too_many_arguments)]
     pub fn phase2_verify_vss_construct_keypair(
         &self,
         params: &Parameters,
+        session_id: &[u8],
         y_vec: &Vec<GE>,
-        secret_shares_vec: &Vec<FE>,
-        vss_scheme_vec: &Vec<VerifiableSS<GE>>,
-        index: &usize,
-    ) -> Result<SharedKeys, Error> {
+        msg3_vec: &Vec<KeyGenMessage3>,
+        vss_echo_vec: &[EchoMessage],
+        encryption_key: &EncryptionKey,
+        sender_key_vec: &[GE],
+        parties: &[usize],
+    ) -> Result<SharedKeys, Blame> {
         assert_eq!(y_vec.len(), params.share_count);
-        assert_eq!(secret_shares_vec.len(), params.share_count);
-        assert_eq!(vss_scheme_vec.len(), params.share_count);
+        Keys::phase2_construct_keypair_at(
+            self.party_index,
+            session_id,
+            y_vec,
+            msg3_vec,
+            vss_echo_vec,
+            encryption_key,
+            sender_key_vec,
+            parties,
+        )
+    }
+
+    // the share at `point`, from the messages of the dealers parties[i] with key y_vec[i]
+    #[allow(clippy::too_many_arguments)]
+    pub fn phase2_construct_keypair_at(
+        point: usize,
+        session_id: &[u8],
+        y_vec: &[GE],
+        msg3_vec: &[KeyGenMessage3],
+        vss_echo_vec: &[EchoMessage],
+        encryption_key: &EncryptionKey,
+        sender_key_vec: &[GE],
+        parties: &[usize],
+    ) -> Result<SharedKeys, Blame> {
+        assert_eq!(msg3_vec.len(), y_vec.len());
+        assert_eq!(sender_key_vec.len(), y_vec.len());
+        assert_eq!(parties.len(), y_vec.len());
+        // the VSS commitments are the broadcast part of the messages
+        let vss_scheme_vec = msg3_vec
+            .iter()
+            .map(|msg3| msg3.vss_scheme.clone())
+            .collect::<Vec<VerifiableSS<GE>>>();
+        verify_echoes(
+            session_id,
+            VSS_COMMITMENTS,
+            &vss_scheme_vec,
+            vss_echo_vec,
+            sender_key_vec,
+            parties,
+        )?;
 
-        let correct_ss_verify = (0..y_vec.len())
+        let secret_shares_vec = (0..y_vec.len())
             .map(|i| {
-                vss_scheme_vec[i]
-                    .validate_share(&secret_shares_vec[i], *index)
-                    .is_ok()
-                    && vss_scheme_vec[i].commitments[0] == y_vec[i]
+                if msg3_vec[i].sender_index != parties[i] {
+                    return Err(InvalidSS);
+                }
+                msg3_vec[i].encrypted_share.decrypt(
+                    encryption_key,
+                    &sender_key_vec[i],
+                    session_id,
+                    parties[i],
+                    point,
+                )
             })
-            .all(|x| x == true);
-
-        match correct_ss_verify {
-            true => {
-                let mut y_vec_iter = y_vec.iter();
-                let y0 = y_vec_iter.next().unwrap();
-                let y = y_vec_iter.fold(y0.clone(), |acc, x| acc + x);
-                let x_i = secret_shares_vec.iter().fold(FE::zero(), |acc, x| acc + x);
-                Ok(SharedKeys { y, x_i })
-            }
-            false => Err(InvalidSS),
+            .collect::<Vec<Result<FE, Error>>>();
+        let bad_parties = (0..y_vec.len())
+            .filter(|i| match secret_shares_vec[*i] {
+                Ok(ref secret_share) => {
+                    msg3_vec[*i]
+                        .vss_scheme
+                        .validate_share(secret_share, point)
+                        .is_err()
+                        || msg3_vec[*i].vss_scheme.commitments[0] != y_vec[*i]
+                }
+                Err(_) => true,
+            })
+            .collect::<Vec<usize>>();
+        if !bad_parties.is_empty() {
+            return Err(Blame {
+                error: InvalidSS,
+                parties: bad_parties,
+            });
         }
+
+        let mut y_vec_iter = y_vec.iter();
+        let y0 = y_vec_iter.next().unwrap();
+        let y = y_vec_iter.fold(y0.clone(), |acc, x| acc + x);
+        let x_i = secret_shares_vec
+            .iter()
+            .fold(FE::zero(), |acc, x| acc + x.as_ref().unwrap());
+        Ok(SharedKeys {
+            y,
+            x_i: Secret::new(x_i),
+        })
     }
 
     // remove secret shares from x_i for parties that are not participating in signing
@@ -199,15 +422,15 @@ impl Keys {
         }
         SharedKeys {
             y: shared_key.y.clone(),
-            x_i: new_xi,
+            x_i: Secret::new(new_xi),
         }
     }
 }
 
 #[derive(Clone, Debug, Serialize, Deserialize, Copy)]
 pub struct LocalSig {
-    gamma_i: FE,
-    e: FE,
+    pub(crate) gamma_i: FE,
+    pub(crate) e: FE,
 }
 
 impl LocalSig {
@@ -216,27 +439,37 @@ impl LocalSig {
         local_ephemaral_key: &SharedKeys,
         local_private_key: &SharedKeys,
     ) -> LocalSig {
-        let beta_i = local_ephemaral_key.x_i.clone();
-        let alpha_i = local_private_key.x_i.clone();
-        /*
-        let hash_in_concat = local_ephemaral_key.y.bytes_compressed_to_big_int()
-            + (local_private_key.y.bytes_compressed_to_big_int() << 264)
-            + (BigInt::from_bytes(message) << 528);
-        let e_bn = HSha256::create_hash(&[&hash_in_concat]);
-        */
-        let e_bn = HSha256::create_hash(&[
-            &local_ephemaral_key.y.bytes_compressed_to_big_int(),
-            &local_private_key.y.bytes_compressed_to_big_int(),
-            &BigInt::from_bytes(message),
-        ]);
-
-        let e: FE = ECScalar::from(&e_bn);
+        let beta_i = *local_ephemaral_key.x_i.expose();
+        let alpha_i = *local_private_key.x_i.expose();
+        let e = challenge(&local_ephemaral_key.y, &local_private_key.y, message);
         let gamma_i = beta_i.sub(&(e.clone() * alpha_i).get_element());
         //   let gamma_i = e.clone() * alpha_i ;
 
         LocalSig { gamma_i, e }
     }
 
+    // checks a single local signature of the share at `party` against the public key
+    // packages of the key and of the ephemeral key: gamma_i*G + e*x_i*G = beta_i*G
+    pub fn verify(
+        &self,
+        message: &[u8],
+        party: usize,
+        key_package: &PublicKeyPackage,
+        eph_package: &PublicKeyPackage,
+    ) -> Result<(), Error> {
+        let (public_share, eph_public_share) =
+            verification_shares(key_package, eph_package, party)?;
+        if self.e != challenge(&eph_package.y, &key_package.y, message) {
+            return Err(InvalidSig);
+        }
+        let g: GE = GE::generator();
+        if g * &self.gamma_i + &(public_share * &self.e) == eph_public_share {
+            Ok(())
+        } else {
+            Err(InvalidSig)
+        }
+    }
+
     // section 4.2 step 3
     #[allow(unused_doc_comments)]
     pub fn verify_local_sigs(
@@ -248,6 +481,27 @@ impl LocalSig {
         //parties_index_vec is a vector with indices of the parties that are participating and provided gamma_i for this step
         // test that enough parties are in this round
         assert!(parties_index_vec.len() > vss_private_keys[0].parameters.threshold);
+        let quorum = SigningQuorum::new(
+            vss_private_keys[0].parameters.threshold,
+            vss_private_keys[0].parameters.share_count,
+            parties_index_vec,
+        )?;
+        LocalSig::verify_local_sigs_in_quorum(
+            gamma_vec,
+            &quorum,
+            vss_private_keys,
+            vss_ephemeral_keys,
+        )
+    }
+
+    // gamma_vec[k] is the local signature of the k-th member of the quorum
+    pub fn verify_local_sigs_in_quorum<Q: Interpolation>(
+        gamma_vec: &Vec<LocalSig>,
+        quorum: &Q,
+        vss_private_keys: &Vec<VerifiableSS<GE>>,
+        vss_ephemeral_keys: &Vec<VerifiableSS<GE>>,
+    ) -> Result<VerifiableSS<GE>, Error> {
+        assert_eq!(gamma_vec.len(), quorum.members().len());
 
         // Vec of joint commitments:
         // n' = num of signers, n - num of parties in keygen
@@ -280,11 +534,11 @@ impl LocalSig {
         };
 
         let g: GE = GE::generator();
-        let correct_ss_verify = (0..parties_index_vec.len())
+        let correct_ss_verify = (0..gamma_vec.len())
             .map(|i| {
                 let gamma_i_g = &g * &gamma_vec[i].gamma_i;
-                vss_sum
-                    .validate_share_public(&gamma_i_g, parties_index_vec[i] + 1)
+                quorum
+                    .validate_share_public(&vss_sum, &gamma_i_g, i)
                     .is_ok()
             })
             .collect::<Vec<bool>>();
@@ -303,54 +557,81 @@ pub struct Signature {
 }
 
 impl Signature {
-    pub fn generate(
+    // collects and checks local signatures as they arrive
+    pub fn aggregator(
+        message: &[u8],
+        key_package: &PublicKeyPackage,
+        eph_package: &PublicKeyPackage,
+    ) -> Result<SignatureAggregator<LocalSig, Signature>, Error> {
+        SignatureAggregator::new(
+            message,
+            key_package,
+            eph_package,
+            |local_sig: &LocalSig, message, party, key_package, eph_package| {
+                local_sig
+                    .verify(message, party, key_package, eph_package)
+                    .map(|_| local_sig.gamma_i)
+            },
+            |s, message, key_package, eph_package| Signature {
+                s,
+                e: challenge(&eph_package.y, &key_package.y, message),
+            },
+        )
+    }
+
+    // local_sig_vec[k] is the local signature of the k-th member of the quorum
+    pub fn generate<Q: Interpolation>(
         vss_sum_local_sigs: &VerifiableSS<GE>,
         local_sig_vec: &Vec<LocalSig>,
-        parties_index_vec: &[usize],
+        quorum: &Q,
         v: &GE,
         Y: &GE,
         message: &[u8],
-    ) -> Signature {
-        let gamma_vec = (0..parties_index_vec.len())
-            .map(|i| local_sig_vec[i].gamma_i.clone())
-            .collect::<Vec<FE>>();
-        let reconstruct_limit = vss_sum_local_sigs.parameters.threshold.clone() + 1;
-        let s = vss_sum_local_sigs.reconstruct(
-            &parties_index_vec[0..reconstruct_limit.clone()],
-            &gamma_vec[0..reconstruct_limit.clone()],
-        );
-        /*
-        let hash_in_concat = v.bytes_compressed_to_big_int()
-            + (Y.bytes_compressed_to_big_int() << 264)
-            + (BigInt::from_bytes(message) << 528);
-        let r = HSha256::create_hash(&[&hash_in_concat]);
-        */
-        let r = HSha256::create_hash(&[
-            &v.bytes_compressed_to_big_int(),
-            &Y.bytes_compressed_to_big_int(),
-            &BigInt::from_bytes(message),
-        ]);
-
-        Signature {
-            s,
-            e: ECScalar::from(&r),
+    ) -> Result<Signature, Blame> {
+        assert_eq!(local_sig_vec.len(), quorum.members().len());
+        // each local signature must be a share of the sum of the VSS schemes
+        let g: GE = GE::generator();
+        let bad_parties = (0..local_sig_vec.len())
+            .filter(|k| {
+                quorum
+                    .validate_share_public(
+                        vss_sum_local_sigs,
+                        &(g * &local_sig_vec[*k].gamma_i),
+                        *k,
+                    )
+                    .is_err()
+            })
+            .collect::<Vec<usize>>();
+        if !bad_parties.is_empty() {
+            return Err(Blame {
+                error: InvalidSS,
+                parties: bad_parties,
+            });
         }
+        let gamma_vec = local_sig_vec
+            .iter()
+            .map(|local_sig| local_sig.gamma_i)
+            .collect::<Vec<FE>>();
+        Ok(Signature {
+            s: quorum.interpolate(&gamma_vec),
+            e: challenge(v, Y, message),
+        })
     }
 
     pub fn verify(&self, message: &[u8], pubkey_y: &GE) -> Result<(), Error> {
-        let g: GE = GE::generator();
-        let sg = g * self.s;
-        let ey: GE = *pubkey_y * self.e;
-        let sg_plus_ey: GE = sg + ey;
-        /*
-        let hash_in_concat = sg_plus_ey.bytes_compressed_to_big_int()
-            + (pubkey_y.bytes_compressed_to_big_int() << 264)
-            + (BigInt::from_bytes(message) << 528);
-        let r = HSha256::create_hash(&[&hash_in_concat]);
-        */
+        let r = challenge(&self.commitment(pubkey_y), pubkey_y, message);
+        if r == self.e {
+            Ok(())
+        } else {
+            Err(InvalidSig)
+        }
+    }
 
+    // verify a signature made with the encoding used before transcript version 1;
+    // kept only to migrate existing signatures
+    pub fn verify_legacy(&self, message: &[u8], pubkey_y: &GE) -> Result<(), Error> {
         let r = HSha256::create_hash(&[
-            &sg_plus_ey.bytes_compressed_to_big_int(),
+            &self.commitment(pubkey_y).bytes_compressed_to_big_int(),
             &pubkey_y.bytes_compressed_to_big_int(),
             &BigInt::from_bytes(message),
         ]);
@@ -362,4 +643,21 @@ impl Signature {
             Err(InvalidSig)
         }
     }
+
+    // v = sG + eY
+    fn commitment(&self, pubkey_y: &GE) -> GE {
+        let g: GE = GE::generator();
+        let sg = g * self.s;
+        let ey: GE = *pubkey_y * self.e;
+        sg + ey
+    }
+}
+
+// e = H(R || Y || message)
+pub fn challenge(R: &GE, Y: &GE, message: &[u8]) -> FE {
+    let mut transcript = Transcript::new(b"threshold-zilliqa/challenge");
+    transcript.append_point(b"R", R);
+    transcript.append_point(b"Y", Y);
+    transcript.append_message(b"message", message);
+    transcript.challenge_scalar()
 }
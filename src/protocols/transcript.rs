@@ -0,0 +1,155 @@
+/*
+    Multisig Schnorr
+
+    Copyright 2018 by Kzen Networks
+
+    This file is part of Multisig Schnorr library
+    (https://github.com/KZen-networks/multisig-schnorr)
+
+    Multisig Schnorr is free software: you can redistribute
+    it and/or modify it under the terms of the GNU General Public
+    License as published by the Free Software Foundation, either
+    version 3 of the License, or (at your option) any later version.
+
+    @license GPL-3.0+ <https://github.com/KZen-networks/multisig-schnorr/blob/master/LICENSE>
+*/
+
+//! Injective encoding of hash inputs.
+//!
+//! Hashing `BigInt::from_bytes(message)` drops leading zero bytes, so `[0x00, 0x01]` and `[0x01]`
+//! hashed to the same challenge, and values of different kinds were concatenated without framing.
+//! A transcript starts with the library label, the encoding version and a domain tag; every
+//! appended item is a length-prefixed label followed by a length-prefixed value. Points are
+//! encoded as 33 compressed bytes and scalars / x coordinates as 32 big-endian bytes.
+//!
+//! Signatures produced with the old encoding can still be checked while they are migrated with
+//! the `verify_legacy` functions of `musig_three_rounds`, `bitcoin_schnorr` and `zilliqa_schnorr`.
+use curv::arithmetic::traits::Converter;
+use curv::cryptographic_primitives::hashing::hash_sha256::HSha256;
+use curv::cryptographic_primitives::hashing::traits::Hash;
+use curv::elliptic::curves::traits::*;
+use curv::BigInt;
+
+type GE = curv::elliptic::curves::secp256_k1::GE;
+type FE = curv::elliptic::curves::secp256_k1::FE;
+
+/// bumped whenever the encoding changes, so old and new transcripts never collide
+pub const TRANSCRIPT_VERSION: u8 = 1;
+const PROTOCOL_LABEL: &[u8] = b"multi-party-schnorr";
+
+pub const SCALAR_BYTES: usize = 32;
+pub const POINT_BYTES: usize = 33;
+
+#[derive(Clone, Debug)]
+pub struct Transcript {
+    bytes: Vec<u8>,
+}
+
+impl Transcript {
+    pub fn new(domain: &[u8]) -> Transcript {
+        let mut transcript = Transcript { bytes: Vec::new() };
+        transcript.append_framed(PROTOCOL_LABEL);
+        transcript.bytes.push(TRANSCRIPT_VERSION);
+        transcript.append_framed(domain);
+        transcript
+    }
+
+    fn append_framed(&mut self, data: &[u8]) {
+        self.bytes
+            .extend_from_slice(&(data.len() as u64).to_be_bytes());
+        self.bytes.extend_from_slice(data);
+    }
+
+    pub fn append_message(&mut self, label: &[u8], message: &[u8]) {
+        self.append_framed(label);
+        self.append_framed(message);
+    }
+
+    pub fn append_point(&mut self, label: &[u8], point: &GE) {
+        self.append_message(label, &point_bytes(point));
+    }
+
+    pub fn append_scalar(&mut self, label: &[u8], scalar: &FE) {
+        self.append_bn(label, &scalar.to_big_int());
+    }
+
+    /// for x coordinates and other values below 2^256
+    pub fn append_bn(&mut self, label: &[u8], n: &BigInt) {
+        self.append_message(label, &to_fixed_bytes(n, SCALAR_BYTES));
+    }
+
+    pub fn append_u64(&mut self, label: &[u8], n: u64) {
+        self.append_message(label, &n.to_be_bytes());
+    }
+
+    pub fn challenge(&self) -> BigInt {
+        HSha256::create_hash_from_slice(&self.bytes)
+    }
+
+    pub fn challenge_scalar(&self) -> FE {
+        ECScalar::from(&self.challenge())
+    }
+}
+
+pub fn point_bytes(point: &GE) -> Vec<u8> {
+    to_fixed_bytes(&point.bytes_compressed_to_big_int(), POINT_BYTES)
+}
+
+/// big-endian encoding left-padded to `width` bytes
+pub fn to_fixed_bytes(n: &BigInt, width: usize) -> Vec<u8> {
+    let bytes = BigInt::to_bytes(n);
+    assert!(bytes.len() <= width);
+    let mut padded = vec![0u8; width - bytes.len()];
+    padded.extend_from_slice(&bytes);
+    padded
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_leading_zero_bytes_are_kept() {
+        // the old encoding: both messages map to the same integer
+        let legacy_1 = HSha256::create_hash(&[&BigInt::from_bytes(&[0x00, 0x01])]);
+        let legacy_2 = HSha256::create_hash(&[&BigInt::from_bytes(&[0x01])]);
+        assert_eq!(legacy_1, legacy_2);
+
+        let mut transcript_1 = Transcript::new(b"test");
+        transcript_1.append_message(b"message", &[0x00, 0x01]);
+        let mut transcript_2 = Transcript::new(b"test");
+        transcript_2.append_message(b"message", &[0x01]);
+        assert_ne!(transcript_1.challenge(), transcript_2.challenge());
+    }
+
+    #[test]
+    fn test_items_are_framed() {
+        // moving bytes across an item boundary changes the transcript
+        let mut transcript_1 = Transcript::new(b"test");
+        transcript_1.append_message(b"a", &[0x01, 0x02]);
+        transcript_1.append_message(b"b", &[0x03]);
+        let mut transcript_2 = Transcript::new(b"test");
+        transcript_2.append_message(b"a", &[0x01]);
+        transcript_2.append_message(b"b", &[0x02, 0x03]);
+        assert_ne!(transcript_1.challenge(), transcript_2.challenge());
+
+        // and so does the domain
+        let mut transcript_3 = Transcript::new(b"other");
+        transcript_3.append_message(b"a", &[0x01, 0x02]);
+        transcript_3.append_message(b"b", &[0x03]);
+        assert_ne!(transcript_1.challenge(), transcript_3.challenge());
+    }
+
+    #[test]
+    fn test_fixed_width_encodings() {
+        let g: GE = ECPoint::generator();
+        assert_eq!(point_bytes(&g).len(), POINT_BYTES);
+        let one: FE = ECScalar::from(&BigInt::from(1));
+        let mut transcript_1 = Transcript::new(b"test");
+        transcript_1.append_scalar(b"x", &one);
+        let mut transcript_2 = Transcript::new(b"test");
+        transcript_2.append_bn(b"x", &BigInt::from(1));
+        assert_eq!(transcript_1.challenge(), transcript_2.challenge());
+        assert_eq!(to_fixed_bytes(&BigInt::from(1), 4), vec![0, 0, 0, 1]);
+    }
+}